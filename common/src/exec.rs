@@ -0,0 +1,47 @@
+//! Wire format of interactive exec sessions, shared by the runtime API, the
+//! executor and `reznctl`.
+//!
+//! Sessions run over a WebSocket. Binary frames carry terminal data, the first
+//! byte naming the stream ([`STDIN`], [`STDOUT`] or [`STDERR`]) and the rest
+//! being raw bytes. Text frames carry one JSON [`ExecControl`] message. The
+//! client opens with `start` and may send `resize` at any time; an empty stdin
+//! frame closes the process's stdin. The server ends with `exit` (or `error`)
+//! and closes.
+
+use serde::{Deserialize, Serialize};
+
+pub const STDIN: u8 = 0;
+pub const STDOUT: u8 = 1;
+pub const STDERR: u8 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ExecControl {
+    Start(ExecStart),
+    Resize { cols: u16, rows: u16 },
+    Exit { code: Option<i64> },
+    Error { message: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExecStart {
+    /// Container to attach to; the runtime picks a replica when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replica: Option<String>,
+    pub command: Vec<String>,
+    /// Allocate a pseudo-terminal. stdout and stderr are merged when set.
+    #[serde(default)]
+    pub tty: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u16>,
+}
+
+/// A binary frame carrying `data` on `stream`.
+pub fn frame(stream: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 1);
+    out.push(stream);
+    out.extend_from_slice(data);
+    out
+}
@@ -1 +1,3 @@
+pub mod exec;
+pub mod signing;
 pub mod types;
@@ -0,0 +1,386 @@
+//! Signed runtime → executor commands.
+//!
+//! The runtime signs every state-changing request with its ed25519 key. The
+//! signature covers the method, path, a timestamp, a random nonce and the
+//! SHA-256 of the body:
+//!
+//! ```text
+//! rezn-command-v1\n<METHOD>\n<path>\n<timestamp>\n<nonce>\n<sha256(body) hex>
+//! ```
+//!
+//! and travels in the `X-Rezn-*` headers below. Executors check it with
+//! [`CommandVerifier`], which also rejects stale timestamps and reused nonces.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+pub const KEY_HEADER: &str = "x-rezn-key";
+pub const SIGNATURE_HEADER: &str = "x-rezn-signature";
+pub const NONCE_HEADER: &str = "x-rezn-nonce";
+pub const TIMESTAMP_HEADER: &str = "x-rezn-timestamp";
+
+/// Accepted clock difference between runtime and executor, in seconds.
+pub const DEFAULT_MAX_SKEW: u64 = 30;
+
+const DOMAIN: &str = "rezn-command-v1";
+
+/// The signature headers attached to one request.
+#[derive(Debug, Clone)]
+pub struct CommandSignature {
+    /// Base64 ed25519 public key of the signer.
+    pub key: String,
+    /// Base64 ed25519 signature.
+    pub signature: String,
+    pub nonce: String,
+    /// Unix seconds.
+    pub timestamp: i64,
+}
+
+impl CommandSignature {
+    pub fn headers(&self) -> [(&'static str, String); 4] {
+        [
+            (KEY_HEADER, self.key.clone()),
+            (SIGNATURE_HEADER, self.signature.clone()),
+            (NONCE_HEADER, self.nonce.clone()),
+            (TIMESTAMP_HEADER, self.timestamp.to_string()),
+        ]
+    }
+
+    /// Collect the signature headers through `get`, which looks up a header
+    /// value by (lowercase) name in whatever HTTP library the caller uses.
+    pub fn from_headers<'a>(get: impl Fn(&str) -> Option<&'a str>) -> Result<Self, VerifyError> {
+        let header = |name: &'static str| get(name).ok_or(VerifyError::MissingHeader(name));
+
+        Ok(Self {
+            key: header(KEY_HEADER)?.to_string(),
+            signature: header(SIGNATURE_HEADER)?.to_string(),
+            nonce: header(NONCE_HEADER)?.to_string(),
+            timestamp: header(TIMESTAMP_HEADER)?
+                .parse()
+                .map_err(|_| VerifyError::Malformed("timestamp"))?,
+        })
+    }
+}
+
+/// Bytes covered by the signature.
+pub fn signing_payload(
+    method: &str,
+    path: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> Vec<u8> {
+    format!(
+        "{DOMAIN}\n{}\n{path}\n{timestamp}\n{nonce}\n{}",
+        method.to_ascii_uppercase(),
+        hex::encode(Sha256::digest(body))
+    )
+    .into_bytes()
+}
+
+/// Signs commands with the runtime's identity.
+pub struct CommandSigner {
+    key: SigningKey,
+}
+
+impl CommandSigner {
+    pub fn new(key: SigningKey) -> Self {
+        Self { key }
+    }
+
+    pub fn generate() -> Self {
+        Self::new(SigningKey::generate(&mut rand::rngs::OsRng))
+    }
+
+    /// Parse a base64-encoded 32-byte ed25519 secret key.
+    pub fn from_base64(encoded: &str) -> Result<Self, VerifyError> {
+        let bytes = general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|_| VerifyError::Malformed("signing key"))?;
+        let secret: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| VerifyError::Malformed("signing key"))?;
+        Ok(Self::new(SigningKey::from_bytes(&secret)))
+    }
+
+    pub fn to_base64(&self) -> String {
+        general_purpose::STANDARD.encode(self.key.to_bytes())
+    }
+
+    /// Base64 public key, the value executors add to their trusted keys.
+    pub fn public_key(&self) -> String {
+        general_purpose::STANDARD.encode(self.key.verifying_key().as_bytes())
+    }
+
+    pub fn sign(&self, method: &str, path: &str, body: &[u8]) -> CommandSignature {
+        let timestamp = unix_now();
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        let payload = signing_payload(method, path, timestamp, &nonce, body);
+
+        CommandSignature {
+            key: self.public_key(),
+            signature: general_purpose::STANDARD.encode(self.key.sign(&payload).to_bytes()),
+            nonce,
+            timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    MissingHeader(&'static str),
+    Malformed(&'static str),
+    UntrustedKey,
+    BadSignature,
+    Expired,
+    Replayed,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHeader(h) => write!(f, "missing {h} header"),
+            Self::Malformed(what) => write!(f, "malformed {what}"),
+            Self::UntrustedKey => write!(f, "command signed by an untrusted key"),
+            Self::BadSignature => write!(f, "invalid command signature"),
+            Self::Expired => write!(f, "command timestamp outside the accepted window"),
+            Self::Replayed => write!(f, "command nonce was already used"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Executor-side check of signed commands.
+///
+/// Nonces are remembered for the skew window, so a captured request cannot be
+/// replayed while its timestamp is still acceptable.
+pub struct CommandVerifier {
+    trusted: Vec<VerifyingKey>,
+    max_skew: u64,
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl CommandVerifier {
+    /// `trusted` holds base64 ed25519 public keys of the runtimes allowed to
+    /// issue commands.
+    pub fn new<S: AsRef<str>>(trusted: &[S]) -> Result<Self, VerifyError> {
+        let trusted = trusted
+            .iter()
+            .map(|k| decode_verifying_key(k.as_ref()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            trusted,
+            max_skew: DEFAULT_MAX_SKEW,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn with_max_skew(mut self, secs: u64) -> Self {
+        self.max_skew = secs;
+        self
+    }
+
+    pub fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        sig: &CommandSignature,
+    ) -> Result<(), VerifyError> {
+        let key = decode_verifying_key(&sig.key)?;
+        if !self.trusted.contains(&key) {
+            return Err(VerifyError::UntrustedKey);
+        }
+
+        let now = unix_now();
+        if now.abs_diff(sig.timestamp) > self.max_skew {
+            return Err(VerifyError::Expired);
+        }
+
+        let sig_bytes = general_purpose::STANDARD
+            .decode(&sig.signature)
+            .map_err(|_| VerifyError::Malformed("signature"))?;
+        let sig_array: [u8; 64] = sig_bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| VerifyError::Malformed("signature"))?;
+
+        let payload = signing_payload(method, path, sig.timestamp, &sig.nonce, body);
+        key.verify(&payload, &Signature::from_bytes(&sig_array))
+            .map_err(|_| VerifyError::BadSignature)?;
+
+        // Only remember nonces of genuine commands, so garbage cannot fill the cache.
+        let mut seen = self.seen.lock().unwrap();
+        let max_skew = self.max_skew as i64;
+        seen.retain(|_, ts| now - *ts <= max_skew);
+        if seen.insert(sig.nonce.clone(), sig.timestamp).is_some() {
+            return Err(VerifyError::Replayed);
+        }
+
+        Ok(())
+    }
+}
+
+fn decode_verifying_key(encoded: &str) -> Result<VerifyingKey, VerifyError> {
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|_| VerifyError::Malformed("public key"))?;
+    let array: [u8; 32] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| VerifyError::Malformed("public key"))?;
+    VerifyingKey::from_bytes(&array).map_err(|_| VerifyError::Malformed("public key"))
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"name":"web"}"#;
+
+    /// A signature as `sign` makes it, with the timestamp and nonce chosen.
+    fn sign_at(signer: &CommandSigner, timestamp: i64, nonce: &str) -> CommandSignature {
+        let payload = signing_payload("POST", "/containers", timestamp, nonce, BODY);
+        CommandSignature {
+            key: signer.public_key(),
+            signature: general_purpose::STANDARD.encode(signer.key.sign(&payload).to_bytes()),
+            nonce: nonce.to_string(),
+            timestamp,
+        }
+    }
+
+    fn verifier(signer: &CommandSigner) -> CommandVerifier {
+        CommandVerifier::new(&[signer.public_key()]).unwrap()
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let signer = CommandSigner::generate();
+        let sig = signer.sign("post", "/containers", BODY);
+
+        assert_eq!(
+            verifier(&signer).verify("POST", "/containers", BODY, &sig),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn signatures_survive_the_headers() {
+        let signer = CommandSigner::generate();
+        let sig = signer.sign("POST", "/containers", BODY);
+        let headers: HashMap<&str, String> = sig.headers().into_iter().collect();
+
+        let parsed = CommandSignature::from_headers(|h| headers.get(h).map(String::as_str));
+        assert_eq!(
+            verifier(&signer).verify("POST", "/containers", BODY, &parsed.unwrap()),
+            Ok(())
+        );
+        assert_eq!(
+            CommandSignature::from_headers(|_| None).unwrap_err(),
+            VerifyError::MissingHeader(KEY_HEADER)
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_commands() {
+        let signer = CommandSigner::generate();
+        let verifier = verifier(&signer);
+        let sig = signer.sign("POST", "/containers", BODY);
+
+        let cases: [(&str, &str, &[u8]); 3] = [
+            ("POST", "/containers", br#"{"name":"evil"}"#),
+            ("DELETE", "/containers", BODY),
+            ("POST", "/containers/web/exec", BODY),
+        ];
+        for (method, path, body) in cases {
+            assert_eq!(
+                verifier.verify(method, path, body, &sig),
+                Err(VerifyError::BadSignature),
+                "{method} {path}"
+            );
+        }
+
+        let mut garbled = sig.clone();
+        garbled.signature = "not base64!".into();
+        assert_eq!(
+            verifier.verify("POST", "/containers", BODY, &garbled),
+            Err(VerifyError::Malformed("signature"))
+        );
+    }
+
+    #[test]
+    fn rejects_untrusted_keys() {
+        let trusted = CommandSigner::generate();
+        let intruder = CommandSigner::generate();
+        let sig = intruder.sign("POST", "/containers", BODY);
+
+        assert_eq!(
+            verifier(&trusted).verify("POST", "/containers", BODY, &sig),
+            Err(VerifyError::UntrustedKey)
+        );
+
+        // Claiming the trusted key does not help without its signature.
+        let mut forged = sig;
+        forged.key = trusted.public_key();
+        assert_eq!(
+            verifier(&trusted).verify("POST", "/containers", BODY, &forged),
+            Err(VerifyError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_timestamps_outside_the_skew_window() {
+        let signer = CommandSigner::generate();
+        let verifier = verifier(&signer).with_max_skew(30);
+        let now = unix_now();
+
+        for (offset, expected) in [
+            (-31, Err(VerifyError::Expired)),
+            (31, Err(VerifyError::Expired)),
+            (-25, Ok(())),
+            (25, Ok(())),
+        ] {
+            let sig = sign_at(&signer, now + offset, &format!("nonce{offset}"));
+            assert_eq!(
+                verifier.verify("POST", "/containers", BODY, &sig),
+                expected,
+                "offset {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_replayed_nonces() {
+        let signer = CommandSigner::generate();
+        let verifier = verifier(&signer);
+        let sig = signer.sign("POST", "/containers", BODY);
+
+        assert_eq!(verifier.verify("POST", "/containers", BODY, &sig), Ok(()));
+        assert_eq!(
+            verifier.verify("POST", "/containers", BODY, &sig),
+            Err(VerifyError::Replayed)
+        );
+
+        // A fresh nonce with the same content is a new command.
+        let again = signer.sign("POST", "/containers", BODY);
+        assert_eq!(verifier.verify("POST", "/containers", BODY, &again), Ok(()));
+    }
+}
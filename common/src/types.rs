@@ -31,7 +31,34 @@ pub struct InstructionMeta {
 #[serde(untagged)]
 pub enum EnvVar {
     Raw(String),
-    FromSource { from: EnvSource, name: String },
+    FromSource {
+        from: EnvSource,
+        name: String,
+        /// Pin a secret version; the current one when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+        /// Write the value to this path in the container and set the
+        /// variable to the path instead, for binary secrets. Like
+        /// [`SecretFile`], the file's directory becomes an in-memory mount.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file: Option<String>,
+    },
+}
+
+impl EnvVar {
+    /// The secret this reads at its current version, if any. Pinned versions
+    /// never change underneath a pod.
+    pub fn unpinned_secret(&self) -> Option<&str> {
+        match self {
+            EnvVar::FromSource {
+                from: EnvSource::Secret,
+                name,
+                version: None,
+                ..
+            } => Some(name),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -58,17 +85,185 @@ pub struct Instruction {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PodFields {
+    /// `name[:tag]` or `name@sha256:<digest>`.
     pub image: String,
+    pub pull_policy: Option<PullPolicy>,
     pub replicas: usize,
     pub ports: Vec<u16>,
     pub secure: Option<bool>,
     pub env: Option<EnvMap>,
+    /// Only schedule replicas on nodes carrying all of these labels.
+    pub node_selector: Option<HashMap<String, String>>,
+    pub autoscale: Option<AutoscaleFields>,
+    /// Pods in the same molecule that must be up before this one starts.
+    pub depends_on: Option<Vec<PodDependency>>,
+    /// Seconds a replica gets to exit after SIGTERM before it is killed.
+    pub stop_grace_period: Option<u64>,
+    /// Runs before a replica is stopped; counts against the grace period.
+    pub pre_stop: Option<PreStopHook>,
+    /// Roll the replicas when a secret the env reads at its current version
+    /// changes (default true).
+    pub restart_on_secret_change: Option<bool>,
+    /// Secrets mounted as files on in-memory filesystems.
+    pub secret_files: Option<Vec<SecretFile>>,
+}
+
+/// A secret delivered to a pod as a file rather than through its env.
+///
+/// The directory holding the file becomes an in-memory mount with only the
+/// pod's secret files in it. Unpinned files are rewritten in place when the
+/// secret changes; the replicas keep running.
+///
+/// Files belong to the user the runtime (or executor) runs as, and the
+/// default mode lets only that user read them. A container running as
+/// another user needs `uid`/`gid` set to its own, or a mode that lets its
+/// group or everyone read.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct SecretFile {
+    /// Secret key.
+    pub secret: String,
+    /// Absolute path of the file in the container.
+    pub path: String,
+    /// Octal permissions, e.g. `"0440"` (default `"0400"`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Owner of the file, as seen from inside the container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Group of the file, as seen from inside the container.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Pin a secret version; the current one when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+}
+
+/// When the executor fetches a pod's image from the registry.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullPolicy {
+    /// Pull before every start, picking up a moved tag.
+    Always,
+    /// Pull only when the node does not have the image yet.
+    #[default]
+    IfNotPresent,
+    /// Never pull; starting fails if the image is missing.
+    Never,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PreStopHook {
+    /// Run a command inside the container.
+    Exec { command: Vec<String> },
+    /// Send `GET path` to the host port published for container `port`.
+    Http { path: String, port: u16 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(untagged)]
+pub enum PodDependency {
+    Name(String),
+    Gated {
+        pod: String,
+        #[serde(default)]
+        condition: DependencyCondition,
+    },
+}
+
+impl PodDependency {
+    pub fn pod(&self) -> &str {
+        match self {
+            PodDependency::Name(pod) | PodDependency::Gated { pod, .. } => pod,
+        }
+    }
+
+    pub fn condition(&self) -> DependencyCondition {
+        match self {
+            PodDependency::Name(_) => DependencyCondition::Started,
+            PodDependency::Gated { condition, .. } => *condition,
+        }
+    }
+}
+
+/// When a dependency counts as satisfied.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyCondition {
+    /// All desired replicas exist.
+    #[default]
+    Started,
+    /// All desired replicas are running and not reported unhealthy.
+    Ready,
+}
+
+/// Horizontal autoscaling bounds and targets for a pod.
+///
+/// Utilisation is averaged over the pod's replicas; at least one target
+/// must be set for the pod to scale.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct AutoscaleFields {
+    pub min_replicas: usize,
+    pub max_replicas: usize,
+    /// Target average CPU usage per replica, in percent.
+    pub target_cpu: Option<f64>,
+    /// Target average peak memory per replica, in bytes.
+    pub target_mem: Option<u64>,
+    /// Seconds of recommendations considered before scaling up (default 0).
+    pub scale_up_window: Option<u64>,
+    /// Seconds of recommendations considered before scaling down (default 300).
+    pub scale_down_window: Option<u64>,
+    /// Minimum seconds between two scaling decisions (default 60).
+    pub cooldown: Option<u64>,
+}
+
+/// A run-to-completion workload, started once per applied program.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobFields {
+    pub image: String,
+    pub command: Option<Vec<String>>,
+    pub env: Option<EnvMap>,
+    /// How many times a failed run is retried (default 0).
+    pub retries: Option<u32>,
+    pub node_selector: Option<HashMap<String, String>>,
+}
+
+/// Creates a job run on a cron schedule.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CronFields {
+    /// Standard 5-field cron expression, or 6/7 fields with seconds and year.
+    pub schedule: String,
+    pub concurrency_policy: Option<ConcurrencyPolicy>,
+    #[serde(flatten)]
+    pub job: JobFields,
+}
+
+/// What to do when a cron fires while its previous run is still active.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcurrencyPolicy {
+    /// Start the new run alongside the old one.
+    Allow,
+    /// Skip the new run.
+    #[default]
+    Forbid,
+    /// Stop the old run and start the new one.
+    Replace,
 }
 
 pub struct PodSpec {
     pub mol_name: String,
     pub name: String,
     pub image: String,
+    pub pull_policy: PullPolicy,
     pub replicas: usize,
     pub ports: Vec<u16>,
+    pub env: HashMap<String, EnvVar>,
+    pub node_selector: HashMap<String, String>,
+    pub autoscale: Option<AutoscaleFields>,
+    pub depends_on: Vec<PodDependency>,
+    pub stop_grace_period: Option<u64>,
+    pub pre_stop: Option<PreStopHook>,
+    pub restart_on_secret_change: bool,
+    pub secret_files: Vec<SecretFile>,
 }
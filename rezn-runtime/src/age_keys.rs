@@ -1,69 +1,527 @@
-use age::x25519;
+//! The runtime's age identity and its rotation.
+//!
+//! The current identity lives at `REZN_AGE_IDENTITY` (default `identity.txt`),
+//! in plain text or, when `REZN_AGE_PASSPHRASE` or `REZN_AGE_PASSPHRASE_FILE`
+//! is set, as a passphrase-encrypted age file. It can also be supplied from
+//! outside, via `REZN_AGE_IDENTITY_KEY` or by passing its number as
+//! `REZN_AGE_IDENTITY_FD`, in which case it cannot be rotated here. A missing
+//! identity file is only generated with `REZN_AGE_GENERATE=true`; its public
+//! key goes to `<path>.pub`.
+//!
+//! Rotating writes the new identity to `<path>.next` first, re-encrypts the
+//! secret store, then moves the old one aside as `<path>.retired-<unix>` and
+//! the new one into place. Retired identities keep decrypting for
+//! `REZN_AGE_ROTATION_WINDOW` seconds (default 86400); after that they are
+//! archived as `<path>.archived-<unix>` or, with
+//! `REZN_AGE_RETIRED_KEYS=destroy`, overwritten and deleted.
+//!
+//! Secrets are also encrypted to the extra recipients listed in
+//! `REZN_AGE_RECIPIENTS_FILE` (one `age1…` key per line, `#` comments) and
+//! `REZN_AGE_RECIPIENTS` (comma separated), e.g. an offline escrow key.
+//!
+//! In sealed mode (see [`crate::seal`]) the identity files hold the identity
+//! encrypted under the unseal key instead of in plain text.
+
+use age::{scrypt, x25519};
 use anyhow::{anyhow, Context, Result};
-use once_cell::sync::OnceCell;
-use secrecy::ExposeSecret;
+use chrono::{DateTime, Duration, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use zeroize::Zeroizing;
+
+use crate::seal::{MasterKey, SealedIdentity};
 use std::{
+    env,
     fs::{self, OpenOptions},
     io::Write,
     os::unix::fs::OpenOptionsExt,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-static IDENTITY: OnceCell<x25519::Identity> = OnceCell::new();
+/// Seconds a retired identity keeps decrypting, unless
+/// `REZN_AGE_ROTATION_WINDOW` says otherwise.
+const DEFAULT_ROTATION_WINDOW: i64 = 86_400;
 
-/// Public entry-point: grab the singleton private key.
-pub fn get_identity() -> Result<&'static x25519::Identity> {
-    IDENTITY.get_or_try_init(load_or_generate_identity)
+const RETIRED_MARKER: &str = ".retired-";
+
+/// An identity replaced by a rotation, still accepted for decryption.
+#[derive(Clone)]
+pub struct RetiredIdentity {
+    pub identity: x25519::Identity,
+    pub retired_at: DateTime<Utc>,
 }
 
-/// Try to load an existing key from disk; otherwise generate + persist it.
-fn load_or_generate_identity() -> Result<x25519::Identity> {
+/// The identity secrets are encrypted to, plus the ones they may still be
+/// encrypted to.
+#[derive(Clone)]
+pub struct Keyring {
+    pub current: x25519::Identity,
+    pub retired: Vec<RetiredIdentity>,
+}
+
+impl Keyring {
+    /// Every identity that may decrypt, current first.
+    pub fn identities(&self) -> impl Iterator<Item = &x25519::Identity> {
+        std::iter::once(&self.current).chain(self.retired.iter().map(|r| &r.identity))
+    }
+}
+
+/// What to do with a retired identity once its window has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetiredKeyPolicy {
+    Archive,
+    Destroy,
+}
+
+impl RetiredKeyPolicy {
+    pub fn from_env() -> Self {
+        match env::var("REZN_AGE_RETIRED_KEYS").as_deref() {
+            Ok("destroy") => Self::Destroy,
+            _ => Self::Archive,
+        }
+    }
+}
+
+pub fn rotation_window() -> Duration {
+    let secs = env::var("REZN_AGE_ROTATION_WINDOW")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_ROTATION_WINDOW);
+    Duration::seconds(secs.max(0))
+}
+
+fn identity_path() -> PathBuf {
     // Let admins override the path if they really need to.
-    let id_path = std::env::var("REZN_AGE_IDENTITY")
+    env::var("REZN_AGE_IDENTITY")
         .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from("identity.txt"));
+        .unwrap_or_else(|_| PathBuf::from("identity.txt"))
+}
+
+/// Where the current identity comes from.
+enum IdentitySource {
+    File(PathBuf),
+    Env,
+    Fd(u32),
+}
 
+impl IdentitySource {
+    /// `REZN_AGE_IDENTITY_KEY` wins over `REZN_AGE_IDENTITY_FD`, which wins
+    /// over the identity file.
+    fn from_env() -> Result<Self> {
+        if env::var_os("REZN_AGE_IDENTITY_KEY").is_some() {
+            return Ok(Self::Env);
+        }
+        if let Ok(fd) = env::var("REZN_AGE_IDENTITY_FD") {
+            let fd = fd
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| anyhow!("REZN_AGE_IDENTITY_FD is not a file descriptor: '{fd}'"))?;
+            return Ok(Self::Fd(fd));
+        }
+        Ok(Self::File(identity_path()))
+    }
+
+    /// Rotation and sealing rewrite the identity file, so they need one.
+    fn require_file(self) -> Result<PathBuf> {
+        match self {
+            Self::File(path) => Ok(path),
+            Self::Env => Err(anyhow!(
+                "the age identity comes from REZN_AGE_IDENTITY_KEY; change it there"
+            )),
+            Self::Fd(fd) => Err(anyhow!(
+                "the age identity is read from file descriptor {fd}; change it at its source"
+            )),
+        }
+    }
+}
+
+fn generate_enabled() -> bool {
+    matches!(
+        env::var("REZN_AGE_GENERATE").as_deref(),
+        Ok("1" | "true" | "yes")
+    )
+}
+
+/// The passphrase protecting identity files, if one is configured.
+fn passphrase() -> Result<Option<SecretString>> {
+    if let Ok(path) = env::var("REZN_AGE_PASSPHRASE_FILE") {
+        let raw =
+            Zeroizing::new(fs::read_to_string(&path).with_context(|| format!("reading {path}"))?);
+        return Ok(Some(SecretString::from(
+            raw.trim_end_matches(['\r', '\n']).to_string(),
+        )));
+    }
+    Ok(env::var("REZN_AGE_PASSPHRASE").ok().map(SecretString::from))
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Load the current identity together with the retired ones still inside
+/// the rotation window. `master` opens sealed identity files.
+pub fn load_keyring(master: Option<&MasterKey>) -> Result<Keyring> {
+    let id_path = identity_path();
+    let current = match IdentitySource::from_env()? {
+        IdentitySource::File(path) => load_or_generate_identity(&path, master)?,
+        IdentitySource::Env => {
+            let raw = Zeroizing::new(env::var("REZN_AGE_IDENTITY_KEY")?.into_bytes());
+            decode_identity(&raw, "REZN_AGE_IDENTITY_KEY", master)?
+        }
+        IdentitySource::Fd(fd) => {
+            let path = PathBuf::from(format!("/dev/fd/{fd}"));
+            let raw = Zeroizing::new(
+                fs::read(&path).with_context(|| format!("reading file descriptor {fd}"))?,
+            );
+            decode_identity(&raw, &format!("file descriptor {fd}"), master)?
+        }
+    };
+    let mut retired = load_retired(&id_path, master)?;
+
+    let next = sibling(&id_path, ".next");
+    if next.exists() {
+        tracing::warn!(
+            "[age] {} left behind by an interrupted rotation; keeping it for decryption, \
+             rotate again to finish",
+            next.display()
+        );
+        retired.push(RetiredIdentity {
+            identity: read_identity(&next, master)?,
+            retired_at: Utc::now(),
+        });
+    }
+
+    Ok(Keyring { current, retired })
+}
+
+/// Load the key from disk; generate + persist it if that is enabled.
+fn load_or_generate_identity(
+    id_path: &Path,
+    master: Option<&MasterKey>,
+) -> Result<x25519::Identity> {
     if id_path.exists() {
-        // ----- Load ---------------------------------------------------
-        let raw = fs::read_to_string(&id_path)
-            .with_context(|| format!("reading {}", id_path.display()))?;
-
-        raw.lines()
-            .find(|l| l.trim_start().starts_with("AGE-SECRET-KEY-1"))
-            .ok_or_else(|| anyhow!("no age identity in file"))?
-            .trim()
-            .parse::<x25519::Identity>()
-            .map_err(|e| anyhow!("parsing age identity failed: {e}"))
+        read_identity(id_path, master)
+    } else if !generate_enabled() {
+        Err(anyhow!(
+            "no age identity at {}; provide one, or set REZN_AGE_GENERATE=true to generate it",
+            id_path.display()
+        ))
     } else {
-        // ----- Generate ----------------------------------------------
         if let Some(dir) = id_path.parent() {
             fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
         }
 
         let id = x25519::Identity::generate();
+        write_identity(id_path, &id, master)?;
+        write_public(id_path, &id)?;
+        tracing::info!("[age] Generated identity {}", id.to_public());
+
+        Ok(id)
+    }
+}
+
+/// The `(threshold, shares)` split the identity is sealed with, or `None`
+/// for a plain, externally supplied or not yet generated identity.
+pub fn sealed_split() -> Result<Option<(u8, u8)>> {
+    let IdentitySource::File(id_path) = IdentitySource::from_env()? else {
+        return Ok(None);
+    };
+    if !id_path.exists() {
+        return Ok(None);
+    }
+    let raw = fs::read(&id_path).with_context(|| format!("reading {}", id_path.display()))?;
+    Ok(std::str::from_utf8(&raw)
+        .ok()
+        .and_then(SealedIdentity::parse)
+        .map(|s| (s.threshold, s.shares)))
+}
+
+fn read_identity(path: &Path, master: Option<&MasterKey>) -> Result<x25519::Identity> {
+    let raw =
+        Zeroizing::new(fs::read(path).with_context(|| format!("reading {}", path.display()))?);
+    decode_identity(&raw, &path.display().to_string(), master)
+}
 
-        // write private key (0600)
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .mode(0o600)
-            .open(&id_path)?
-            .write_all(id.to_string().expose_secret().as_bytes())?;
-
-        // write public key (0644)
-        let pub_path = PathBuf::from("default.txt");
-        if let Some(dir) = pub_path.parent() {
-            fs::create_dir_all(dir)?;
+/// Parse an identity that may be sealed, passphrase-encrypted or plain.
+fn decode_identity(
+    raw: &[u8],
+    origin: &str,
+    master: Option<&MasterKey>,
+) -> Result<x25519::Identity> {
+    let text = if raw.starts_with(b"age-encryption.org/")
+        || raw.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+    {
+        let passphrase = passphrase()?.ok_or_else(|| {
+            anyhow!(
+                "{origin} is passphrase-protected; set REZN_AGE_PASSPHRASE or \
+                 REZN_AGE_PASSPHRASE_FILE"
+            )
+        })?;
+        let plain = age::decrypt(&scrypt::Identity::new(passphrase), raw)
+            .map_err(|e| anyhow!("decrypting {origin} failed: {e}"))?;
+        Zeroizing::new(
+            String::from_utf8(plain).map_err(|_| anyhow!("identity in {origin} is not text"))?,
+        )
+    } else {
+        let text = std::str::from_utf8(raw).map_err(|_| anyhow!("{origin} is not text"))?;
+        match SealedIdentity::parse(text) {
+            Some(sealed) => {
+                let master = master.ok_or_else(|| anyhow!("{origin} is sealed; unseal first"))?;
+                let plain = master
+                    .open(&sealed)
+                    .with_context(|| format!("opening {origin}"))?;
+                Zeroizing::new(
+                    String::from_utf8(plain.to_vec())
+                        .map_err(|_| anyhow!("sealed identity in {origin} is not text"))?,
+                )
+            }
+            None => Zeroizing::new(text.to_string()),
         }
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .mode(0o644)
-            .open(&pub_path)?
-            .write_all(id.to_public().to_string().as_bytes())?;
-
-        Ok(id) // ← this branch returns Result<Identity, Error>
+    };
+
+    text.lines()
+        .find(|l| l.trim_start().starts_with("AGE-SECRET-KEY-1"))
+        .ok_or_else(|| anyhow!("no age identity in {origin}"))?
+        .trim()
+        .parse::<x25519::Identity>()
+        .map_err(|e| anyhow!("parsing age identity in {origin} failed: {e}"))
+}
+
+/// Write a private key (0600), sealed under `master` if given, otherwise
+/// passphrase-encrypted if a passphrase is configured.
+fn write_identity(path: &Path, id: &x25519::Identity, master: Option<&MasterKey>) -> Result<()> {
+    let secret = id.to_string();
+    let contents = match (master, passphrase()?) {
+        (Some(master), _) => Zeroizing::new(serde_json::to_string(
+            &master.seal(secret.expose_secret().as_bytes())?,
+        )?),
+        (None, Some(passphrase)) => Zeroizing::new(
+            age::encrypt_and_armor(
+                &scrypt::Recipient::new(passphrase),
+                secret.expose_secret().as_bytes(),
+            )
+            .map_err(|e| anyhow!("encrypting {} failed: {e}", path.display()))?,
+        ),
+        (None, None) => Zeroizing::new(secret.expose_secret().to_string()),
+    };
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("writing {}", path.display()))?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Write the public key next to the identity as `<path>.pub` (0644).
+fn write_public(id_path: &Path, id: &x25519::Identity) -> Result<()> {
+    let pub_path = sibling(id_path, ".pub");
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o644)
+        .open(&pub_path)
+        .with_context(|| format!("writing {}", pub_path.display()))?
+        .write_all(id.to_public().to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Retired identity files, with the time encoded in their name.
+fn retired_files(id_path: &Path) -> Result<Vec<(PathBuf, DateTime<Utc>)>> {
+    let dir = match id_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let Some(base) = id_path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{base}{RETIRED_MARKER}");
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("listing {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(ts) = name.to_str().and_then(|n| n.strip_prefix(&prefix)) else {
+            continue;
+        };
+        let Some(retired_at) = ts
+            .parse::<i64>()
+            .ok()
+            .and_then(|s| DateTime::from_timestamp(s, 0))
+        else {
+            continue;
+        };
+        found.push((entry.path(), retired_at));
     }
+
+    Ok(found)
+}
+
+fn load_retired(id_path: &Path, master: Option<&MasterKey>) -> Result<Vec<RetiredIdentity>> {
+    let cutoff = Utc::now() - rotation_window();
+
+    retired_files(id_path)?
+        .into_iter()
+        .filter(|(_, retired_at)| *retired_at > cutoff)
+        .map(|(path, retired_at)| {
+            Ok(RetiredIdentity {
+                identity: read_identity(&path, master)?,
+                retired_at,
+            })
+        })
+        .collect()
+}
+
+/// The extra recipients configured for the secret store.
+pub fn load_escrow() -> Result<Vec<x25519::Recipient>> {
+    let mut keys: Vec<String> = Vec::new();
+
+    if let Ok(path) = env::var("REZN_AGE_RECIPIENTS_FILE") {
+        let raw = fs::read_to_string(&path).with_context(|| format!("reading {path}"))?;
+        keys.extend(
+            raw.lines()
+                .map(|l| l.split('#').next().unwrap_or_default().trim())
+                .filter(|l| !l.is_empty())
+                .map(String::from),
+        );
+    }
+
+    if let Ok(list) = env::var("REZN_AGE_RECIPIENTS") {
+        keys.extend(
+            list.split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(String::from),
+        );
+    }
+
+    keys.sort();
+    keys.dedup();
+
+    keys.iter()
+        .map(|k| {
+            k.parse::<x25519::Recipient>()
+                .map_err(|e| anyhow!("invalid age recipient '{k}': {e}"))
+        })
+        .collect()
+}
+
+/// Generate the next identity and persist it as `<path>.next`, so it exists
+/// on disk before anything is encrypted to it.
+pub fn stage_next_identity(master: Option<&MasterKey>) -> Result<x25519::Identity> {
+    let next = sibling(&IdentitySource::from_env()?.require_file()?, ".next");
+    let id = x25519::Identity::generate();
+    write_identity(&next, &id, master)?;
+    tracing::info!(
+        "[age] Staged new identity {} at {}",
+        id.to_public(),
+        next.display()
+    );
+    Ok(id)
+}
+
+/// Drop a staged identity after a failed rotation.
+pub fn abandon_next_identity() {
+    let next = sibling(&identity_path(), ".next");
+    if let Err(e) = fs::remove_file(&next) {
+        tracing::warn!("[age] Failed to remove {}: {}", next.display(), e);
+    }
+}
+
+/// Make the staged identity current and retire the old one. Returns when the
+/// old identity was retired.
+pub fn promote_next_identity(new: &x25519::Identity) -> Result<DateTime<Utc>> {
+    let id_path = identity_path();
+    let next = sibling(&id_path, ".next");
+    let retired_at = Utc::now();
+    let retired = sibling(
+        &id_path,
+        &format!("{RETIRED_MARKER}{}", retired_at.timestamp()),
+    );
+
+    fs::rename(&id_path, &retired)
+        .with_context(|| format!("retiring {} to {}", id_path.display(), retired.display()))?;
+    tracing::info!("[age] Retired old identity to {}", retired.display());
+
+    fs::rename(&next, &id_path)
+        .with_context(|| format!("moving {} to {}", next.display(), id_path.display()))?;
+    write_public(&id_path, new)?;
+    tracing::info!(
+        "[age] Identity {} is now current at {}",
+        new.to_public(),
+        id_path.display()
+    );
+
+    Ok(retired_at)
+}
+
+/// Rewrite the current, staged and retired identity files sealed under
+/// `master`. Each file is replaced atomically; archived files are left alone.
+pub fn seal_identity_files(master: &MasterKey) -> Result<()> {
+    let id_path = IdentitySource::from_env()?.require_file()?;
+    let next = sibling(&id_path, ".next");
+
+    let mut paths = vec![id_path.clone()];
+    if next.exists() {
+        paths.push(next);
+    }
+    paths.extend(retired_files(&id_path)?.into_iter().map(|(path, _)| path));
+
+    for path in paths {
+        let id = read_identity(&path, None)?;
+        let tmp = sibling(&path, ".sealing");
+        write_identity(&tmp, &id, Some(master))?;
+        fs::rename(&tmp, &path)
+            .with_context(|| format!("moving {} to {}", tmp.display(), path.display()))?;
+        tracing::info!("[age] Sealed {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Archive or destroy retired identity files whose window has passed.
+pub fn sweep_retired(policy: RetiredKeyPolicy) -> Result<()> {
+    let id_path = identity_path();
+    let cutoff = Utc::now() - rotation_window();
+
+    for (path, retired_at) in retired_files(&id_path)? {
+        if retired_at > cutoff {
+            continue;
+        }
+
+        match policy {
+            RetiredKeyPolicy::Archive => {
+                let archived = sibling(&id_path, &format!(".archived-{}", retired_at.timestamp()));
+                fs::rename(&path, &archived).with_context(|| {
+                    format!("archiving {} to {}", path.display(), archived.display())
+                })?;
+                tracing::info!(
+                    "[age] Rotation window over, archived {}",
+                    archived.display()
+                );
+            }
+            RetiredKeyPolicy::Destroy => {
+                destroy(&path)?;
+                tracing::info!("[age] Rotation window over, destroyed {}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Overwrite a key file before unlinking it.
+fn destroy(path: &Path) -> Result<()> {
+    let len = fs::metadata(path)?.len() as usize;
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.write_all(&vec![0u8; len])?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path).with_context(|| format!("removing {}", path.display()))
 }
@@ -0,0 +1,83 @@
+//! Audit log of interactive access to running containers and of sensitive
+//! secret and key operations.
+//!
+//! Entries live under `audit/{nanos:020}` so a reverse scan returns the most
+//! recent first.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    /// API token name of the caller.
+    pub user: String,
+    /// What was done, e.g. `exec`.
+    pub action: String,
+    /// What the action targeted, e.g. `mol:pod` for exec or a secret key.
+    pub target: String,
+    /// Container the session ran in, once a replica was chosen.
+    #[serde(default)]
+    pub container: Option<String>,
+    #[serde(default)]
+    pub node: Option<String>,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub ended_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub exit_code: Option<i64>,
+    /// Why the session failed, if it did.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// An entry for an action on `target` that involves no container.
+    pub fn new(user: &str, action: &str, target: &str) -> Self {
+        Self {
+            at: Utc::now(),
+            user: user.to_string(),
+            action: action.to_string(),
+            target: target.to_string(),
+            container: None,
+            node: None,
+            command: Vec::new(),
+            ended_at: None,
+            exit_code: None,
+            error: None,
+        }
+    }
+}
+
+/// Store `entry` and return its key, for [`update`] when the session ends.
+pub fn record(db: &Db, entry: &AuditEntry) -> Result<String> {
+    let nanos = entry.at.timestamp_nanos_opt().unwrap_or_default();
+    let key = format!("audit/{nanos:020}");
+
+    db.insert(key.as_str(), serde_json::to_vec(entry)?)?;
+    db.flush()?;
+    Ok(key)
+}
+
+pub fn update(db: &Db, key: &str, entry: &AuditEntry) -> Result<()> {
+    db.insert(key, serde_json::to_vec(entry)?)?;
+    db.flush()?;
+    Ok(())
+}
+
+/// The last `limit` entries, newest first.
+pub fn list(db: &Db, limit: usize) -> Result<Vec<AuditEntry>> {
+    db.scan_prefix("audit/")
+        .values()
+        .rev()
+        .take(limit)
+        .map(|v| {
+            let bytes = v?;
+            serde_json::from_slice(&bytes).context("Failed to parse audit entry")
+        })
+        .collect()
+}
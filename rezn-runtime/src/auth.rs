@@ -0,0 +1,440 @@
+use std::{env, fmt, fs, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{routes::common::AppError, tls::Peer, AppState};
+
+/// What a role lets its holder do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Read state, stats, logs and pod details.
+    Viewer,
+    /// Viewer, plus apply programs, change params and modes, and exec.
+    Deployer,
+    /// Read secret values and their versions.
+    SecretReader,
+    /// Secret reader, plus create, delete and roll back secrets.
+    SecretWriter,
+    /// Everything, including key management, sealing and the audit log.
+    Admin,
+}
+
+impl Role {
+    fn allows(self, permission: Permission) -> bool {
+        use Permission::*;
+        match self {
+            Role::Viewer => matches!(permission, View),
+            Role::Deployer => matches!(permission, View | Deploy | Exec),
+            Role::SecretReader => matches!(permission, ReadSecrets),
+            Role::SecretWriter => matches!(permission, ReadSecrets | WriteSecrets),
+            Role::Admin => true,
+        }
+    }
+}
+
+/// An action a handler checks before doing anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    View,
+    Deploy,
+    Exec,
+    ReadSecrets,
+    WriteSecrets,
+    Admin,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Permission::View => "view",
+            Permission::Deploy => "deploy",
+            Permission::Exec => "exec",
+            Permission::ReadSecrets => "read secrets",
+            Permission::WriteSecrets => "write secrets",
+            Permission::Admin => "administer",
+        })
+    }
+}
+
+/// What a permission is checked against.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope<'a> {
+    /// Runtime-wide data, e.g. nodes or stats of every molecule. Only
+    /// unscoped grants cover it.
+    Global,
+    Molecule(&'a str),
+    /// A secret key.
+    Secret(&'a str),
+}
+
+impl fmt::Display for Scope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Global => f.write_str("the runtime"),
+            Scope::Molecule(mol) => write!(f, "molecule '{mol}'"),
+            Scope::Secret(key) => write!(f, "secret '{key}'"),
+        }
+    }
+}
+
+/// A role, optionally limited to some molecules and secret key prefixes.
+///
+/// A limit on one axis also shuts the grant out of the other: a grant for
+/// some molecules covers no secrets, and one for some secret prefixes covers
+/// no molecules.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Grant {
+    pub role: Role,
+    /// Molecules the grant applies to; all when absent.
+    #[serde(default)]
+    pub molecules: Option<Vec<String>>,
+    /// Secret key prefixes the grant applies to, e.g. `rezn/prod/shop/`; all
+    /// when absent.
+    #[serde(default)]
+    pub secret_prefixes: Option<Vec<String>>,
+}
+
+impl Grant {
+    fn covers(&self, scope: Scope<'_>) -> bool {
+        match scope {
+            Scope::Global => self.molecules.is_none() && self.secret_prefixes.is_none(),
+            Scope::Molecule(mol) => {
+                self.secret_prefixes.is_none()
+                    && self
+                        .molecules
+                        .as_ref()
+                        .is_none_or(|mols| mols.iter().any(|m| m == mol))
+            }
+            Scope::Secret(key) => {
+                self.molecules.is_none()
+                    && self
+                        .secret_prefixes
+                        .as_ref()
+                        .is_none_or(|prefixes| prefixes.iter().any(|p| key.starts_with(p.as_str())))
+            }
+        }
+    }
+}
+
+/// A grant as written in the tokens file: a bare role name, or an object
+/// with scopes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum GrantConfig {
+    Role(Role),
+    Scoped(Grant),
+}
+
+impl From<GrantConfig> for Grant {
+    fn from(cfg: GrantConfig) -> Self {
+        match cfg {
+            GrantConfig::Role(role) => Grant {
+                role,
+                molecules: None,
+                secret_prefixes: None,
+            },
+            GrantConfig::Scoped(grant) => grant,
+        }
+    }
+}
+
+/// One API principal as declared in the tokens file.
+///
+/// A principal authenticates with a bearer token, a TLS client certificate,
+/// or either. Only SHA-256 digests are stored: of the token, e.g.
+/// `printf %s "$TOKEN" | sha256sum`, and of the DER certificate, e.g.
+/// `openssl x509 -in client.pem -outform der | sha256sum`.
+#[derive(Debug, Clone, Deserialize)]
+struct PrincipalConfig {
+    name: String,
+    #[serde(default)]
+    token_sha256: Option<String>,
+    #[serde(default)]
+    cert_sha256: Option<String>,
+    /// Absent in files written before roles existed; such principals are
+    /// authenticated but granted nothing.
+    #[serde(default)]
+    roles: Option<Vec<GrantConfig>>,
+}
+
+struct Principal {
+    name: String,
+    token_sha256: Option<String>,
+    cert_sha256: Option<String>,
+    grants: Arc<[Grant]>,
+}
+
+/// Resolves bearer tokens and client certificates to callers.
+pub struct Authenticator {
+    principals: Vec<Principal>,
+}
+
+impl Authenticator {
+    /// Reads `REZN_API_TOKENS_FILE`, a JSON array of principals:
+    ///
+    /// ```json
+    /// [{"name": "ci", "token_sha256": "…",
+    ///   "roles": ["viewer", {"role": "deployer", "molecules": ["shop"]}]}]
+    /// ```
+    ///
+    /// Without it no caller is valid and authenticated endpoints answer 401.
+    pub fn from_env() -> Result<Self> {
+        let Ok(path) = env::var("REZN_API_TOKENS_FILE") else {
+            tracing::warn!("REZN_API_TOKENS_FILE not set: authenticated endpoints are disabled");
+            return Ok(Self {
+                principals: Vec::new(),
+            });
+        };
+
+        let raw = fs::read_to_string(&path).with_context(|| format!("reading {path}"))?;
+        let configs: Vec<PrincipalConfig> =
+            serde_json::from_str(&raw).with_context(|| format!("parsing {path}"))?;
+
+        let principals = configs
+            .into_iter()
+            .map(|cfg| {
+                if cfg.token_sha256.is_none() && cfg.cert_sha256.is_none() {
+                    return Err(anyhow!(
+                        "principal '{}' in {path} has neither token_sha256 nor cert_sha256",
+                        cfg.name
+                    ));
+                }
+
+                let grants: Vec<Grant> = match cfg.roles {
+                    Some(roles) => roles.into_iter().map(Grant::from).collect(),
+                    None => {
+                        tracing::warn!(
+                            "API principal '{}' has no roles and may do nothing. \
+                             Add \"roles\" to {} to grant it access",
+                            cfg.name,
+                            path
+                        );
+                        Vec::new()
+                    }
+                };
+
+                Ok(Principal {
+                    name: cfg.name,
+                    token_sha256: cfg.token_sha256,
+                    cert_sha256: cfg.cert_sha256,
+                    grants: grants.into(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        tracing::info!("Loaded {} API principal(s) from {}", principals.len(), path);
+
+        Ok(Self { principals })
+    }
+
+    fn authenticate(&self, token: &str) -> Option<&Principal> {
+        let digest = hex::encode(Sha256::digest(token.as_bytes()));
+        self.principals.iter().find(|p| {
+            p.token_sha256
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case(&digest))
+        })
+    }
+
+    fn authenticate_cert(&self, cert_sha256: &str) -> Option<&Principal> {
+        self.principals.iter().find(|p| {
+            p.cert_sha256
+                .as_deref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(cert_sha256))
+        })
+    }
+}
+
+/// The authenticated identity behind a request.
+///
+/// Use as a handler argument to require an `Authorization: Bearer` token or
+/// a known client certificate, then check what the caller may do with
+/// [`Caller::require`] (or [`Caller::can`] to filter listings).
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub name: String,
+    grants: Arc<[Grant]>,
+}
+
+impl Caller {
+    pub fn can(&self, permission: Permission, scope: Scope<'_>) -> bool {
+        self.grants
+            .iter()
+            .any(|g| g.role.allows(permission) && g.covers(scope))
+    }
+
+    /// 403 unless the caller may do `permission` on `scope`.
+    pub fn require(&self, permission: Permission, scope: Scope<'_>) -> Result<(), AppError> {
+        if self.can(permission, scope) {
+            return Ok(());
+        }
+
+        tracing::info!("Denied {} to {} on {}", self.name, permission, scope);
+        Err((
+            StatusCode::FORBIDDEN,
+            format!("'{}' may not {} on {}", self.name, permission, scope),
+        ))
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for Caller {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+
+        let principal = match token {
+            Some(token) => app
+                .auth
+                .authenticate(token.trim())
+                .ok_or_else(|| unauthorized("invalid token"))?,
+            None => {
+                let cert = parts
+                    .extensions
+                    .get::<ConnectInfo<Peer>>()
+                    .and_then(|ConnectInfo(peer)| peer.cert_sha256.as_deref())
+                    .ok_or_else(|| unauthorized("missing bearer token or client certificate"))?;
+                app.auth
+                    .authenticate_cert(cert)
+                    .ok_or_else(|| unauthorized("unknown client certificate"))?
+            }
+        };
+
+        Ok(Caller {
+            name: principal.name.clone(),
+            grants: Arc::clone(&principal.grants),
+        })
+    }
+}
+
+fn unauthorized(msg: &str) -> AppError {
+    (StatusCode::UNAUTHORIZED, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grants(json: &str) -> Vec<Grant> {
+        serde_json::from_str::<Vec<GrantConfig>>(json)
+            .unwrap()
+            .into_iter()
+            .map(Grant::from)
+            .collect()
+    }
+
+    fn caller(json: &str) -> Caller {
+        Caller {
+            name: "test".to_string(),
+            grants: grants(json).into(),
+        }
+    }
+
+    #[test]
+    fn grant_configs_parse_as_bare_roles_or_scoped_objects() {
+        let parsed = grants(
+            r#"["viewer", "secret-writer",
+                {"role": "deployer", "molecules": ["shop"]},
+                {"role": "secret-reader", "secret_prefixes": ["shop/"]}]"#,
+        );
+
+        let summary: Vec<_> = parsed
+            .iter()
+            .map(|g| (g.role, g.molecules.clone(), g.secret_prefixes.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Role::Viewer, None, None),
+                (Role::SecretWriter, None, None),
+                (Role::Deployer, Some(vec!["shop".to_string()]), None),
+                (Role::SecretReader, None, Some(vec!["shop/".to_string()])),
+            ]
+        );
+
+        assert!(serde_json::from_str::<Vec<GrantConfig>>(r#"["root"]"#).is_err());
+    }
+
+    #[test]
+    fn grants_cover_only_their_own_axis() {
+        let unscoped = &grants(r#"["admin"]"#)[0];
+        let shop = &grants(r#"[{"role": "admin", "molecules": ["shop"]}]"#)[0];
+        let shop_secrets = &grants(r#"[{"role": "admin", "secret_prefixes": ["shop/"]}]"#)[0];
+        let both =
+            &grants(r#"[{"role": "admin", "molecules": ["shop"], "secret_prefixes": ["shop/"]}]"#)
+                [0];
+
+        let cases: &[(&Grant, Scope, bool)] = &[
+            (unscoped, Scope::Global, true),
+            (unscoped, Scope::Molecule("shop"), true),
+            (unscoped, Scope::Secret("bank/key"), true),
+            (shop, Scope::Global, false),
+            (shop, Scope::Molecule("shop"), true),
+            (shop, Scope::Molecule("bank"), false),
+            (shop, Scope::Secret("shop/key"), false),
+            (shop_secrets, Scope::Global, false),
+            (shop_secrets, Scope::Secret("shop/key"), true),
+            (shop_secrets, Scope::Secret("bank/key"), false),
+            (shop_secrets, Scope::Molecule("shop"), false),
+            (both, Scope::Global, false),
+            (both, Scope::Molecule("shop"), false),
+            (both, Scope::Secret("shop/key"), false),
+        ];
+        for (grant, scope, expected) in cases {
+            assert_eq!(grant.covers(*scope), *expected, "{grant:?} on {scope}");
+        }
+    }
+
+    #[test]
+    fn require_checks_role_and_scope_together() {
+        let caller = caller(
+            r#"["viewer",
+                {"role": "deployer", "molecules": ["shop"]},
+                {"role": "secret-reader", "secret_prefixes": ["shop/"]}]"#,
+        );
+
+        let cases: &[(Permission, Scope, bool)] = &[
+            (Permission::View, Scope::Global, true),
+            (Permission::View, Scope::Molecule("bank"), true),
+            (Permission::Deploy, Scope::Molecule("shop"), true),
+            (Permission::Deploy, Scope::Molecule("bank"), false),
+            (Permission::Exec, Scope::Molecule("shop"), true),
+            (Permission::ReadSecrets, Scope::Secret("shop/db"), true),
+            (Permission::ReadSecrets, Scope::Secret("bank/db"), false),
+            (Permission::WriteSecrets, Scope::Secret("shop/db"), false),
+            (Permission::Admin, Scope::Global, false),
+        ];
+        for (permission, scope, allowed) in cases {
+            let result = caller.require(*permission, *scope);
+            match result {
+                Ok(()) => assert!(allowed, "{permission} on {scope} was allowed"),
+                Err((status, _)) => {
+                    assert!(!allowed, "{permission} on {scope} was denied");
+                    assert_eq!(status, StatusCode::FORBIDDEN);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn callers_without_grants_may_do_nothing() {
+        let caller = caller("[]");
+        assert!(!caller.can(Permission::View, Scope::Global));
+        assert!(!caller.can(Permission::ReadSecrets, Scope::Secret("shop/db")));
+    }
+}
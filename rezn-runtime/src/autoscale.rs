@@ -0,0 +1,472 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use common::types::{AutoscaleFields, PodSpec};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use utoipa::ToSchema;
+
+use crate::{container_runtime::ContainerSummary, StatsMap};
+
+const DEFAULT_SCALE_UP_WINDOW: u64 = 0;
+const DEFAULT_SCALE_DOWN_WINDOW: u64 = 300;
+const DEFAULT_COOLDOWN: u64 = 60;
+
+/// Samples older than this are ignored.
+const STATS_MAX_AGE: u64 = 60;
+
+/// Utilisation within this ratio of the target does not trigger scaling.
+const TOLERANCE: f64 = 0.1;
+
+/// Per-pod autoscaler memory, persisted in sled under `autoscale/{mol}/{pod}`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AutoscaleState {
+    replicas: Option<usize>,
+    last_scaled_at: Option<u64>,
+    /// `(timestamp, recommended replicas)` pairs, oldest first.
+    recommendations: Vec<(u64, usize)>,
+}
+
+/// An audited scaling decision, stored under `autoscale_event/{mol}/{pod}/{ts}`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScalingEvent {
+    pub mol: String,
+    pub pod: String,
+    pub at: u64,
+    pub from: usize,
+    pub to: usize,
+    pub reason: String,
+    pub cpu_avg: Option<f64>,
+    pub mem_avg: Option<u64>,
+}
+
+/// Compute the effective replica count for an autoscaled pod.
+///
+/// Falls back to the pod's declared `replicas`, clamped to the autoscale
+/// bounds, until enough stats are available to make a recommendation.
+pub fn effective_replicas(
+    db: &Db,
+    pod: &PodSpec,
+    spec: &AutoscaleFields,
+    containers: &[&ContainerSummary],
+    stats: &StatsMap,
+) -> Result<usize> {
+    let now = now_secs();
+    let key = format!("autoscale/{}/{}", pod.mol_name, pod.name);
+
+    let mut state: AutoscaleState = match db.get(&key)? {
+        Some(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        None => AutoscaleState::default(),
+    };
+
+    let min = spec.min_replicas;
+    let max = spec.max_replicas.max(min);
+    let current = state.replicas.unwrap_or(pod.replicas).clamp(min, max);
+
+    let up_window = spec.scale_up_window.unwrap_or(DEFAULT_SCALE_UP_WINDOW);
+    let down_window = spec.scale_down_window.unwrap_or(DEFAULT_SCALE_DOWN_WINDOW);
+    let cooldown = spec.cooldown.unwrap_or(DEFAULT_COOLDOWN);
+
+    let (cpu_avg, mem_avg) = average_usage(containers, stats, now);
+
+    let mut ratio: Option<f64> = None;
+    if let (Some(target), Some(avg)) = (spec.target_cpu, cpu_avg) {
+        if target > 0.0 {
+            ratio = Some(avg / target);
+        }
+    }
+    if let (Some(target), Some(avg)) = (spec.target_mem, mem_avg) {
+        if target > 0 {
+            let r = avg as f64 / target as f64;
+            ratio = Some(ratio.map_or(r, |c: f64| c.max(r)));
+        }
+    }
+
+    if let Some(ratio) = ratio {
+        let recommended = if (ratio - 1.0).abs() <= TOLERANCE {
+            current
+        } else {
+            ((current.max(1) as f64) * ratio).ceil() as usize
+        };
+        state
+            .recommendations
+            .push((now, recommended.clamp(min, max)));
+    }
+
+    let horizon = up_window.max(down_window);
+    state
+        .recommendations
+        .retain(|(at, _)| now.saturating_sub(*at) <= horizon);
+
+    // Scale up on the lowest recommendation seen in the up window and down on
+    // the highest seen in the down window, so short spikes and dips are ignored.
+    let within = |window: u64| {
+        state
+            .recommendations
+            .iter()
+            .filter(move |(at, _)| now.saturating_sub(*at) <= window)
+            .map(|(_, r)| *r)
+    };
+
+    let target = match (within(up_window).min(), within(down_window).max()) {
+        (Some(up), _) if up > current => up,
+        (_, Some(down)) if down < current => down,
+        _ => current,
+    };
+
+    let cooling_down = state
+        .last_scaled_at
+        .is_some_and(|at| now.saturating_sub(at) < cooldown);
+
+    let decided = if target != current && !cooling_down {
+        let reason = format!(
+            "utilisation cpu={} mem={} against target cpu={} mem={}",
+            fmt_opt(cpu_avg),
+            fmt_opt(mem_avg),
+            fmt_opt(spec.target_cpu),
+            fmt_opt(spec.target_mem),
+        );
+
+        tracing::info!(
+            "[autoscale] {}:{} scaling {} -> {} ({})",
+            pod.mol_name,
+            pod.name,
+            current,
+            target,
+            reason
+        );
+
+        record_event(
+            db,
+            &ScalingEvent {
+                mol: pod.mol_name.clone(),
+                pod: pod.name.clone(),
+                at: now,
+                from: current,
+                to: target,
+                reason,
+                cpu_avg,
+                mem_avg,
+            },
+        )?;
+
+        state.last_scaled_at = Some(now);
+        target
+    } else {
+        current
+    };
+
+    state.replicas = Some(decided);
+    db.insert(key, serde_json::to_vec(&state)?)?;
+
+    Ok(decided)
+}
+
+/// The last decided replica count, without evaluating stats or persisting.
+pub fn current_replicas(db: &Db, pod: &PodSpec, spec: &AutoscaleFields) -> Result<usize> {
+    let key = format!("autoscale/{}/{}", pod.mol_name, pod.name);
+    let state: AutoscaleState = match db.get(&key)? {
+        Some(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        None => AutoscaleState::default(),
+    };
+
+    let min = spec.min_replicas;
+    Ok(state
+        .replicas
+        .unwrap_or(pod.replicas)
+        .clamp(min, spec.max_replicas.max(min)))
+}
+
+/// All recorded scaling decisions, optionally narrowed to one molecule or pod.
+pub fn events(db: &Db, mol: Option<&str>, pod: Option<&str>) -> Result<Vec<ScalingEvent>> {
+    let prefix = match (mol, pod) {
+        (Some(mol), Some(pod)) => format!("autoscale_event/{mol}/{pod}/"),
+        (Some(mol), None) => format!("autoscale_event/{mol}/"),
+        _ => "autoscale_event/".to_string(),
+    };
+
+    let mut out = Vec::new();
+    for kv in db.scan_prefix(prefix) {
+        let (_, v) = kv?;
+        let event: ScalingEvent =
+            serde_json::from_slice(&v).context("Failed to parse scaling event")?;
+        if pod.is_none_or(|p| p == event.pod) {
+            out.push(event);
+        }
+    }
+    Ok(out)
+}
+
+fn record_event(db: &Db, event: &ScalingEvent) -> Result<()> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let key = format!("autoscale_event/{}/{}/{:020}", event.mol, event.pod, nanos);
+    db.insert(key, serde_json::to_vec(event)?)?;
+    Ok(())
+}
+
+/// Average fresh CPU and memory samples over the given containers.
+fn average_usage(
+    containers: &[&ContainerSummary],
+    stats: &StatsMap,
+    now: u64,
+) -> (Option<f64>, Option<u64>) {
+    let mut cpu = Vec::new();
+    let mut mem = Vec::new();
+
+    for c in containers {
+        // Stats may be keyed by the short or the full container ID.
+        let sample = stats
+            .iter()
+            .find(|(id, _)| c.id.starts_with(id.as_str()) || id.starts_with(&c.id))
+            .map(|(_, s)| s);
+
+        if let Some(s) = sample.filter(|s| now.saturating_sub(s.timestamp) <= STATS_MAX_AGE) {
+            cpu.extend(s.stats.cpu_avg);
+            mem.extend(s.stats.max_mem);
+        }
+    }
+
+    let cpu_avg = (!cpu.is_empty()).then(|| cpu.iter().sum::<f64>() / cpu.len() as f64);
+    let mem_avg = (!mem.is_empty()).then(|| mem.iter().sum::<u64>() / mem.len() as u64);
+
+    (cpu_avg, mem_avg)
+}
+
+fn fmt_opt<T: std::fmt::Display>(v: Option<T>) -> String {
+    v.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Stats, TimestampedStats};
+
+    fn pod(replicas: usize) -> PodSpec {
+        PodSpec {
+            mol_name: "shop".into(),
+            name: "web".into(),
+            image: "web:1".into(),
+            pull_policy: Default::default(),
+            replicas,
+            ports: Vec::new(),
+            env: Default::default(),
+            node_selector: Default::default(),
+            autoscale: None,
+            depends_on: Vec::new(),
+            stop_grace_period: None,
+            pre_stop: None,
+            restart_on_secret_change: true,
+            secret_files: Vec::new(),
+        }
+    }
+
+    /// Scales on CPU alone and decides on every evaluation.
+    fn spec(min: usize, max: usize) -> AutoscaleFields {
+        AutoscaleFields {
+            min_replicas: min,
+            max_replicas: max,
+            target_cpu: Some(50.0),
+            target_mem: None,
+            scale_up_window: Some(0),
+            scale_down_window: Some(0),
+            cooldown: Some(0),
+        }
+    }
+
+    fn containers(n: usize) -> Vec<ContainerSummary> {
+        (0..n)
+            .map(|i| {
+                serde_json::from_value(serde_json::json!({
+                    "Id": format!("{i:0>12}cafe"),
+                    "Names": [format!("/shop-web-{i}")],
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    /// Every container at `cpu` percent, sampled `age` seconds ago and keyed
+    /// by the short ID.
+    fn stats(containers: &[ContainerSummary], cpu: f64, age: u64) -> StatsMap {
+        containers
+            .iter()
+            .map(|c| {
+                let sample = TimestampedStats {
+                    stats: Stats {
+                        cpu_avg: Some(cpu),
+                        max_mem: None,
+                    },
+                    timestamp: now_secs() - age,
+                };
+                (c.id[..12].to_string(), sample)
+            })
+            .collect()
+    }
+
+    fn decide(
+        db: &Db,
+        pod: &PodSpec,
+        spec: &AutoscaleFields,
+        containers: &[ContainerSummary],
+        stats: &StatsMap,
+    ) -> usize {
+        let running: Vec<_> = containers.iter().collect();
+        effective_replicas(db, pod, spec, &running, stats).unwrap()
+    }
+
+    fn db() -> Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    /// Replace what the autoscaler remembers about `pod`.
+    fn remember(db: &Db, pod: &PodSpec, state: AutoscaleState) {
+        let key = format!("autoscale/{}/{}", pod.mol_name, pod.name);
+        db.insert(key, serde_json::to_vec(&state).unwrap()).unwrap();
+    }
+
+    /// Decided on `replicas` long ago, with no recommendations left.
+    fn settled(replicas: usize) -> AutoscaleState {
+        AutoscaleState {
+            replicas: Some(replicas),
+            last_scaled_at: Some(now_secs() - 3600),
+            recommendations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn without_stats_the_declared_replicas_are_clamped_to_the_bounds() {
+        let db = db();
+        assert_eq!(decide(&db, &pod(1), &spec(2, 4), &[], &StatsMap::new()), 2);
+
+        let db = self::db();
+        assert_eq!(decide(&db, &pod(9), &spec(2, 4), &[], &StatsMap::new()), 4);
+        assert!(events(&db, None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn utilisation_scales_up_and_down_within_the_bounds() {
+        let db = db();
+        let pod = pod(2);
+        let spec = spec(1, 5);
+        let running = containers(2);
+
+        assert_eq!(
+            decide(&db, &pod, &spec, &running, &stats(&running, 100.0, 0)),
+            4
+        );
+
+        remember(&db, &pod, settled(4));
+        let running = containers(4);
+        assert_eq!(
+            decide(&db, &pod, &spec, &running, &stats(&running, 500.0, 0)),
+            5
+        );
+
+        remember(&db, &pod, settled(5));
+        let running = containers(5);
+        assert_eq!(
+            decide(&db, &pod, &spec, &running, &stats(&running, 10.0, 0)),
+            1
+        );
+
+        let events = events(&db, Some("shop"), Some("web")).unwrap();
+        let moves: Vec<_> = events.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(moves, [(2, 4), (4, 5), (5, 1)]);
+        assert_eq!(events[0].cpu_avg, Some(100.0));
+        assert_eq!(current_replicas(&db, &pod, &spec).unwrap(), 1);
+    }
+
+    #[test]
+    fn utilisation_within_tolerance_keeps_the_count() {
+        let db = db();
+        let running = containers(3);
+
+        let replicas = decide(
+            &db,
+            &pod(3),
+            &spec(1, 10),
+            &running,
+            &stats(&running, 54.0, 0),
+        );
+
+        assert_eq!(replicas, 3);
+        assert!(events(&db, None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn stale_stats_are_ignored() {
+        let db = db();
+        let running = containers(2);
+        let stale = stats(&running, 100.0, STATS_MAX_AGE + 5);
+
+        assert_eq!(decide(&db, &pod(2), &spec(1, 5), &running, &stale), 2);
+    }
+
+    #[test]
+    fn a_recent_decision_holds_the_count_until_the_cooldown_ends() {
+        let db = db();
+        let pod = pod(2);
+        let spec = AutoscaleFields {
+            cooldown: Some(600),
+            ..spec(1, 10)
+        };
+        let running = containers(2);
+        let busy = stats(&running, 100.0, 0);
+
+        remember(
+            &db,
+            &pod,
+            AutoscaleState {
+                last_scaled_at: Some(now_secs() - 60),
+                ..settled(2)
+            },
+        );
+        assert_eq!(decide(&db, &pod, &spec, &running, &busy), 2);
+        assert!(events(&db, None, None).unwrap().is_empty());
+
+        remember(&db, &pod, settled(2));
+        assert_eq!(decide(&db, &pod, &spec, &running, &busy), 4);
+    }
+
+    #[test]
+    fn a_dip_inside_the_scale_down_window_is_ignored() {
+        let db = db();
+        let pod = pod(4);
+        let spec = AutoscaleFields {
+            scale_down_window: Some(300),
+            ..spec(1, 10)
+        };
+        let running = containers(4);
+        let idle = stats(&running, 10.0, 0);
+
+        remember(
+            &db,
+            &pod,
+            AutoscaleState {
+                recommendations: vec![(now_secs() - 120, 4)],
+                ..settled(4)
+            },
+        );
+        assert_eq!(decide(&db, &pod, &spec, &running, &idle), 4);
+
+        remember(
+            &db,
+            &pod,
+            AutoscaleState {
+                recommendations: vec![(now_secs() - 400, 4)],
+                ..settled(4)
+            },
+        );
+        assert_eq!(decide(&db, &pod, &spec, &running, &idle), 1);
+    }
+}
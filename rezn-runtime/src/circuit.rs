@@ -0,0 +1,215 @@
+use std::{
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Consecutive failed calls that open the circuit, unless
+/// `REZN_BREAKER_THRESHOLD` says otherwise.
+const DEFAULT_THRESHOLD: u32 = 5;
+
+/// Seconds the circuit stays open before a trial call, unless
+/// `REZN_BREAKER_COOLDOWN` says otherwise.
+const DEFAULT_COOLDOWN: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// The executor is considered down; calls fail fast.
+    Open,
+    /// Cooldown elapsed; the next call decides whether to close again.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// When an open circuit lets the next trial call through.
+    pub open_until: Option<DateTime<Utc>>,
+}
+
+struct Inner {
+    state: CircuitState,
+    failures: u32,
+    opened_at: Option<(Instant, DateTime<Utc>)>,
+}
+
+/// Stops hammering an executor that keeps failing.
+pub struct CircuitBreaker {
+    name: String,
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn from_env(name: impl Into<String>) -> Self {
+        let threshold = env::var("REZN_BREAKER_THRESHOLD")
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_THRESHOLD);
+        let cooldown = env::var("REZN_BREAKER_COOLDOWN")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_COOLDOWN);
+
+        Self::new(name, threshold, Duration::from_secs(cooldown))
+    }
+
+    /// Opens after `threshold` consecutive failures and lets a trial call
+    /// through once `cooldown` has passed.
+    pub fn new(name: impl Into<String>, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name: name.into(),
+            threshold: threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                failures: 0,
+                opened_at: None,
+            }),
+        }
+    }
+
+    /// Whether a call may go out now. Moves an open circuit whose cooldown
+    /// has elapsed to half-open.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed | CircuitState::HalfOpen => true,
+            CircuitState::Open => {
+                let elapsed = inner
+                    .opened_at
+                    .is_some_and(|(at, _)| at.elapsed() >= self.cooldown);
+                if elapsed {
+                    inner.state = CircuitState::HalfOpen;
+                }
+                elapsed
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state != CircuitState::Closed {
+            tracing::info!("[circuit] '{}' closed, executor is responding", self.name);
+        }
+        inner.state = CircuitState::Closed;
+        inner.failures = 0;
+        inner.opened_at = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.failures += 1;
+
+        let trip = match inner.state {
+            CircuitState::Closed => inner.failures >= self.threshold,
+            // The trial call failed: back to waiting.
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+
+        if trip {
+            tracing::warn!(
+                "[circuit] '{}' open after {} consecutive failures, pausing for {:?}",
+                self.name,
+                inner.failures,
+                self.cooldown
+            );
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some((Instant::now(), Utc::now()));
+        }
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        let inner = self.inner.lock().unwrap();
+        CircuitStatus {
+            state: inner.state,
+            consecutive_failures: inner.failures,
+            open_until: match inner.state {
+                CircuitState::Open => inner.opened_at.map(|(_, wall)| {
+                    wall + chrono::Duration::from_std(self.cooldown).unwrap_or_default()
+                }),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(breaker: &CircuitBreaker, times: u32) {
+        for _ in 0..times {
+            breaker.record_failure();
+        }
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::from_secs(60));
+
+        fail(&breaker, 2);
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert!(breaker.allow());
+
+        // A success in between starts the count over.
+        breaker.record_success();
+        fail(&breaker, 2);
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+
+        breaker.record_failure();
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.consecutive_failures, 3);
+        assert!(status.open_until.is_some());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn half_opens_after_the_cooldown_and_closes_on_success() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::ZERO);
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Open);
+
+        assert!(breaker.allow());
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+
+        breaker.record_success();
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Closed);
+        assert_eq!(status.consecutive_failures, 0);
+        assert_eq!(status.open_until, None);
+    }
+
+    #[test]
+    fn a_failed_trial_call_reopens() {
+        let breaker = CircuitBreaker::new("test", 5, Duration::ZERO);
+        fail(&breaker, 5);
+        assert!(breaker.allow());
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+
+        // One failure is enough while half-open.
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Open);
+    }
+
+    #[test]
+    fn stays_open_during_the_cooldown() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::from_secs(60));
+        breaker.record_failure();
+
+        assert!(!breaker.allow());
+        assert!(!breaker.allow());
+        assert_eq!(breaker.status().state, CircuitState::Open);
+    }
+}
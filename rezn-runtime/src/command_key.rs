@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Context, Result};
+use common::signing::CommandSigner;
+use once_cell::sync::OnceCell;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+};
+
+static SIGNER: OnceCell<CommandSigner> = OnceCell::new();
+
+/// The ed25519 identity the runtime signs executor commands with.
+pub fn get_signer() -> Result<&'static CommandSigner> {
+    SIGNER.get_or_try_init(load_or_generate_signer)
+}
+
+/// Load the key from `REZN_COMMAND_KEY` (default `command_key.txt`), or
+/// generate one and write its public half next to it as `<path>.pub`.
+fn load_or_generate_signer() -> Result<CommandSigner> {
+    let key_path = std::env::var("REZN_COMMAND_KEY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("command_key.txt"));
+
+    if key_path.exists() {
+        let raw = fs::read_to_string(&key_path)
+            .with_context(|| format!("reading {}", key_path.display()))?;
+
+        return CommandSigner::from_base64(&raw)
+            .map_err(|e| anyhow!("parsing {} failed: {e}", key_path.display()));
+    }
+
+    if let Some(dir) = key_path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+
+    let signer = CommandSigner::generate();
+
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&key_path)?
+        .write_all(signer.to_base64().as_bytes())?;
+
+    let mut pub_path = key_path.into_os_string();
+    pub_path.push(".pub");
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o644)
+        .open(&pub_path)?
+        .write_all(signer.public_key().as_bytes())?;
+
+    Ok(signer)
+}
@@ -0,0 +1,331 @@
+//! The executor-facing abstraction reconcile drives.
+//!
+//! Backends: [`OrqosClient`](crate::orqos_client::OrqosClient) talks to an
+//! Orqos executor over HTTP, [`DockerClient`](crate::docker_client::DockerClient)
+//! talks to a Docker/Podman Engine API socket directly, and
+//! [`MemoryRuntime`](crate::memory_runtime::MemoryRuntime) keeps containers
+//! in memory for local testing.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine};
+use bytes::Bytes;
+use common::{exec::ExecStart, types::PullPolicy};
+use futures_util::{stream::BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+
+use crate::{circuit::CircuitStatus, images::ResolvedImage};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CreateReq {
+    pub name: String,
+    pub image: String,
+    pub cpu: Option<String>,
+    pub ports: Vec<PortMap>,
+    pub labels: HashMap<String, String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    /// Placed on in-memory mounts before the container starts, so they
+    /// never touch the container's filesystem or the node's disk.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ContainerFile>,
+    /// Whether a backend may pull `image` if it went missing after
+    /// [`pull_image`](ContainerRuntime::pull_image).
+    #[serde(skip)]
+    pub pull_policy: PullPolicy,
+}
+
+/// A file placed in a container, e.g. a secret. Files in the same
+/// directory share one mount, which holds nothing else.
+#[derive(Serialize, Debug, Clone)]
+pub struct ContainerFile {
+    /// Absolute path inside the container.
+    pub path: String,
+    #[serde(serialize_with = "as_base64")]
+    pub content: Vec<u8>,
+    pub mode: u32,
+    /// Owner and group; whoever writes the file when absent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+}
+
+fn as_base64<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&general_purpose::STANDARD.encode(bytes))
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PortMap {
+    pub container: u16,
+    pub host: u16,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerSummary {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Names")]
+    pub names: Vec<String>,
+    /// e.g. `running`, `exited`, `restarting`.
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Human readable, e.g. `Up 5 minutes (healthy)`.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub ports: Vec<ContainerPort>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// The image as the container was created from it.
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default, rename = "ImageID")]
+    pub image_id: Option<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Unix seconds.
+    #[serde(default)]
+    pub created: Option<i64>,
+}
+
+/// Lifecycle state of a container as reported by the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerPhase {
+    Created,
+    Running,
+    Paused,
+    /// The engine's restart policy is bringing it back after a crash.
+    Restarting,
+    Removing,
+    Exited,
+    Dead,
+    Unknown,
+}
+
+impl ContainerPhase {
+    pub fn parse(state: &str) -> Self {
+        match state {
+            "created" => Self::Created,
+            "running" => Self::Running,
+            "paused" => Self::Paused,
+            "restarting" => Self::Restarting,
+            "removing" => Self::Removing,
+            "exited" => Self::Exited,
+            "dead" => Self::Dead,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The process is gone and will not come back on its own.
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Exited | Self::Dead)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerPort {
+    pub private_port: u16,
+    #[serde(default)]
+    pub public_port: Option<u16>,
+}
+
+impl ContainerSummary {
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(|s| s.trim_start_matches('/'))
+    }
+
+    /// Host port published for `private_port`, if any.
+    pub fn public_port(&self, private_port: u16) -> Option<u16> {
+        self.ports
+            .iter()
+            .find(|p| p.private_port == private_port)
+            .and_then(|p| p.public_port)
+    }
+
+    /// Exit code of a stopped container, from a status like `Exited (137) 2 minutes ago`.
+    pub fn exit_code(&self) -> Option<i64> {
+        let status = self.status.as_deref()?.strip_prefix("Exited (")?;
+        status.split_once(')')?.0.parse().ok()
+    }
+
+    pub fn phase(&self) -> ContainerPhase {
+        self.state
+            .as_deref()
+            .map_or(ContainerPhase::Unknown, ContainerPhase::parse)
+    }
+
+    /// Running and not reported unhealthy or still starting its health check.
+    pub fn is_ready(&self) -> bool {
+        let running = self.phase() == ContainerPhase::Running;
+        let unhealthy = self
+            .status
+            .as_deref()
+            .is_some_and(|s| s.contains("(unhealthy)") || s.contains("(health: starting)"));
+        running && !unhealthy
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerInspect {
+    /// RFC 3339.
+    #[serde(default)]
+    pub created: Option<String>,
+    /// Image ID the container runs.
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Times the engine restarted it after it exited.
+    #[serde(default)]
+    pub restart_count: u64,
+    #[serde(default)]
+    pub config: ContainerConfig,
+    pub state: ContainerState,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerConfig {
+    /// The image as requested at creation.
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Output goes through a terminal, so logs are not multiplexed.
+    #[serde(default)]
+    pub tty: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerState {
+    /// e.g. `created`, `running`, `exited`, `dead`.
+    pub status: String,
+    #[serde(default)]
+    pub exit_code: Option<i64>,
+    #[serde(default, rename = "OOMKilled")]
+    pub oom_killed: bool,
+    /// Set by the engine when the container failed to start.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// RFC 3339; the engine reports `0001-01-01T00:00:00Z` for never.
+    #[serde(default)]
+    pub started_at: Option<String>,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+}
+
+impl ContainerState {
+    pub fn phase(&self) -> ContainerPhase {
+        ContainerPhase::parse(&self.status)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase().is_finished()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExecResult {
+    #[serde(default)]
+    pub exit_code: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    /// Only the last `tail` lines.
+    pub tail: Option<usize>,
+    /// Only lines written after this unix timestamp.
+    pub since: Option<i64>,
+    /// Keep the stream open and deliver new lines as they are written.
+    pub follow: bool,
+}
+
+/// Raw log output, stdout and stderr interleaved.
+pub type LogStream = BoxStream<'static, Result<Bytes>>;
+
+#[derive(Debug)]
+pub enum ExecInput {
+    Stdin(Bytes),
+    Resize { cols: u16, rows: u16 },
+}
+
+#[derive(Debug)]
+pub enum ExecOutput {
+    Stdout(Bytes),
+    Stderr(Bytes),
+    /// Always the last item of a session.
+    Exit(Option<i64>),
+}
+
+/// A running interactive exec. Dropping `input` closes the process's stdin.
+pub struct ExecSession {
+    pub input: mpsc::Sender<ExecInput>,
+    pub output: BoxStream<'static, Result<ExecOutput>>,
+}
+
+impl ExecSession {
+    /// A session together with the backend's ends of its channels.
+    pub fn pair() -> (
+        Self,
+        mpsc::Receiver<ExecInput>,
+        mpsc::Sender<Result<ExecOutput>>,
+    ) {
+        let (input, in_rx) = mpsc::channel(64);
+        let (out_tx, mut out_rx) = mpsc::channel(64);
+        let output = futures_util::stream::poll_fn(move |cx| out_rx.poll_recv(cx)).boxed();
+
+        (Self { input, output }, in_rx, out_tx)
+    }
+}
+
+#[async_trait]
+pub trait ContainerRuntime: Send + Sync {
+    /// Containers carrying `label` (`key=value`), whatever their state.
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>>;
+
+    /// Make `image` available according to `policy` and resolve it to the
+    /// digest the node will run.
+    async fn pull_image(&self, image: &str, policy: PullPolicy) -> Result<ResolvedImage>;
+
+    async fn create_container(&self, req: CreateReq) -> Result<()>;
+
+    /// Replace files on a running container's in-memory mounts. Only paths
+    /// in directories the container was created with files in can be written.
+    async fn write_files(&self, name: &str, files: &[ContainerFile]) -> Result<()>;
+
+    /// Stop `name`, killing it after `timeout` seconds (the backend's
+    /// default when `None`). Returns once the container has stopped.
+    async fn stop_container(&self, name: &str, timeout: Option<u64>) -> Result<()>;
+
+    /// Force-remove `name`.
+    async fn remove_container(&self, name: &str) -> Result<()>;
+
+    /// Returns `Ok(None)` when the backend does not know the container.
+    async fn inspect_container(&self, name: &str) -> Result<Option<ContainerInspect>>;
+
+    /// Run `command` inside `name` and wait for it to finish.
+    async fn exec_container(
+        &self,
+        name: &str,
+        command: &[String],
+        timeout: Duration,
+    ) -> Result<ExecResult>;
+
+    /// Start `start.command` inside `name` attached to stdin/stdout/stderr.
+    async fn exec_session(&self, name: &str, start: &ExecStart) -> Result<ExecSession>;
+
+    async fn logs(&self, name: &str, opts: &LogOptions) -> Result<LogStream>;
+
+    /// Circuit breaker state, for backends that keep one.
+    fn circuit(&self) -> Option<CircuitStatus> {
+        None
+    }
+}
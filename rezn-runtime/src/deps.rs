@@ -0,0 +1,163 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Context, Result};
+use common::types::{Instruction, PodDependency};
+
+/// Order pods so that every pod comes after the pods it depends on.
+///
+/// `pods` is a list of `(pod name, dependency names)` in declaration order,
+/// which is kept for pods that do not constrain each other. Fails on unknown
+/// dependencies and on cycles.
+pub fn topo_order(pods: &[(String, Vec<String>)]) -> Result<Vec<String>> {
+    let index: HashMap<&str, usize> = pods
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| (name.as_str(), i))
+        .collect();
+
+    let mut indegree = vec![0usize; pods.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); pods.len()];
+
+    for (i, (name, deps)) in pods.iter().enumerate() {
+        for dep in deps {
+            let j = *index
+                .get(dep.as_str())
+                .ok_or_else(|| anyhow!("pod '{name}' depends on unknown pod '{dep}'"))?;
+            if i == j {
+                return Err(anyhow!("pod '{name}' depends on itself"));
+            }
+            indegree[i] += 1;
+            dependents[j].push(i);
+        }
+    }
+
+    let mut ready: VecDeque<usize> = (0..pods.len()).filter(|&i| indegree[i] == 0).collect();
+    let mut order = Vec::with_capacity(pods.len());
+
+    while let Some(i) = ready.pop_front() {
+        order.push(pods[i].0.clone());
+        for &d in &dependents[i] {
+            indegree[d] -= 1;
+            if indegree[d] == 0 {
+                ready.push_back(d);
+            }
+        }
+    }
+
+    if order.len() < pods.len() {
+        let stuck: Vec<&str> = pods
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| indegree[*i] > 0)
+            .map(|(_, (name, _))| name.as_str())
+            .collect();
+        return Err(anyhow!(
+            "dependency cycle between pods: {}",
+            stuck.join(", ")
+        ));
+    }
+
+    Ok(order)
+}
+
+/// Reject programs whose pods have unknown or cyclic `depends_on` entries.
+pub fn validate_program(program: &[Instruction]) -> Result<()> {
+    let mut pods = Vec::new();
+
+    for item in program.iter().filter(|i| i.kind == "pod") {
+        let deps: Option<Vec<PodDependency>> =
+            match item.fields.as_ref().and_then(|f| f.get("depends_on")) {
+                Some(v) => serde_json::from_value(v.clone())
+                    .with_context(|| format!("invalid depends_on in pod '{}'", item.name))?,
+                None => None,
+            };
+        let deps = deps.unwrap_or_default();
+
+        pods.push((
+            item.name.clone(),
+            deps.iter().map(|d| d.pod().to_string()).collect(),
+        ));
+    }
+
+    topo_order(&pods).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pods(spec: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        spec.iter()
+            .map(|(name, deps)| {
+                (
+                    name.to_string(),
+                    deps.iter().map(|d| d.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn program(json: serde_json::Value) -> Vec<Instruction> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn dependencies_come_first_and_declaration_order_is_kept() {
+        let order = topo_order(&pods(&[
+            ("web", &["api"]),
+            ("worker", &[]),
+            ("api", &["db", "cache"]),
+            ("cache", &[]),
+            ("db", &[]),
+        ]))
+        .unwrap();
+
+        assert_eq!(order, ["worker", "cache", "db", "api", "web"]);
+    }
+
+    #[test]
+    fn unknown_and_self_dependencies_are_rejected() {
+        let err = topo_order(&pods(&[("web", &["api"])])).unwrap_err();
+        assert_eq!(err.to_string(), "pod 'web' depends on unknown pod 'api'");
+
+        let err = topo_order(&pods(&[("web", &["web"])])).unwrap_err();
+        assert_eq!(err.to_string(), "pod 'web' depends on itself");
+    }
+
+    #[test]
+    fn cycles_are_rejected_naming_the_pods_in_them() {
+        let err = topo_order(&pods(&[
+            ("db", &[]),
+            ("a", &["c", "db"]),
+            ("b", &["a"]),
+            ("c", &["b"]),
+        ]))
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "dependency cycle between pods: a, b, c");
+    }
+
+    #[test]
+    fn programs_are_checked_with_either_dependency_form() {
+        let ok = program(serde_json::json!([
+            {"kind": "pod", "name": "db", "fields": {}},
+            {"kind": "pod", "name": "web", "fields": {"depends_on": [
+                "db", {"pod": "db", "condition": "ready"}
+            ]}},
+            {"kind": "job", "name": "migrate", "fields": {"depends_on": ["nothing"]}}
+        ]));
+        validate_program(&ok).unwrap();
+
+        let cyclic = program(serde_json::json!([
+            {"kind": "pod", "name": "a", "fields": {"depends_on": ["b"]}},
+            {"kind": "pod", "name": "b", "fields": {"depends_on": [{"pod": "a"}]}}
+        ]));
+        assert!(validate_program(&cyclic).is_err());
+
+        let malformed = program(serde_json::json!([
+            {"kind": "pod", "name": "a", "fields": {"depends_on": [{"condition": "ready"}]}}
+        ]));
+        let err = validate_program(&malformed).unwrap_err();
+        assert_eq!(err.to_string(), "invalid depends_on in pod 'a'");
+    }
+}
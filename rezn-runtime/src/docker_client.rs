@@ -0,0 +1,832 @@
+//! Docker/Podman Engine API client over a unix socket.
+//!
+//! Lets a node run without an Orqos executor in front of the engine. Each call
+//! opens its own connection; the daemon is local, so that is cheap.
+//!
+//! Container files are written under `REZN_SECRET_MOUNT_DIR` (default
+//! `/dev/shm/rezn`), one directory per container directory, and bind-mounted
+//! read-only. The default lives on tmpfs, so they never reach the disk.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    io::Write,
+    os::unix::fs::{fchown, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use common::{exec::ExecStart, types::PullPolicy};
+use futures_util::StreamExt;
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::Incoming,
+    header::{CONNECTION, CONTENT_TYPE, HOST, UPGRADE},
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+use url::form_urlencoded::byte_serialize;
+
+use crate::{
+    container_runtime::{
+        ContainerFile, ContainerInspect, ContainerRuntime, ContainerSummary, CreateReq, ExecInput,
+        ExecOutput, ExecResult, ExecSession, LogOptions, LogStream,
+    },
+    images::{ImageRef, ResolvedImage},
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Pulling an image can take a while on a cold node.
+const PULL_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ImageInspect {
+    id: String,
+    #[serde(default)]
+    repo_digests: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ExecCreated {
+    #[serde(rename = "Id")]
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct ExecInspect {
+    #[serde(rename = "ExitCode")]
+    exit_code: Option<i64>,
+}
+
+#[derive(Clone)]
+pub struct DockerClient {
+    socket: PathBuf,
+    mount_dir: PathBuf,
+}
+
+impl DockerClient {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+            mount_dir: env::var("REZN_SECRET_MOUNT_DIR")
+                .unwrap_or_else(|_| "/dev/shm/rezn".into())
+                .into(),
+        }
+    }
+
+    /// Where `dir` of container `name` lives on the host.
+    fn host_dir(&self, name: &str, dir: &str) -> PathBuf {
+        let digest = hex::encode(Sha256::digest(dir.as_bytes()));
+        self.mount_dir.join(name).join(&digest[..12])
+    }
+
+    /// Write `files` into their host directories, creating them when
+    /// `create` is set. Returns the binds, one per container directory.
+    fn write_mounted(
+        &self,
+        name: &str,
+        files: &[ContainerFile],
+        create: bool,
+    ) -> Result<Vec<String>> {
+        let mut dirs: BTreeMap<&str, Vec<&ContainerFile>> = BTreeMap::new();
+        for file in files {
+            let (dir, _) = file
+                .path
+                .rsplit_once('/')
+                .filter(|_| file.path.starts_with('/'))
+                .ok_or_else(|| anyhow!("file path '{}' is not absolute", file.path))?;
+            if dir.is_empty() {
+                return Err(anyhow!("cannot mount {} over /", file.path));
+            }
+            dirs.entry(dir).or_default().push(file);
+        }
+
+        let mut binds = Vec::new();
+        for (dir, files) in dirs {
+            let host = self.host_dir(name, dir);
+            if create {
+                fs::create_dir_all(&host)
+                    .with_context(|| format!("creating {}", host.display()))?;
+                fs::set_permissions(&host, fs::Permissions::from_mode(0o755))?;
+            } else if !host.is_dir() {
+                return Err(anyhow!("{dir} is not a file mount of container {name}"));
+            }
+            for file in files {
+                let file_name = file.path.rsplit('/').next().unwrap_or_default();
+                write_atomically(&host.join(file_name), file)
+                    .with_context(|| format!("writing {} for {name}", file.path))?;
+            }
+            binds.push(format!("{}:{dir}:ro", host.display()));
+        }
+        Ok(binds)
+    }
+
+    fn remove_mounted(&self, name: &str) {
+        let dir = self.mount_dir.join(name);
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("[docker] Failed to remove {}: {}", dir.display(), e);
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Response<Incoming>> {
+        let body = match body {
+            Some(json) => Some(("application/json", Bytes::from(serde_json::to_vec(&json)?))),
+            None => None,
+        };
+        self.send(method, path, body, timeout, false).await
+    }
+
+    /// `body` is a content type and payload. With `upgrade`, asks the engine
+    /// to hijack the connection for a raw stdin/stdout stream (answered with
+    /// 101).
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<(&str, Bytes)>,
+        timeout: Duration,
+        upgrade: bool,
+    ) -> Result<Response<Incoming>> {
+        let send = async {
+            let stream = UnixStream::connect(&self.socket)
+                .await
+                .with_context(|| format!("connecting to {}", self.socket.display()))?;
+
+            let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+                .await
+                .context("Engine API handshake failed")?;
+
+            tokio::spawn(async move {
+                if let Err(e) = conn.with_upgrades().await {
+                    tracing::debug!("[docker] Connection closed with error: {}", e);
+                }
+            });
+
+            let mut req = Request::builder()
+                .method(method)
+                .uri(path)
+                .header(HOST, "docker");
+            if upgrade {
+                req = req.header(CONNECTION, "Upgrade").header(UPGRADE, "tcp");
+            }
+
+            let body = match body {
+                Some((content_type, bytes)) => {
+                    req = req.header(CONTENT_TYPE, content_type);
+                    Full::new(bytes)
+                }
+                None => Full::new(Bytes::new()),
+            };
+
+            sender
+                .send_request(req.body(body)?)
+                .await
+                .with_context(|| format!("Engine API request {path} failed"))
+        };
+
+        tokio::time::timeout(timeout, send)
+            .await
+            .map_err(|_| anyhow!("Engine API request {path} timed out"))?
+    }
+
+    async fn json<T: DeserializeOwned>(res: Response<Incoming>, what: &str) -> Result<T> {
+        let res = Self::check(res, what).await?;
+        let body = res.into_body().collect().await?.to_bytes();
+        serde_json::from_slice(&body).with_context(|| format!("Failed to parse {what} response"))
+    }
+
+    /// Turn a non-2xx answer into an error carrying the daemon's message.
+    async fn check(res: Response<Incoming>, what: &str) -> Result<Response<Incoming>> {
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+
+        let body = res.into_body().collect().await?.to_bytes();
+        let message = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|v| v.get("message").and_then(Value::as_str).map(String::from))
+            .unwrap_or_else(|| String::from_utf8_lossy(&body).into_owned());
+
+        Err(anyhow!("{what} failed ({status}): {message}"))
+    }
+
+    async fn pull(&self, image: &str) -> Result<()> {
+        tracing::info!("[docker] Pulling image {}", image);
+
+        // Without a tag or digest the engine would pull every tag.
+        let parsed = ImageRef::parse(image)?;
+        let from = if parsed.tag.is_none() && parsed.digest.is_none() {
+            format!("{image}:latest")
+        } else {
+            image.to_string()
+        };
+
+        let res = self
+            .request(
+                Method::POST,
+                &format!("/images/create?fromImage={}", encode(&from)),
+                None,
+                PULL_TIMEOUT,
+            )
+            .await?;
+        let res = Self::check(res, "Image pull").await?;
+
+        // Progress is streamed as JSON lines; failures show up as an `error` line
+        // in an otherwise successful response.
+        let body = res.into_body().collect().await?.to_bytes();
+        for line in body.split(|b| *b == b'\n') {
+            if let Ok(progress) = serde_json::from_slice::<Value>(line) {
+                if let Some(err) = progress.get("error").and_then(Value::as_str) {
+                    return Err(anyhow!("Image pull failed: {err}"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn inspect_exec(&self, id: &str) -> Result<Option<i64>> {
+        let res = self
+            .request(
+                Method::GET,
+                &format!("/exec/{id}/json"),
+                None,
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+        let info: ExecInspect = Self::json(res, "Exec inspection").await?;
+        Ok(info.exit_code)
+    }
+
+    async fn resize_exec(&self, id: &str, cols: u16, rows: u16) -> Result<()> {
+        let res = self
+            .request(
+                Method::POST,
+                &format!("/exec/{id}/resize?h={rows}&w={cols}"),
+                None,
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+        Self::check(res, "Exec resize").await?;
+        Ok(())
+    }
+
+    /// Returns `Ok(None)` when the engine does not have `image`.
+    async fn inspect_image(&self, image: &str) -> Result<Option<ImageInspect>> {
+        // Image names keep their slashes: the engine matches the rest of the path.
+        let res = self
+            .request(
+                Method::GET,
+                &format!("/images/{image}/json"),
+                None,
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Self::json(res, "Image inspection").await.map(Some)
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for DockerClient {
+    async fn pull_image(&self, image: &str, policy: PullPolicy) -> Result<ResolvedImage> {
+        let image_ref = ImageRef::parse(image)?;
+
+        if policy == PullPolicy::Always {
+            self.pull(image).await?;
+        }
+
+        let info = match self.inspect_image(image).await? {
+            Some(info) => info,
+            None if policy == PullPolicy::Never => {
+                return Err(anyhow!(
+                    "image {image} is not present and its pull policy is never"
+                ));
+            }
+            None => {
+                self.pull(image).await?;
+                self.inspect_image(image)
+                    .await?
+                    .ok_or_else(|| anyhow!("image {image} missing right after pulling it"))?
+            }
+        };
+
+        // Prefer the registry digest of this repository; an image that was
+        // built locally only has its ID.
+        let repo_digest = info
+            .repo_digests
+            .iter()
+            .filter_map(|d| d.split_once('@'))
+            .find(|(repo, _)| *repo == image_ref.repository)
+            .or_else(|| info.repo_digests.iter().find_map(|d| d.split_once('@')))
+            .map(|(_, digest)| digest.to_string());
+
+        Ok(match repo_digest {
+            Some(digest) => ResolvedImage {
+                reference: image_ref.pinned(&digest),
+                digest,
+            },
+            None => ResolvedImage {
+                reference: info.id.clone(),
+                digest: info.id,
+            },
+        })
+    }
+
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>> {
+        let filters = json!({ "label": [label] }).to_string();
+        let res = self
+            .request(
+                Method::GET,
+                &format!("/containers/json?all=true&filters={}", encode(&filters)),
+                None,
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+
+        Self::json(res, "Container list").await
+    }
+
+    async fn create_container(&self, req: CreateReq) -> Result<()> {
+        let mut exposed = HashMap::new();
+        let mut bindings = HashMap::new();
+        for p in &req.ports {
+            let key = format!("{}/tcp", p.container);
+            exposed.insert(key.clone(), json!({}));
+            bindings.insert(key, json!([{ "HostPort": p.host.to_string() }]));
+        }
+
+        let mut host_config = json!({ "PortBindings": bindings });
+        if let Some(cpu) = &req.cpu {
+            let cpus: f64 = cpu
+                .parse()
+                .with_context(|| format!("invalid cpu limit '{cpu}'"))?;
+            host_config["NanoCpus"] = json!((cpus * 1e9) as i64);
+        }
+
+        let mut body = json!({
+            "Image": req.image,
+            "Labels": req.labels,
+            "ExposedPorts": exposed,
+            "HostConfig": host_config,
+        });
+        if let Some(cmd) = &req.command {
+            body["Cmd"] = json!(cmd);
+        }
+        if !req.env.is_empty() {
+            let env: Vec<String> = req.env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+            body["Env"] = json!(env);
+        }
+
+        // Env values and files may be resolved secrets: log names only.
+        tracing::debug!(
+            "[docker] Creating {} from {} (env: {:?}, files: {:?})",
+            req.name,
+            req.image,
+            req.env.keys().collect::<Vec<_>>(),
+            req.files.iter().map(|f| &f.path).collect::<Vec<_>>()
+        );
+
+        if !req.files.is_empty() {
+            body["HostConfig"]["Binds"] = json!(self.write_mounted(&req.name, &req.files, true)?);
+        }
+
+        let path = format!("/containers/create?name={}", encode(&req.name));
+        let created = async {
+            let mut res = self
+                .request(Method::POST, &path, Some(body.clone()), DEFAULT_TIMEOUT)
+                .await?;
+
+            if res.status() == StatusCode::NOT_FOUND {
+                // A local image ID cannot be pulled back.
+                if req.pull_policy == PullPolicy::Never || req.image.starts_with("sha256:") {
+                    return Err(anyhow!(
+                        "image {} is not present and cannot be pulled",
+                        req.image
+                    ));
+                }
+                self.pull(&req.image).await?;
+                res = self
+                    .request(Method::POST, &path, Some(body), DEFAULT_TIMEOUT)
+                    .await?;
+            }
+            Self::check(res, "Container creation").await
+        };
+        if let Err(e) = created.await {
+            self.remove_mounted(&req.name);
+            return Err(e);
+        }
+
+        let started = async {
+            let res = self
+                .request(
+                    Method::POST,
+                    &format!("/containers/{}/start", encode(&req.name)),
+                    None,
+                    DEFAULT_TIMEOUT,
+                )
+                .await?;
+            Self::check(res, "Container start").await
+        };
+        if let Err(e) = started.await {
+            // Leave nothing behind for a replica that never ran.
+            if let Err(rm) = self.remove_container(&req.name).await {
+                tracing::warn!("[docker] Failed to remove {}: {:#}", req.name, rm);
+            }
+            self.remove_mounted(&req.name);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn write_files(&self, name: &str, files: &[ContainerFile]) -> Result<()> {
+        self.write_mounted(name, files, false)?;
+        Ok(())
+    }
+
+    async fn stop_container(&self, name: &str, timeout: Option<u64>) -> Result<()> {
+        let mut path = format!("/containers/{}/stop", encode(name));
+        let mut wait = DEFAULT_TIMEOUT;
+        if let Some(secs) = timeout {
+            path.push_str(&format!("?t={secs}"));
+            wait += Duration::from_secs(secs);
+        }
+
+        let res = self.request(Method::POST, &path, None, wait).await?;
+
+        // 304: already stopped.
+        if res.status() == StatusCode::NOT_MODIFIED {
+            return Ok(());
+        }
+        Self::check(res, "Container stop").await?;
+        Ok(())
+    }
+
+    async fn remove_container(&self, name: &str) -> Result<()> {
+        let res = self
+            .request(
+                Method::DELETE,
+                &format!("/containers/{}?force=true", encode(name)),
+                None,
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+        Self::check(res, "Container removal").await?;
+        self.remove_mounted(name);
+        Ok(())
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<Option<ContainerInspect>> {
+        let res = self
+            .request(
+                Method::GET,
+                &format!("/containers/{}/json", encode(name)),
+                None,
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Self::json(res, "Container inspection").await.map(Some)
+    }
+
+    async fn exec_container(
+        &self,
+        name: &str,
+        command: &[String],
+        timeout: Duration,
+    ) -> Result<ExecResult> {
+        let run = async {
+            let res = self
+                .request(
+                    Method::POST,
+                    &format!("/containers/{}/exec", encode(name)),
+                    Some(json!({ "Cmd": command, "AttachStdout": true, "AttachStderr": true })),
+                    DEFAULT_TIMEOUT,
+                )
+                .await?;
+            let created: ExecCreated = Self::json(res, "Exec creation").await?;
+
+            // Attached start returns once the command exits; drain its output.
+            let res = self
+                .request(
+                    Method::POST,
+                    &format!("/exec/{}/start", created.id),
+                    Some(json!({ "Detach": false, "Tty": false })),
+                    timeout,
+                )
+                .await?;
+            Self::check(res, "Exec start")
+                .await?
+                .into_body()
+                .collect()
+                .await?;
+
+            Ok(ExecResult {
+                exit_code: self.inspect_exec(&created.id).await?,
+            })
+        };
+
+        tokio::time::timeout(timeout, run)
+            .await
+            .map_err(|_| anyhow!("exec in {name} timed out"))?
+    }
+
+    async fn exec_session(&self, name: &str, start: &ExecStart) -> Result<ExecSession> {
+        let res = self
+            .request(
+                Method::POST,
+                &format!("/containers/{}/exec", encode(name)),
+                Some(json!({
+                    "Cmd": start.command,
+                    "Tty": start.tty,
+                    "AttachStdin": true,
+                    "AttachStdout": true,
+                    "AttachStderr": true,
+                })),
+                DEFAULT_TIMEOUT,
+            )
+            .await?;
+        let created: ExecCreated = Self::json(res, "Exec creation").await?;
+        let id = created.id;
+
+        let res = self
+            .send(
+                Method::POST,
+                &format!("/exec/{id}/start"),
+                Some((
+                    "application/json",
+                    Bytes::from(serde_json::to_vec(
+                        &json!({ "Detach": false, "Tty": start.tty }),
+                    )?),
+                )),
+                DEFAULT_TIMEOUT,
+                true,
+            )
+            .await?;
+        if res.status() != StatusCode::SWITCHING_PROTOCOLS {
+            Self::check(res, "Exec start").await?;
+            return Err(anyhow!("engine did not hijack the exec connection"));
+        }
+        let upgraded = hyper::upgrade::on(res)
+            .await
+            .context("Exec connection upgrade failed")?;
+        let (mut rd, mut wr) = tokio::io::split(TokioIo::new(upgraded));
+
+        if let (true, Some(cols), Some(rows)) = (start.tty, start.cols, start.rows) {
+            if let Err(e) = self.resize_exec(&id, cols, rows).await {
+                tracing::debug!("[docker] Initial exec resize failed: {:#}", e);
+            }
+        }
+
+        let (session, mut input, output) = ExecSession::pair();
+
+        let client = self.clone();
+        let exec_id = id.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = input.recv().await {
+                match msg {
+                    // An empty write is how callers signal end of input.
+                    ExecInput::Stdin(data) if data.is_empty() => {
+                        let _ = wr.shutdown().await;
+                    }
+                    ExecInput::Stdin(data) => {
+                        if wr.write_all(&data).await.is_err() {
+                            return;
+                        }
+                    }
+                    ExecInput::Resize { cols, rows } => {
+                        if let Err(e) = client.resize_exec(&exec_id, cols, rows).await {
+                            tracing::debug!("[docker] Exec resize failed: {:#}", e);
+                        }
+                    }
+                }
+            }
+            let _ = wr.shutdown().await;
+        });
+
+        let client = self.clone();
+        let tty = start.tty;
+        tokio::spawn(async move {
+            let mut buf = BytesMut::new();
+            let mut chunk = vec![0u8; 8192];
+
+            loop {
+                let n = match rd.read(&mut chunk).await {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) => {
+                        let _ = output.send(Err(e.into())).await;
+                        return;
+                    }
+                };
+
+                let mut items = Vec::new();
+                if tty {
+                    items.push(ExecOutput::Stdout(Bytes::copy_from_slice(&chunk[..n])));
+                } else {
+                    buf.extend_from_slice(&chunk[..n]);
+                    while let Some((kind, data)) = next_frame(&mut buf) {
+                        items.push(match kind {
+                            2 => ExecOutput::Stderr(data),
+                            _ => ExecOutput::Stdout(data),
+                        });
+                    }
+                }
+
+                for item in items {
+                    if output.send(Ok(item)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+
+            let code = match client.inspect_exec(&id).await {
+                Ok(code) => code,
+                Err(e) => {
+                    tracing::debug!("[docker] Failed to inspect exec {}: {:#}", id, e);
+                    None
+                }
+            };
+            let _ = output.send(Ok(ExecOutput::Exit(code))).await;
+        });
+
+        Ok(session)
+    }
+
+    async fn logs(&self, name: &str, opts: &LogOptions) -> Result<LogStream> {
+        let tty = self
+            .inspect_container(name)
+            .await?
+            .is_some_and(|c| c.config.tty);
+
+        let mut path = format!(
+            "/containers/{}/logs?stdout=true&stderr=true&follow={}",
+            encode(name),
+            opts.follow
+        );
+        if let Some(tail) = opts.tail {
+            path.push_str(&format!("&tail={tail}"));
+        }
+        if let Some(since) = opts.since {
+            path.push_str(&format!("&since={since}"));
+        }
+
+        let res = self
+            .request(Method::GET, &path, None, DEFAULT_TIMEOUT)
+            .await?;
+        let res = Self::check(res, "Log fetch").await?;
+
+        let chunks = res
+            .into_body()
+            .into_data_stream()
+            .map(|chunk| chunk.map_err(anyhow::Error::from));
+
+        // A TTY container's output is written as is, without stream headers.
+        Ok(if tty {
+            chunks.boxed()
+        } else {
+            demux(chunks).boxed()
+        })
+    }
+}
+
+/// Take one complete `[kind, 0, 0, 0, len_be32]`-prefixed frame off `buf`,
+/// the format the engine uses for non-TTY container output.
+fn next_frame(buf: &mut BytesMut) -> Option<(u8, Bytes)> {
+    if buf.len() < 8 {
+        return None;
+    }
+    let len = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+    if buf.len() < 8 + len {
+        return None;
+    }
+    let kind = buf[0];
+    Some((kind, buf.split_to(8 + len).split_off(8).freeze()))
+}
+
+/// Strip the stream headers from a log response.
+fn demux<S>(frames: S) -> impl futures_util::Stream<Item = Result<Bytes>> + Send
+where
+    S: futures_util::Stream<Item = Result<Bytes>> + Send + Unpin,
+{
+    futures_util::stream::unfold(
+        (frames, BytesMut::new()),
+        |(mut frames, mut buf)| async move {
+            loop {
+                if let Some((_, frame)) = next_frame(&mut buf) {
+                    return Some((Ok(frame), (frames, buf)));
+                }
+
+                match frames.next().await {
+                    Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                    Some(Err(e)) => return Some((Err(e), (frames, buf))),
+                    None => return None,
+                }
+            }
+        },
+    )
+}
+
+fn encode(value: &str) -> String {
+    byte_serialize(value.as_bytes()).collect()
+}
+
+/// Replace `path` so readers see either the old content or the new, never
+/// a partial write.
+fn write_atomically(path: &Path, file: &ContainerFile) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{name}.tmp"));
+    let mut out = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(file.mode)
+        .open(&tmp)?;
+    out.write_all(&file.content)?;
+    // Binds do not remap IDs, so the container sees these as they are.
+    if file.uid.is_some() || file.gid.is_some() {
+        fchown(&out, file.uid, file.gid).context("changing the file's owner")?;
+    }
+    out.set_permissions(fs::Permissions::from_mode(file.mode))?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container_runtime::ContainerInspect;
+
+    fn frame(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![kind, 0, 0, 0];
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    #[tokio::test]
+    async fn demux_reassembles_frames_split_across_chunks() {
+        let body = [
+            frame(1, b"hello "),
+            frame(2, b"oops\n"),
+            frame(1, b"world\n"),
+        ]
+        .concat();
+        let chunks: Vec<Result<Bytes>> = body
+            .chunks(5)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+
+        let out: Vec<Bytes> = demux(futures_util::stream::iter(chunks))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(out, ["hello ", "oops\n", "world\n"].map(Bytes::from));
+    }
+
+    #[test]
+    fn inspect_reports_whether_a_container_has_a_tty() {
+        let inspect = |config: Value| -> ContainerInspect {
+            serde_json::from_value(json!({
+                "Config": config,
+                "State": {"Status": "running"},
+            }))
+            .unwrap()
+        };
+
+        assert!(inspect(json!({"Image": "web:1", "Tty": true})).config.tty);
+        assert!(!inspect(json!({"Image": "web:1", "Tty": false})).config.tty);
+        assert!(!inspect(json!({"Image": "web:1"})).config.tty);
+    }
+}
@@ -0,0 +1,130 @@
+//! Image references, digest pinning and per-container provenance.
+//!
+//! Pods may name images by tag (`nginx:alpine`) or by digest
+//! (`nginx@sha256:…`). Before a replica starts, the node resolves the image to
+//! a digest according to the pod's pull policy and the container is created
+//! from that digest, so every replica of one rollout runs the same bits. The
+//! resolution is kept under `provenance/{mol}/{container}`.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use common::types::Instruction;
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use utoipa::ToSchema;
+
+/// A parsed `repository[:tag][@digest]` reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    pub fn parse(image: &str) -> Result<Self> {
+        let (rest, digest) = match image.split_once('@') {
+            Some((rest, digest)) => {
+                validate_digest(digest)?;
+                (rest, Some(digest.to_string()))
+            }
+            None => (image, None),
+        };
+
+        // A colon after the last slash separates the tag; one before it is a
+        // registry port (`localhost:5000/app`).
+        let (repository, tag) = match rest.rfind(':') {
+            Some(i) if !rest[i..].contains('/') => (&rest[..i], Some(rest[i + 1..].to_string())),
+            _ => (rest, None),
+        };
+
+        if repository.is_empty() || tag.as_deref() == Some("") {
+            return Err(anyhow!("invalid image reference '{image}'"));
+        }
+
+        Ok(Self {
+            repository: repository.to_string(),
+            tag,
+            digest,
+        })
+    }
+
+    /// `repository@digest`, the reference a container is created from.
+    pub fn pinned(&self, digest: &str) -> String {
+        format!("{}@{}", self.repository, digest)
+    }
+}
+
+fn validate_digest(digest: &str) -> Result<()> {
+    let hex = digest
+        .strip_prefix("sha256:")
+        .ok_or_else(|| anyhow!("unsupported digest '{digest}': expected sha256:<hex>"))?;
+
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow!("invalid sha256 digest '{digest}'"));
+    }
+    Ok(())
+}
+
+/// An image as made available on a node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedImage {
+    /// What to create the container from, normally `repository@sha256:…`.
+    pub reference: String,
+    pub digest: String,
+}
+
+/// Reject pods whose `image` is not a valid reference. Run on a resolved
+/// program so images chosen through an `$enum` switch are checked too.
+pub fn validate_program(program: &[Instruction]) -> Result<()> {
+    for item in program.iter().filter(|i| i.kind == "pod") {
+        let Some(image) = item
+            .fields
+            .as_ref()
+            .and_then(|f| f.get("image"))
+            .and_then(|v| v.as_str())
+        else {
+            continue;
+        };
+
+        ImageRef::parse(image).map_err(|e| anyhow!("pod '{}': {:#}", item.name, e))?;
+    }
+
+    Ok(())
+}
+
+/// Where a running container's image came from.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Provenance {
+    pub container: String,
+    pub pod: String,
+    pub node: String,
+    /// The image as written in the program.
+    pub image: String,
+    /// The digest the node resolved it to.
+    pub digest: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub fn record_provenance(db: &Db, mol: &str, record: &Provenance) -> Result<()> {
+    db.insert(
+        format!("provenance/{mol}/{}", record.container),
+        serde_json::to_vec(record)?,
+    )?;
+    Ok(())
+}
+
+pub fn forget_provenance(db: &Db, mol: &str, container: &str) -> Result<()> {
+    db.remove(format!("provenance/{mol}/{container}"))?;
+    Ok(())
+}
+
+pub fn provenance(db: &Db, mol: &str) -> Result<Vec<Provenance>> {
+    db.scan_prefix(format!("provenance/{mol}/"))
+        .values()
+        .map(|v| {
+            let bytes = v?;
+            serde_json::from_slice(&bytes).context("Failed to parse provenance record")
+        })
+        .collect()
+}
@@ -0,0 +1,623 @@
+use std::{collections::HashMap, env, str::FromStr};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use common::types::{
+    ConcurrencyPolicy, CronFields, DesiredMap, InstructionMeta, JobFields, PullPolicy,
+};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sled::Db;
+use utoipa::ToSchema;
+
+use crate::{
+    container_runtime::{CreateReq, LogOptions},
+    nodes::{Node, NodeRegistry},
+    params,
+    scheduler::Scheduler,
+    secret::{self, SecretStore},
+    AppState,
+};
+
+/// Finished runs kept per job unless `REZN_JOB_HISTORY` says otherwise.
+const DEFAULT_HISTORY: usize = 10;
+
+/// Output lines kept from a failed attempt.
+const LOG_TAIL_LINES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobAttempt {
+    pub container: String,
+    pub node: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub exit_code: Option<i64>,
+    /// Last lines of output, kept for failed attempts.
+    #[serde(default)]
+    pub log_tail: Option<String>,
+}
+
+/// One execution of a `job` or `cron` instruction, including its retries.
+///
+/// Stored under `jobrun/{mol}/{job}/{created_at_nanos}` so a prefix scan
+/// yields runs oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobRun {
+    pub mol: String,
+    pub job: String,
+    pub run_id: String,
+    /// `apply` for `job` instructions, `cron` for scheduled runs.
+    pub trigger: String,
+    /// Identifies the applied program a `job` run belongs to.
+    pub generation: Option<String>,
+    pub status: RunStatus,
+    pub max_attempts: u32,
+    pub attempts: Vec<JobAttempt>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub message: Option<String>,
+}
+
+impl JobRun {
+    fn key(&self) -> String {
+        format!(
+            "jobrun/{}/{}/{:020}",
+            self.mol,
+            self.job,
+            self.created_at.timestamp_nanos_opt().unwrap_or_default()
+        )
+    }
+
+    fn finish(&mut self, status: RunStatus, message: Option<String>) {
+        self.status = status;
+        self.finished_at = Some(Utc::now());
+        if message.is_some() {
+            self.message = message;
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CronState {
+    last_fired_at: Option<DateTime<Utc>>,
+}
+
+/// Drive `job` and `cron` instructions: create runs, watch their containers
+/// to completion, retry failures and prune old history.
+pub async fn reconcile_jobs(
+    app: &AppState,
+    desired: &DesiredMap,
+    scheduler: &mut Scheduler,
+) -> Result<()> {
+    let db = &*app.db;
+
+    for (mol, atoms) in desired {
+        let selected = params::selections(db, mol, atoms)?;
+
+        for item in atoms {
+            if item.kind != "job" && item.kind != "cron" {
+                continue;
+            }
+
+            let Some(fields_val) = &item.fields else {
+                continue;
+            };
+
+            let fields_val = &match params::resolve(fields_val, &selected) {
+                Ok(v) => v,
+                Err(e) => {
+                    tracing::warn!("[jobs] Skipping '{}/{}': {:#}", mol, item.name, e);
+                    continue;
+                }
+            };
+
+            let job = match item.kind.as_str() {
+                "job" => {
+                    let job: JobFields = match serde_json::from_value(fields_val.clone()) {
+                        Ok(job) => job,
+                        Err(e) => {
+                            tracing::warn!("[jobs] Invalid job '{}/{}': {}", mol, item.name, e);
+                            continue;
+                        }
+                    };
+
+                    let generation = generation(db, mol, fields_val)?;
+                    let runs = runs_for(db, mol, &item.name)?;
+                    if !runs
+                        .iter()
+                        .any(|r| r.generation.as_deref() == Some(generation.as_str()))
+                    {
+                        create_run(db, mol, &item.name, &job, "apply", Some(generation))?;
+                    }
+
+                    job
+                }
+                "cron" => {
+                    let cron: CronFields = match serde_json::from_value(fields_val.clone()) {
+                        Ok(cron) => cron,
+                        Err(e) => {
+                            tracing::warn!("[jobs] Invalid cron '{}/{}': {}", mol, item.name, e);
+                            continue;
+                        }
+                    };
+
+                    let policy = cron.concurrency_policy.unwrap_or_default();
+                    if let Err(e) = fire_cron(app, mol, &item.name, &cron, policy).await {
+                        tracing::warn!("[jobs] Cron '{}/{}' failed: {:#}", mol, item.name, e);
+                    }
+
+                    cron.job
+                }
+                _ => continue,
+            };
+
+            for mut run in runs_for(db, mol, &item.name)?
+                .into_iter()
+                .filter(|r| r.status == RunStatus::Running)
+            {
+                advance(&app.nodes, &app.secret_store, scheduler, &mut run, &job).await;
+                db.insert(run.key(), serde_json::to_vec(&run)?)?;
+            }
+
+            prune(db, mol, &item.name)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// All retained runs for a molecule, grouped by job and oldest first.
+pub fn molecule_runs(db: &Db, mol: &str) -> Result<Vec<JobRun>> {
+    scan(db, &format!("jobrun/{mol}/"))
+}
+
+fn runs_for(db: &Db, mol: &str, job: &str) -> Result<Vec<JobRun>> {
+    scan(db, &format!("jobrun/{mol}/{job}/"))
+}
+
+fn scan(db: &Db, prefix: &str) -> Result<Vec<JobRun>> {
+    let mut out = Vec::new();
+    for kv in db.scan_prefix(prefix) {
+        let (_, v) = kv?;
+        out.push(serde_json::from_slice(&v).context("Failed to parse job run")?);
+    }
+    Ok(out)
+}
+
+fn create_run(
+    db: &Db,
+    mol: &str,
+    job: &str,
+    fields: &JobFields,
+    trigger: &str,
+    generation: Option<String>,
+) -> Result<JobRun> {
+    let created_at = Utc::now();
+    let run = JobRun {
+        mol: mol.to_string(),
+        job: job.to_string(),
+        run_id: format!("{:x}", created_at.timestamp_nanos_opt().unwrap_or_default()),
+        trigger: trigger.to_string(),
+        generation,
+        status: RunStatus::Running,
+        max_attempts: fields.retries.unwrap_or(0) + 1,
+        attempts: Vec::new(),
+        created_at,
+        finished_at: None,
+        message: None,
+    };
+
+    tracing::info!(
+        "[jobs] Created {} run {} for '{}/{}'",
+        trigger,
+        run.run_id,
+        mol,
+        job
+    );
+
+    db.insert(run.key(), serde_json::to_vec(&run)?)?;
+    Ok(run)
+}
+
+/// A `job` runs once per applied program: the generation hashes the
+/// molecule's signature together with the job's fields.
+fn generation(db: &Db, mol: &str, fields: &serde_json::Value) -> Result<String> {
+    let sig_id = match db.get(format!("instruction/{mol}"))? {
+        Some(bytes) => serde_json::from_slice::<InstructionMeta>(&bytes)
+            .map(|m| m.sig_id)
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(sig_id.as_bytes());
+    hasher.update(serde_json_canonicalizer::to_vec(fields)?);
+    Ok(hex::encode(&hasher.finalize()[..8]))
+}
+
+/// Create a run when the schedule has fired since the last one.
+///
+/// Missed firings are not backfilled: at most one run is created per call.
+async fn fire_cron(
+    app: &AppState,
+    mol: &str,
+    name: &str,
+    cron: &CronFields,
+    policy: ConcurrencyPolicy,
+) -> Result<()> {
+    let db = &*app.db;
+    let schedule = parse_schedule(&cron.schedule)?;
+    let now = Utc::now();
+
+    let key = format!("cron/{mol}/{name}");
+    let mut state: CronState = match db.get(&key)? {
+        Some(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        None => CronState::default(),
+    };
+
+    // First sighting: start counting from now rather than firing immediately.
+    let Some(last) = state.last_fired_at else {
+        state.last_fired_at = Some(now);
+        db.insert(key, serde_json::to_vec(&state)?)?;
+        return Ok(());
+    };
+
+    let due = schedule.after(&last).next().is_some_and(|next| next <= now);
+    if !due {
+        return Ok(());
+    }
+
+    state.last_fired_at = Some(now);
+    db.insert(&key, serde_json::to_vec(&state)?)?;
+
+    let active: Vec<JobRun> = runs_for(db, mol, name)?
+        .into_iter()
+        .filter(|r| r.status == RunStatus::Running)
+        .collect();
+
+    if !active.is_empty() {
+        match policy {
+            ConcurrencyPolicy::Allow => {}
+            ConcurrencyPolicy::Forbid => {
+                tracing::info!(
+                    "[jobs] Skipping cron '{}/{}': previous run still active",
+                    mol,
+                    name
+                );
+                return Ok(());
+            }
+            ConcurrencyPolicy::Replace => {
+                for mut run in active {
+                    stop_run(&app.nodes, &mut run).await;
+                    db.insert(run.key(), serde_json::to_vec(&run)?)?;
+                }
+            }
+        }
+    }
+
+    create_run(db, mol, name, &cron.job, "cron", None)?;
+    Ok(())
+}
+
+/// Accepts standard 5-field expressions by prepending a seconds field.
+fn parse_schedule(expr: &str) -> Result<cron::Schedule> {
+    let expr = if expr.split_whitespace().count() == 5 {
+        format!("0 {expr}")
+    } else {
+        expr.to_string()
+    };
+
+    cron::Schedule::from_str(&expr).with_context(|| format!("invalid cron schedule '{expr}'"))
+}
+
+/// The last [`LOG_TAIL_LINES`] lines a finished container wrote.
+async fn log_tail(node: &Node, container: &str) -> Option<String> {
+    let opts = LogOptions {
+        tail: Some(LOG_TAIL_LINES),
+        ..Default::default()
+    };
+
+    let collect = async {
+        let mut stream = node.client.logs(container, &opts).await?;
+        let mut out = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            out.extend_from_slice(&chunk?);
+        }
+        anyhow::Ok(String::from_utf8_lossy(&out).into_owned())
+    };
+
+    match collect.await {
+        Ok(tail) => Some(tail),
+        Err(e) => {
+            tracing::warn!("[jobs] Failed to fetch logs of {}: {:#}", container, e);
+            None
+        }
+    }
+}
+
+/// Move a running job forward by one step.
+async fn advance(
+    nodes: &NodeRegistry,
+    store: &SecretStore,
+    scheduler: &mut Scheduler,
+    run: &mut JobRun,
+    job: &JobFields,
+) {
+    if let Some(attempt) = run.attempts.last_mut() {
+        if attempt.finished_at.is_none() {
+            let Some(node) = nodes.get(&attempt.node).filter(|n| n.is_reachable()) else {
+                return;
+            };
+
+            match node.client.inspect_container(&attempt.container).await {
+                Ok(Some(info)) if info.state.is_finished() => {
+                    attempt.exit_code = info.state.exit_code;
+                    attempt.finished_at = Some(Utc::now());
+
+                    if attempt.exit_code != Some(0) {
+                        attempt.log_tail = log_tail(node, &attempt.container).await;
+                    }
+
+                    if let Err(e) = node.client.remove_container(&attempt.container).await {
+                        tracing::warn!("Failed to remove {}: {}", attempt.container, e);
+                    }
+                }
+                Ok(Some(_)) => return,
+                Ok(None) => {
+                    tracing::warn!("[jobs] Container {} disappeared", attempt.container);
+                    attempt.finished_at = Some(Utc::now());
+                }
+                Err(e) => {
+                    tracing::warn!("[jobs] Failed to inspect {}: {:#}", attempt.container, e);
+                    return;
+                }
+            }
+
+            tracing::info!(
+                "[jobs] '{}/{}' attempt {} exited with {:?}",
+                run.mol,
+                run.job,
+                run.attempts.len(),
+                run.attempts.last().and_then(|a| a.exit_code)
+            );
+        }
+
+        let last = run.attempts.last().and_then(|a| a.exit_code);
+        if last == Some(0) {
+            run.finish(RunStatus::Succeeded, None);
+            return;
+        }
+        if run.attempts.len() as u32 >= run.max_attempts {
+            let gave_up = format!("gave up after {} attempts", run.attempts.len());
+            let message = match run.message.take() {
+                Some(why) => format!("{gave_up}: {why}"),
+                None => gave_up,
+            };
+            run.finish(RunStatus::Failed, Some(message));
+            return;
+        }
+    }
+
+    start_attempt(store, scheduler, run, job).await;
+}
+
+async fn start_attempt(
+    store: &SecretStore,
+    scheduler: &mut Scheduler,
+    run: &mut JobRun,
+    job: &JobFields,
+) {
+    // Like a missing node, an unreadable secret is retried next round
+    // without using up an attempt. A container that cannot be created does
+    // use one up: a bad image or command would otherwise be retried forever.
+    let env = job.env.clone().map(|e| e.0).unwrap_or_default();
+    let resolved = match secret::resolve_env(store, &env) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            run.message = Some(format!("cannot resolve env: {e:#}"));
+            return;
+        }
+    };
+
+    let selector = job.node_selector.clone().unwrap_or_default();
+    let Some(node) = scheduler.place(&selector, &mut HashMap::new()) else {
+        run.message = Some("no eligible node".to_string());
+        return;
+    };
+
+    let name = format!(
+        "{}-{}-run-{}-{}",
+        run.mol,
+        run.job,
+        run.run_id,
+        run.attempts.len() + 1
+    );
+
+    let mut labels: HashMap<String, String> = HashMap::new();
+    labels.insert("mol".to_string(), run.mol.clone());
+    labels.insert("job".to_string(), format!("{}:{}", run.mol, run.job));
+    labels.insert("run".to_string(), run.run_id.clone());
+    labels.insert("node".to_string(), node.name.clone());
+
+    let req = CreateReq {
+        name: name.clone(),
+        image: job.image.clone(),
+        cpu: None,
+        ports: Vec::new(),
+        labels,
+        env: resolved.vars,
+        command: job.command.clone(),
+        files: resolved.files,
+        pull_policy: PullPolicy::default(),
+    };
+
+    let started_at = Utc::now();
+    let mut finished_at = None;
+    run.message = None;
+
+    if let Err(e) = node.client.create_container(req).await {
+        tracing::warn!("Failed to start {} on {}: {:#}", name, node.name, e);
+        run.message = Some(format!("failed to start container: {e:#}"));
+        finished_at = Some(started_at);
+    }
+
+    run.attempts.push(JobAttempt {
+        container: name,
+        node: node.name.clone(),
+        started_at,
+        finished_at,
+        exit_code: None,
+        log_tail: None,
+    });
+}
+
+async fn stop_run(nodes: &NodeRegistry, run: &mut JobRun) {
+    if let Some(attempt) = run.attempts.last_mut().filter(|a| a.finished_at.is_none()) {
+        if let Some(node) = nodes.get(&attempt.node) {
+            if let Err(e) = node.client.stop_container(&attempt.container, None).await {
+                tracing::warn!("Failed to stop {}: {}", attempt.container, e);
+            }
+            if let Err(e) = node.client.remove_container(&attempt.container).await {
+                tracing::warn!("Failed to remove {}: {}", attempt.container, e);
+            }
+        }
+        attempt.finished_at = Some(Utc::now());
+    }
+
+    run.finish(
+        RunStatus::Failed,
+        Some("replaced by a newer run".to_string()),
+    );
+}
+
+/// Drop the oldest finished runs beyond the retention limit.
+fn prune(db: &Db, mol: &str, job: &str) -> Result<()> {
+    let keep = env::var("REZN_JOB_HISTORY")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_HISTORY)
+        // A `job` must keep its latest run, or it would be started again.
+        .max(1);
+
+    let finished: Vec<JobRun> = runs_for(db, mol, job)?
+        .into_iter()
+        .filter(|r| r.status != RunStatus::Running)
+        .collect();
+
+    if finished.len() > keep {
+        for run in &finished[..finished.len() - keep] {
+            db.remove(run.key())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container_runtime::ContainerRuntime;
+
+    /// A sealed store in a temporary directory, removed on drop.
+    struct TestStore(SecretStore, std::path::PathBuf);
+
+    impl TestStore {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "rezn-jobs-{}",
+                hex::encode(rand::random::<[u8; 8]>())
+            ));
+            Self(SecretStore::open(&dir, None, Vec::new()).unwrap(), dir)
+        }
+    }
+
+    impl Drop for TestStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.1);
+        }
+    }
+
+    fn job(retries: u32) -> JobFields {
+        serde_json::from_value(serde_json::json!({
+            "image": "missing:latest",
+            "retries": retries,
+        }))
+        .unwrap()
+    }
+
+    fn running(job: &JobFields) -> JobRun {
+        JobRun {
+            mol: "shop".into(),
+            job: "migrate".into(),
+            run_id: "1".into(),
+            trigger: "apply".into(),
+            generation: None,
+            status: RunStatus::Running,
+            max_attempts: job.retries.unwrap_or(0) + 1,
+            attempts: Vec::new(),
+            created_at: Utc::now(),
+            finished_at: None,
+            message: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_creates_use_up_attempts_until_the_run_fails() {
+        let store = TestStore::new();
+        let (nodes, runtimes) = NodeRegistry::memory(&["a"]);
+        runtimes[0].set_failing_creates(true);
+
+        let job = job(1);
+        let mut run = running(&job);
+
+        for _ in 0..2 {
+            let mut scheduler = Scheduler::new(nodes.nodes(), HashMap::new());
+            advance(&nodes, &store.0, &mut scheduler, &mut run, &job).await;
+            assert_eq!(run.status, RunStatus::Running);
+        }
+        assert_eq!(run.attempts.len(), 2);
+        assert!(run.attempts.iter().all(|a| a.finished_at.is_some()));
+
+        let mut scheduler = Scheduler::new(nodes.nodes(), HashMap::new());
+        advance(&nodes, &store.0, &mut scheduler, &mut run, &job).await;
+
+        assert_eq!(run.status, RunStatus::Failed);
+        assert_eq!(run.attempts.len(), 2);
+        let message = run.message.unwrap();
+        assert!(
+            message.starts_with("gave up after 2 attempts: failed to start container"),
+            "{message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_run_succeeds_once_an_attempt_exits_cleanly() {
+        let store = TestStore::new();
+        let (nodes, runtimes) = NodeRegistry::memory(&["a"]);
+
+        let job = job(0);
+        let mut run = running(&job);
+        let mut scheduler = Scheduler::new(nodes.nodes(), HashMap::new());
+        advance(&nodes, &store.0, &mut scheduler, &mut run, &job).await;
+
+        let attempt = &run.attempts[0];
+        assert!(attempt.finished_at.is_none());
+        runtimes[0]
+            .stop_container(&attempt.container, None)
+            .await
+            .unwrap();
+
+        advance(&nodes, &store.0, &mut scheduler, &mut run, &job).await;
+        assert_eq!(run.status, RunStatus::Succeeded);
+        assert_eq!(run.attempts[0].exit_code, Some(0));
+    }
+}
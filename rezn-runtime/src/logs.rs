@@ -0,0 +1,84 @@
+//! Pod log streaming: every replica's output merged into one stream of lines,
+//! each prefixed with the replica it came from.
+
+use anyhow::{anyhow, Result};
+use bytes::BytesMut;
+use futures_util::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+
+use crate::{
+    container_runtime::{LogOptions, LogStream},
+    nodes::NodeRegistry,
+    pods,
+};
+
+/// `[replica] line` strings from all replicas of `mol:pod`, interleaved in
+/// the order they arrive.
+pub async fn pod_lines(
+    nodes: &NodeRegistry,
+    mol: &str,
+    pod: &str,
+    opts: &LogOptions,
+) -> Result<BoxStream<'static, Result<String>>> {
+    let replicas = pods::find_replicas(nodes, mol, pod).await;
+    if replicas.is_empty() {
+        return Err(anyhow!("pod '{mol}:{pod}' has no replicas"));
+    }
+
+    let mut streams = Vec::with_capacity(replicas.len());
+    for (node, container) in replicas {
+        let Some(name) = container.name().map(String::from) else {
+            continue;
+        };
+
+        match node.client.logs(&name, opts).await {
+            Ok(raw) => streams.push(prefixed_lines(name, raw)),
+            Err(e) => tracing::warn!(
+                "Failed to fetch logs of {} on '{}': {:#}",
+                name,
+                node.name,
+                e
+            ),
+        }
+    }
+
+    Ok(stream::select_all(streams).boxed())
+}
+
+/// Split a raw log stream into lines and prefix each with `[name] `.
+fn prefixed_lines(name: String, raw: LogStream) -> BoxStream<'static, Result<String>> {
+    stream::unfold(
+        (raw, BytesMut::new(), false),
+        move |(mut raw, mut buf, mut done)| {
+            let name = name.clone();
+            async move {
+                loop {
+                    if let Some(i) = buf.iter().position(|b| *b == b'\n') {
+                        let line = buf.split_to(i + 1);
+                        let text = String::from_utf8_lossy(&line[..i]);
+                        return Some((Ok(format!("[{name}] {text}")), (raw, buf, done)));
+                    }
+
+                    if done {
+                        if buf.is_empty() {
+                            return None;
+                        }
+                        // Last line without a trailing newline.
+                        let text = String::from_utf8_lossy(&buf).into_owned();
+                        buf.clear();
+                        return Some((Ok(format!("[{name}] {text}")), (raw, buf, done)));
+                    }
+
+                    match raw.next().await {
+                        Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                        Some(Err(e)) => return Some((Err(e), (raw, buf, true))),
+                        None => done = true,
+                    }
+                }
+            }
+        },
+    )
+    .boxed()
+}
@@ -1,17 +1,39 @@
 mod age_keys;
+mod audit;
+mod auth;
+mod autoscale;
+mod circuit;
+mod command_key;
+mod container_runtime;
+mod deps;
+mod docker_client;
+mod images;
+mod jobs;
+mod logs;
+mod memory_runtime;
+mod modes;
+mod nodes;
 mod orqos_client;
+mod params;
+mod pods;
 mod reconcile;
 
 mod router;
 mod routes;
+mod scheduler;
+mod seal;
 mod secret;
+mod secret_files;
+mod shutdown;
 mod stats;
+mod tls;
 
 use std::env;
 use std::sync::Arc;
 
 use crate::{
-    reconcile::reconcile, router::build_router, secret::SecretStore, stats::container_stats_handler,
+    auth::Authenticator, nodes::NodeRegistry, reconcile::reconcile, router::build_router,
+    seal::Seal, secret::SecretStore, stats::container_stats_handler,
 };
 use sled::Db;
 use utoipa::ToSchema;
@@ -44,10 +66,13 @@ type StatsMap = BTreeMap<ContainerID, TimestampedStats>;
 #[derive(Clone)]
 struct AppState {
     db: Arc<Db>,
-    orqos: Arc<orqos_client::OrqosClient>,
+    nodes: Arc<NodeRegistry>,
     stats: Arc<RwLock<StatsMap>>,
     stats_tx: broadcast::Sender<serde_json::Value>,
     secret_store: SecretStore,
+    seal: Arc<Seal>,
+    auth: Arc<Authenticator>,
+    reconcile_tx: mpsc::Sender<()>,
 }
 
 #[tokio::main(flavor = "multi_thread")]
@@ -71,36 +96,67 @@ async fn main() -> anyhow::Result<()> {
 
     let (stats_tx, _) = broadcast::channel(100);
 
-    tracing::info!("Setting up ORQOS API client");
+    tracing::info!("Setting up ORQOS API clients");
 
-    let orqos_url = env::var("ORQOS_API_URL").unwrap_or_else(|_| "http://localhost:3000".into());
-    // Validate URL format
-    if !orqos_url.starts_with("http://") && !orqos_url.starts_with("https://") {
-        return Err(anyhow::anyhow!(
-            "Invalid ORQOS_API_URL: must start with http:// or https://"
-        ));
-    }
-    let orqos = Arc::new(orqos_client::OrqosClient::new(&orqos_url));
+    let signer = command_key::get_signer()?;
+    tracing::info!("Signing executor commands with key {}", signer.public_key());
+
+    let nodes = Arc::new(NodeRegistry::from_env()?);
 
-    tracing::info!("ORQOS API client initialized with URL: {}", orqos_url);
+    for node in nodes.nodes() {
+        tracing::info!(
+            "ORQOS node '{}' initialized with URL: {}",
+            node.name,
+            node.url
+        );
+    }
 
-    let identity = age_keys::get_identity()?.clone();
+    let split = age_keys::sealed_split()?;
+    let keyring = match split {
+        Some((threshold, shares)) => {
+            tracing::warn!(
+                "Age identity is sealed; secrets are unavailable until {} of {} shares are \
+                 submitted to /unseal",
+                threshold,
+                shares
+            );
+            None
+        }
+        None => {
+            let keyring = age_keys::load_keyring(None)?;
+            tracing::info!(
+                "Loaded age identity {} ({} retired identities still accepted)",
+                keyring.current.to_public(),
+                keyring.retired.len()
+            );
+            Some(keyring)
+        }
+    };
 
     let secrets_db_path = env::var("SECRETS_DB_PATH").unwrap_or_else(|_| "./secrets".into());
-    let secret_store = SecretStore::open(secrets_db_path, identity)?;
+    let escrow = age_keys::load_escrow()?;
+    let secret_store = SecretStore::open(secrets_db_path, keyring, escrow)?;
+    if let Ok(recipients) = secret_store.recipient_keys() {
+        tracing::info!("Secrets encrypted to: {}", recipients.join(", "));
+    }
+
+    let auth = Arc::new(Authenticator::from_env()?);
 
     let app_state = Arc::new(AppState {
         db,
-        orqos,
+        nodes,
         stats: Arc::new(RwLock::new(BTreeMap::default())),
         stats_tx,
         secret_store,
+        seal: Arc::new(Seal::new(split)),
+        auth,
+        reconcile_tx: reconcile_state_tx.clone(),
     });
 
     let reconcile_state = Arc::clone(&app_state);
     let is_reconciling_clone = Arc::clone(&is_reconciling);
     tokio::spawn(async move {
-        while let Some(_) = resoncile_state_rx.recv().await {
+        while resoncile_state_rx.recv().await.is_some() {
             tracing::debug!("Received reconcile trigger");
 
             if is_reconciling_clone
@@ -108,7 +164,7 @@ async fn main() -> anyhow::Result<()> {
                 .is_ok()
             {
                 tracing::debug!("[reconcile] Begin");
-                if let Err(e) = reconcile(&*reconcile_state.db, &*reconcile_state.orqos).await {
+                if let Err(e) = reconcile(&reconcile_state).await {
                     tracing::error!("[reconcile] Error: {}", e);
                 }
 
@@ -139,28 +195,62 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
-    let container_stats_handler_clone = Arc::clone(&app_state);
-
+    // Retire age identities whose rotation window has passed
+    let sweep_store = app_state.secret_store.clone();
     tokio::spawn(async move {
-        if let Err(e) = container_stats_handler(container_stats_handler_clone).await {
-            tracing::error!("Stats handler error: {}", e);
+        let policy = age_keys::RetiredKeyPolicy::from_env();
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+
+            sweep_store.retire_expired(age_keys::rotation_window());
+            if let Err(e) = age_keys::sweep_retired(policy) {
+                tracing::warn!("[age] Failed to sweep retired identities: {:#}", e);
+            }
         }
     });
 
+    for node in app_state.nodes.nodes() {
+        let Some(ws_url) = node.stats_ws_url.clone() else {
+            continue;
+        };
+        let container_stats_handler_clone = Arc::clone(&app_state);
+        let node_name = node.name.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = container_stats_handler(container_stats_handler_clone, ws_url).await {
+                tracing::error!("Stats handler error for node '{}': {}", node_name, e);
+            }
+        });
+    }
+
     let bind_addr = env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:4000".into());
     let listener = TcpListener::bind(&bind_addr).await?;
-    tracing::info!("Listening on {}", bind_addr);
 
     let router_app_state_clone = Arc::clone(&app_state);
-    let router = build_router(router_app_state_clone);
-
-    axum::serve(listener, router)
-        .with_graceful_shutdown(async {
-            tokio::signal::ctrl_c()
-                .await
-                .expect("failed to install Ctrl-C handler");
-        })
-        .await?;
+    let router =
+        build_router(router_app_state_clone).into_make_service_with_connect_info::<tls::Peer>();
+
+    match tls::config_from_env()? {
+        Some(config) => {
+            tracing::info!("Listening on {} (TLS)", bind_addr);
+            axum::serve(tls::TlsListener::new(listener, config)?, router)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+        None => {
+            tracing::info!("Listening on {}", bind_addr);
+            axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+    }
 
     Ok(())
 }
+
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("failed to install Ctrl-C handler");
+}
@@ -0,0 +1,297 @@
+//! In-memory container runtime.
+//!
+//! Nothing is actually run: containers are records that move between
+//! `running` and `exited`. Useful for exercising reconcile, scheduling and the
+//! API without an executor or a container engine.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use common::{exec::ExecStart, types::PullPolicy};
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    container_runtime::{
+        ContainerConfig, ContainerFile, ContainerInspect, ContainerPort, ContainerRuntime,
+        ContainerState, ContainerSummary, CreateReq, ExecInput, ExecOutput, ExecResult,
+        ExecSession, LogOptions, LogStream,
+    },
+    images::{ImageRef, ResolvedImage},
+};
+
+struct FakeContainer {
+    id: String,
+    labels: HashMap<String, String>,
+    ports: Vec<ContainerPort>,
+    image: String,
+    command: Option<Vec<String>>,
+    created: DateTime<Utc>,
+    finished: Option<DateTime<Utc>>,
+    status: String,
+    exit_code: Option<i64>,
+    /// Health check state shown after the status, e.g. `health: starting`.
+    health: Option<String>,
+    logs: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct MemoryRuntime {
+    containers: Mutex<HashMap<String, FakeContainer>>,
+    next_id: AtomicU64,
+    /// Fail every listing, like an executor that stopped answering.
+    #[cfg(test)]
+    unreachable: std::sync::atomic::AtomicBool,
+    /// Refuse every create, like an engine that cannot pull the image.
+    #[cfg(test)]
+    failing_creates: std::sync::atomic::AtomicBool,
+}
+
+impl MemoryRuntime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    pub fn set_unreachable(&self, unreachable: bool) {
+        self.unreachable.store(unreachable, Ordering::SeqCst);
+    }
+
+    #[cfg(test)]
+    pub fn set_failing_creates(&self, failing: bool) {
+        self.failing_creates.store(failing, Ordering::SeqCst);
+    }
+
+    /// Report `name`'s health check as `health`, or as passed with `None`.
+    #[cfg(test)]
+    pub fn set_health(&self, name: &str, health: Option<&str>) {
+        if let Some(c) = self.containers.lock().unwrap().get_mut(name) {
+            c.health = health.map(str::to_string);
+        }
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for MemoryRuntime {
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>> {
+        #[cfg(test)]
+        if self.unreachable.load(Ordering::SeqCst) {
+            return Err(anyhow!("node is unreachable"));
+        }
+
+        let (key, value) = label.split_once('=').unwrap_or((label, ""));
+        let containers = self.containers.lock().unwrap();
+
+        Ok(containers
+            .iter()
+            .filter(|(_, c)| c.labels.get(key).is_some_and(|v| v == value))
+            .map(|(name, c)| ContainerSummary {
+                id: c.id.clone(),
+                names: vec![format!("/{name}")],
+                state: Some(c.status.clone()),
+                status: Some(match &c.health {
+                    Some(health) => format!("{} ({health})", c.status),
+                    None => c.status.clone(),
+                }),
+                ports: c.ports.clone(),
+                labels: c.labels.clone(),
+                image: Some(c.image.clone()),
+                image_id: None,
+                command: c.command.as_ref().map(|cmd| cmd.join(" ")),
+                created: Some(c.created.timestamp()),
+            })
+            .collect())
+    }
+
+    async fn pull_image(&self, image: &str, _policy: PullPolicy) -> Result<ResolvedImage> {
+        let image_ref = ImageRef::parse(image)?;
+        let digest = match &image_ref.digest {
+            Some(d) => d.clone(),
+            None => format!("sha256:{}", hex::encode(Sha256::digest(image.as_bytes()))),
+        };
+
+        Ok(ResolvedImage {
+            reference: image_ref.pinned(&digest),
+            digest,
+        })
+    }
+
+    async fn create_container(&self, req: CreateReq) -> Result<()> {
+        #[cfg(test)]
+        if self.failing_creates.load(Ordering::SeqCst) {
+            return Err(anyhow!("image '{}' not found", req.image));
+        }
+
+        let mut containers = self.containers.lock().unwrap();
+        if containers.contains_key(&req.name) {
+            return Err(anyhow!("container name '{}' is already in use", req.name));
+        }
+
+        let id = format!("{:064x}", self.next_id.fetch_add(1, Ordering::SeqCst));
+        let ports = req
+            .ports
+            .iter()
+            .map(|p| ContainerPort {
+                private_port: p.container,
+                public_port: Some(p.host),
+            })
+            .collect();
+
+        containers.insert(
+            req.name.clone(),
+            FakeContainer {
+                id,
+                labels: req.labels,
+                ports,
+                image: req.image.clone(),
+                command: req.command,
+                created: Utc::now(),
+                finished: None,
+                status: "running".into(),
+                exit_code: None,
+                health: None,
+                logs: std::iter::once(format!("started {} from {}\n", req.name, req.image))
+                    .chain(req.files.iter().map(|f| format!("wrote {}\n", f.path)))
+                    .collect(),
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn write_files(&self, name: &str, files: &[ContainerFile]) -> Result<()> {
+        let mut containers = self.containers.lock().unwrap();
+        let c = containers
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("no such container: {name}"))?;
+
+        for file in files {
+            c.logs.push(format!("wrote {}\n", file.path));
+        }
+        Ok(())
+    }
+
+    async fn stop_container(&self, name: &str, _timeout: Option<u64>) -> Result<()> {
+        let mut containers = self.containers.lock().unwrap();
+        let c = containers
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("no such container: {name}"))?;
+
+        if c.status == "running" {
+            c.status = "exited".into();
+            c.exit_code = Some(0);
+            c.finished = Some(Utc::now());
+            c.logs.push(format!("stopped {name}\n"));
+        }
+        Ok(())
+    }
+
+    async fn remove_container(&self, name: &str) -> Result<()> {
+        self.containers
+            .lock()
+            .unwrap()
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("no such container: {name}"))
+    }
+
+    async fn inspect_container(&self, name: &str) -> Result<Option<ContainerInspect>> {
+        Ok(self
+            .containers
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|c| ContainerInspect {
+                created: Some(c.created.to_rfc3339()),
+                image: None,
+                restart_count: 0,
+                config: ContainerConfig {
+                    image: Some(c.image.clone()),
+                    tty: false,
+                },
+                state: ContainerState {
+                    status: c.status.clone(),
+                    exit_code: c.exit_code,
+                    oom_killed: false,
+                    error: None,
+                    started_at: Some(c.created.to_rfc3339()),
+                    finished_at: c.finished.map(|t| t.to_rfc3339()),
+                },
+            }))
+    }
+
+    async fn exec_container(
+        &self,
+        name: &str,
+        command: &[String],
+        _timeout: Duration,
+    ) -> Result<ExecResult> {
+        let mut containers = self.containers.lock().unwrap();
+        let c = containers
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("no such container: {name}"))?;
+
+        if c.status != "running" {
+            return Err(anyhow!("container {name} is not running"));
+        }
+
+        c.logs.push(format!("exec {}\n", command.join(" ")));
+        Ok(ExecResult { exit_code: Some(0) })
+    }
+
+    async fn exec_session(&self, name: &str, start: &ExecStart) -> Result<ExecSession> {
+        {
+            let mut containers = self.containers.lock().unwrap();
+            let c = containers
+                .get_mut(name)
+                .ok_or_else(|| anyhow!("no such container: {name}"))?;
+            if c.status != "running" {
+                return Err(anyhow!("container {name} is not running"));
+            }
+            c.logs.push(format!("exec {}\n", start.command.join(" ")));
+        }
+
+        // Behaves like `cat`: echoes stdin until it is closed.
+        let (session, mut input, output) = ExecSession::pair();
+        tokio::spawn(async move {
+            while let Some(msg) = input.recv().await {
+                if let ExecInput::Stdin(data) = msg {
+                    if output.send(Ok(ExecOutput::Stdout(data))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            let _ = output.send(Ok(ExecOutput::Exit(Some(0)))).await;
+        });
+
+        Ok(session)
+    }
+
+    async fn logs(&self, name: &str, opts: &LogOptions) -> Result<LogStream> {
+        let containers = self.containers.lock().unwrap();
+        let c = containers
+            .get(name)
+            .ok_or_else(|| anyhow!("no such container: {name}"))?;
+
+        let skip = opts
+            .tail
+            .map(|t| c.logs.len().saturating_sub(t))
+            .unwrap_or(0);
+        let lines: Vec<Result<Bytes>> = c.logs[skip..]
+            .iter()
+            .map(|l| Ok(Bytes::from(l.clone())))
+            .collect();
+
+        Ok(futures_util::stream::iter(lines).boxed())
+    }
+}
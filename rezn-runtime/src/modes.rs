@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sled::Db;
+use utoipa::ToSchema;
+
+/// How reconcile treats a molecule, persisted under `mode/{mol}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MoleculeMode {
+    /// Converge actual state to desired state.
+    #[default]
+    Active,
+    /// Leave the molecule alone entirely.
+    Paused,
+    /// Compute and report drift, but take no action.
+    ObserveOnly,
+}
+
+/// One difference between desired and actual state.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DriftItem {
+    /// `mol:pod` label of the affected pod.
+    pub pod: String,
+    /// `start`, `remove`, or `pull` ahead of a rolling replace.
+    pub action: String,
+    pub node: String,
+    /// Container name, or the image for `pull`.
+    pub container: String,
+}
+
+/// Drift found by the last reconcile, persisted under `drift/{mol}`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DriftReport {
+    pub computed_at: DateTime<Utc>,
+    /// Whether reconcile acted on the drift (false in observe-only mode).
+    pub acted: bool,
+    pub items: Vec<DriftItem>,
+}
+
+pub fn mode(db: &Db, mol: &str) -> Result<MoleculeMode> {
+    Ok(match db.get(format!("mode/{mol}"))? {
+        Some(bytes) => serde_json::from_slice(&bytes).context("Failed to parse molecule mode")?,
+        None => MoleculeMode::default(),
+    })
+}
+
+pub fn set_mode(db: &Db, mol: &str, mode: MoleculeMode) -> Result<()> {
+    db.insert(format!("mode/{mol}"), serde_json::to_vec(&mode)?)?;
+    db.flush()?;
+    Ok(())
+}
+
+pub fn drift(db: &Db, mol: &str) -> Result<Option<DriftReport>> {
+    db.get(format!("drift/{mol}"))?
+        .map(|bytes| serde_json::from_slice(&bytes).context("Failed to parse drift report"))
+        .transpose()
+}
+
+pub fn record_drift(db: &Db, mol: &str, report: &DriftReport) -> Result<()> {
+    db.insert(format!("drift/{mol}"), serde_json::to_vec(report)?)?;
+    Ok(())
+}
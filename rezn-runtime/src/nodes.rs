@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    command_key, container_runtime::ContainerRuntime, docker_client::DockerClient,
+    memory_runtime::MemoryRuntime, orqos_client::OrqosClient,
+};
+
+const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// How the runtime talks to a node's containers.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeBackend {
+    /// An Orqos executor over HTTP.
+    #[default]
+    Orqos,
+    /// A Docker or Podman Engine API unix socket.
+    Docker,
+    /// In-memory fake, nothing is actually run.
+    Memory,
+}
+
+impl std::str::FromStr for RuntimeBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "orqos" => Ok(Self::Orqos),
+            "docker" => Ok(Self::Docker),
+            "memory" => Ok(Self::Memory),
+            other => Err(anyhow!(
+                "unknown runtime backend '{other}' (expected orqos, docker or memory)"
+            )),
+        }
+    }
+}
+
+/// One executor endpoint as declared in the nodes file.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NodeConfig {
+    pub name: String,
+    #[serde(default)]
+    pub backend: RuntimeBackend,
+    /// Executor URL, required for the `orqos` backend.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Engine API socket for the `docker` backend, defaults to
+    /// `/var/run/docker.sock`.
+    #[serde(default)]
+    pub socket: Option<String>,
+    /// Maximum number of Rezn-managed containers this node accepts.
+    #[serde(default)]
+    pub capacity: Option<usize>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Only used by the `orqos` backend. Defaults to `<url>/stats/ws` with the
+    /// scheme switched to ws(s).
+    #[serde(default)]
+    pub stats_ws_url: Option<String>,
+}
+
+pub struct Node {
+    pub name: String,
+    pub backend: RuntimeBackend,
+    /// Where the node is reached: the executor URL, `unix://<socket>` or
+    /// `memory://`.
+    pub url: String,
+    pub capacity: Option<usize>,
+    pub labels: HashMap<String, String>,
+    /// Container stats feed; only Orqos executors publish one.
+    pub stats_ws_url: Option<String>,
+    pub client: Arc<dyn ContainerRuntime>,
+    reachable: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeStatus {
+    pub name: String,
+    pub backend: RuntimeBackend,
+    pub url: String,
+    pub capacity: Option<usize>,
+    pub labels: HashMap<String, String>,
+    pub reachable: bool,
+}
+
+impl Node {
+    fn from_config(cfg: NodeConfig) -> Result<Self> {
+        let (url, client, stats_ws_url): (String, Arc<dyn ContainerRuntime>, _) = match cfg.backend
+        {
+            RuntimeBackend::Orqos => {
+                let url = cfg
+                    .url
+                    .ok_or_else(|| anyhow!("node '{}': the orqos backend needs a url", cfg.name))?;
+                validate_url(&url).with_context(|| format!("node '{}'", cfg.name))?;
+
+                let stats_ws_url = cfg
+                    .stats_ws_url
+                    .unwrap_or_else(|| default_stats_ws_url(&url));
+
+                let client = Arc::new(OrqosClient::new(&url, command_key::get_signer()?));
+                (url, client, Some(stats_ws_url))
+            }
+            RuntimeBackend::Docker => {
+                let socket = cfg
+                    .socket
+                    .unwrap_or_else(|| DEFAULT_DOCKER_SOCKET.to_string());
+                let client = Arc::new(DockerClient::new(&socket));
+                (format!("unix://{socket}"), client, None)
+            }
+            RuntimeBackend::Memory => ("memory://".into(), Arc::new(MemoryRuntime::new()), None),
+        };
+
+        Ok(Self {
+            client,
+            name: cfg.name,
+            backend: cfg.backend,
+            url,
+            capacity: cfg.capacity,
+            labels: cfg.labels,
+            stats_ws_url,
+            // Optimistic until the first reconcile says otherwise.
+            reachable: AtomicBool::new(true),
+        })
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        self.reachable.load(Ordering::SeqCst)
+    }
+
+    pub fn set_reachable(&self, reachable: bool) {
+        let was = self.reachable.swap(reachable, Ordering::SeqCst);
+        if was != reachable {
+            if reachable {
+                tracing::info!("[nodes] Node '{}' is reachable again", self.name);
+            } else {
+                tracing::warn!("[nodes] Node '{}' became unreachable", self.name);
+            }
+        }
+    }
+
+    /// The executor's circuit breaker is open: leave the node alone until the
+    /// cooldown has passed.
+    pub fn circuit_open(&self) -> bool {
+        self.client
+            .circuit()
+            .is_some_and(|c| c.open_until.is_some_and(|t| t > Utc::now()))
+    }
+
+    /// True when every `selector` entry is present in the node labels.
+    pub fn matches(&self, selector: &HashMap<String, String>) -> bool {
+        selector
+            .iter()
+            .all(|(k, v)| self.labels.get(k).is_some_and(|l| l == v))
+    }
+
+    pub fn status(&self) -> NodeStatus {
+        NodeStatus {
+            name: self.name.clone(),
+            backend: self.backend,
+            url: self.url.clone(),
+            capacity: self.capacity,
+            labels: self.labels.clone(),
+            reachable: self.is_reachable(),
+        }
+    }
+}
+
+/// The set of executors this runtime schedules onto.
+pub struct NodeRegistry {
+    nodes: Vec<Arc<Node>>,
+}
+
+impl NodeRegistry {
+    pub fn new(configs: Vec<NodeConfig>) -> Result<Self> {
+        if configs.is_empty() {
+            return Err(anyhow!("at least one node must be configured"));
+        }
+
+        let mut nodes: Vec<Arc<Node>> = Vec::with_capacity(configs.len());
+        for cfg in configs {
+            if nodes.iter().any(|n| n.name == cfg.name) {
+                return Err(anyhow!("duplicate node name '{}'", cfg.name));
+            }
+            nodes.push(Arc::new(Node::from_config(cfg)?));
+        }
+
+        Ok(Self { nodes })
+    }
+
+    /// Reads `REZN_NODES_FILE` (a JSON array of [`NodeConfig`]) when set,
+    /// otherwise falls back to a single node using `REZN_RUNTIME_BACKEND`
+    /// (default `orqos`) with `ORQOS_API_URL` or `DOCKER_SOCKET`.
+    pub fn from_env() -> Result<Self> {
+        if let Ok(path) = env::var("REZN_NODES_FILE") {
+            let raw = fs::read_to_string(&path).with_context(|| format!("reading {path}"))?;
+            let configs: Vec<NodeConfig> =
+                serde_json::from_str(&raw).with_context(|| format!("parsing {path}"))?;
+            return Self::new(configs);
+        }
+
+        let backend = match env::var("REZN_RUNTIME_BACKEND") {
+            Ok(s) => s.parse()?,
+            Err(_) => RuntimeBackend::default(),
+        };
+        let url = env::var("ORQOS_API_URL").unwrap_or_else(|_| "http://localhost:3000".into());
+        let stats_ws_url = env::var("STATS_WS_URL").ok();
+
+        Self::new(vec![NodeConfig {
+            name: "default".into(),
+            backend,
+            url: Some(url),
+            socket: env::var("DOCKER_SOCKET").ok(),
+            capacity: None,
+            labels: HashMap::new(),
+            stats_ws_url,
+        }])
+    }
+
+    pub fn nodes(&self) -> &[Arc<Node>] {
+        &self.nodes
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<Node>> {
+        self.nodes.iter().find(|n| n.name == name)
+    }
+
+    pub fn statuses(&self) -> Vec<NodeStatus> {
+        self.nodes.iter().map(|n| n.status()).collect()
+    }
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(anyhow!(
+            "invalid executor URL '{url}': must start with http:// or https://"
+        ));
+    }
+    Ok(())
+}
+
+fn default_stats_ws_url(url: &str) -> String {
+    let base = url.trim_end_matches('/');
+    let ws = if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        base.to_string()
+    };
+    format!("{ws}/stats/ws")
+}
+
+#[cfg(test)]
+impl NodeRegistry {
+    /// One in-memory node per name, with handles on their runtimes.
+    pub fn memory(names: &[&str]) -> (Self, Vec<Arc<MemoryRuntime>>) {
+        let runtimes: Vec<_> = names
+            .iter()
+            .map(|_| Arc::new(MemoryRuntime::new()))
+            .collect();
+        let nodes = names
+            .iter()
+            .zip(&runtimes)
+            .map(|(name, runtime)| {
+                Arc::new(Node {
+                    name: name.to_string(),
+                    backend: RuntimeBackend::Memory,
+                    url: "memory://".into(),
+                    capacity: None,
+                    labels: HashMap::new(),
+                    stats_ws_url: None,
+                    client: Arc::clone(runtime) as Arc<dyn ContainerRuntime>,
+                    reachable: AtomicBool::new(true),
+                })
+            })
+            .collect();
+        (Self { nodes }, runtimes)
+    }
+}
@@ -1,57 +1,175 @@
-use anyhow::{Context, Result};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use common::{
+    exec::{self, ExecControl, ExecStart},
+    signing::CommandSigner,
+    types::PullPolicy,
+};
+use futures_util::{SinkExt, StreamExt, TryStreamExt};
+use rand::Rng;
+use reqwest::{header::CONTENT_TYPE, Client, Method, RequestBuilder, Response, StatusCode};
+use std::{env, time::Duration};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+};
+use url::Url;
 
-#[derive(Clone)]
-pub struct OrqosClient {
-    base_url: String,
-    client: Client,
-}
+use crate::{
+    circuit::{CircuitBreaker, CircuitStatus},
+    container_runtime::{
+        ContainerFile, ContainerInspect, ContainerRuntime, ContainerSummary, CreateReq, ExecInput,
+        ExecOutput, ExecResult, ExecSession, LogOptions, LogStream,
+    },
+    images::ResolvedImage,
+};
 
-#[derive(Serialize, Debug)]
-pub struct CreateReq {
-    pub name: String,
-    pub image: String,
-    pub cpu: Option<String>,
-    pub ports: Vec<PortMap>,
-    pub labels: HashMap<String, String>,
-}
+/// Retries of an idempotent call after the first attempt, unless
+/// `REZN_EXECUTOR_RETRIES` says otherwise.
+const DEFAULT_RETRIES: u32 = 3;
 
-#[derive(Serialize, Debug)]
-pub struct PortMap {
-    pub container: u16,
-    pub host: u16,
-}
+/// Pulling an image can take a while on a cold node.
+const PULL_TIMEOUT: Duration = Duration::from_secs(600);
+
+const BACKOFF_BASE: Duration = Duration::from_millis(200);
+const BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
 
-#[derive(Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct ContainerSummary {
-    #[serde(rename = "Id")]
-    pub id: String,
-    #[serde(rename = "Names")]
-    pub names: Vec<String>,
+pub struct OrqosClient {
+    base_url: String,
+    client: Client,
+    signer: &'static CommandSigner,
+    retries: u32,
+    breaker: CircuitBreaker,
 }
 
 impl OrqosClient {
-    pub fn new(base_url: impl Into<String>) -> Self {
+    pub fn new(base_url: impl Into<String>, signer: &'static CommandSigner) -> Self {
+        let base_url = base_url.into();
+        let retries = env::var("REZN_EXECUTOR_RETRIES")
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_RETRIES);
+
         Self {
-            base_url: base_url.into(),
+            breaker: CircuitBreaker::from_env(&base_url),
+            base_url,
             client: Client::builder()
-                .timeout(std::time::Duration::from_secs(20))
+                .timeout(Duration::from_secs(20))
                 .build()
                 .expect("Failed to build HTTP client"),
+            signer,
+            retries,
+        }
+    }
+
+    /// Send the request produced by `build`, through the circuit breaker.
+    ///
+    /// With `retry`, connection failures, timeouts, 5xx and 429 answers are
+    /// retried with jittered exponential backoff. `build` runs once per
+    /// attempt so every attempt carries a fresh signature nonce. Only the
+    /// outcome of the last attempt counts towards the breaker, and only an
+    /// HTTP answer counts as success.
+    async fn send(
+        &self,
+        what: &str,
+        retry: bool,
+        build: impl Fn() -> Result<RequestBuilder>,
+    ) -> Result<Response> {
+        if !self.breaker.allow() {
+            anyhow::bail!("executor circuit is open, not sending {what} request");
+        }
+
+        let attempts = if retry { self.retries + 1 } else { 1 };
+        let mut attempt = 1;
+
+        loop {
+            let outcome = build()?.send().await;
+
+            let transient = match &outcome {
+                Ok(res) => {
+                    res.status().is_server_error() || res.status() == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+
+            if !transient {
+                match &outcome {
+                    // The executor answered, even if it said no.
+                    Ok(_) => self.breaker.record_success(),
+                    // A request that could not be built says nothing about
+                    // the executor.
+                    Err(e) if e.is_builder() => {}
+                    Err(_) => self.breaker.record_failure(),
+                }
+                return outcome.with_context(|| format!("Failed to send {what} request"));
+            }
+
+            if attempt >= attempts {
+                self.breaker.record_failure();
+                return outcome.with_context(|| format!("Failed to send {what} request"));
+            }
+
+            let delay = backoff(attempt);
+            tracing::debug!(
+                "[orqos] {} request to {} failed (attempt {}/{}), retrying in {:?}",
+                what,
+                self.base_url,
+                attempt,
+                attempts,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         }
     }
 
-    pub async fn list_pod_containers(&self, pod_label: &str) -> Result<Vec<ContainerSummary>> {
+    /// A request carrying the runtime's signature over method, path and body.
+    fn signed(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<RequestBuilder> {
+        let url = format!("{}{}", self.base_url.trim_end_matches('/'), path);
+        let signed_path = Url::parse(&url)
+            .context("invalid executor URL")?
+            .path()
+            .to_string();
+        let body = match body {
+            Some(json) => serde_json::to_vec(json)?,
+            None => Vec::new(),
+        };
+
+        let sig = self.signer.sign(method.as_str(), &signed_path, &body);
+
+        let mut req = self.client.request(method, url);
+        for (name, value) in sig.headers() {
+            req = req.header(name, value);
+        }
+        if !body.is_empty() {
+            req = req.header(CONTENT_TYPE, "application/json").body(body);
+        }
+
+        Ok(req)
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for OrqosClient {
+    async fn list_containers(&self, label: &str) -> Result<Vec<ContainerSummary>> {
         let res = self
-            .client
-            .get(format!("{}/containers", self.base_url))
-            .query(&[("label", format!("pod={}", pod_label))])
-            .send()
-            .await
-            .context("Failed to send list request")?
+            .send("list", true, || {
+                Ok(self
+                    .client
+                    .get(format!("{}/containers", self.base_url))
+                    .query(&[("label", label)]))
+            })
+            .await?
+            .error_for_status()
+            .context("Container listing failed")?
             .json::<Vec<ContainerSummary>>()
             .await
             .context("Failed to parse list response")?;
@@ -59,46 +177,386 @@ impl OrqosClient {
         Ok(res)
     }
 
-    pub async fn start_container(&self, req: CreateReq) -> Result<()> {
-        tracing::debug!(
-            "Creating container request:\n{}",
-            serde_json::to_string_pretty(&req).unwrap()
-        );
+    async fn inspect_container(&self, name: &str) -> Result<Option<ContainerInspect>> {
+        let res = self
+            .send("inspect", true, || {
+                Ok(self
+                    .client
+                    .get(format!("{}/containers/{}", self.base_url, name)))
+            })
+            .await?;
+
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
 
-        self.client
-            .post(format!("{}/containers", self.base_url))
-            .json(&req)
-            .send()
+        let info = res
+            .error_for_status()
+            .context("Container inspection failed")?
+            .json::<ContainerInspect>()
             .await
-            .context("Failed to send create request")?
+            .context("Failed to parse inspect response")?;
+
+        Ok(Some(info))
+    }
+
+    async fn pull_image(&self, image: &str, policy: PullPolicy) -> Result<ResolvedImage> {
+        let body = serde_json::json!({ "image": image, "policy": policy });
+
+        let res = self
+            .send("pull", true, || {
+                Ok(self
+                    .signed(Method::POST, "/images/pull", Some(&body))?
+                    .timeout(PULL_TIMEOUT))
+            })
+            .await?
             .error_for_status()
-            .context("Container creation failed")?;
+            .context("Image pull failed")?
+            .json::<ResolvedImage>()
+            .await
+            .context("Failed to parse pull response")?;
+
+        Ok(res)
+    }
+
+    async fn create_container(&self, req: CreateReq) -> Result<()> {
+        // Env values and files may be resolved secrets: log names only.
+        tracing::debug!(
+            "Creating container {} from {} (env: {:?}, files: {:?})",
+            req.name,
+            req.image,
+            req.env.keys().collect::<Vec<_>>(),
+            req.files.iter().map(|f| &f.path).collect::<Vec<_>>()
+        );
+
+        // Same key on every attempt: the executor answers a retried create with
+        // the outcome of the first one instead of starting a second container.
+        let key = hex::encode(rand::random::<[u8; 16]>());
+        let body = serde_json::to_value(&req)?;
+
+        self.send("create", true, || {
+            Ok(self
+                .signed(Method::POST, "/containers", Some(&body))?
+                .header(IDEMPOTENCY_KEY_HEADER, &key))
+        })
+        .await?
+        .error_for_status()
+        .context("Container creation failed")?;
 
         Ok(())
     }
 
-    pub async fn stop_container(&self, name: &str) -> Result<()> {
-        self.client
-            .post(format!("{}/containers/{}/stop", self.base_url, name))
-            .send()
-            .await
-            .context("Failed to send stop container request")?
-            .error_for_status()
-            .context("Failed to stop container")?;
+    async fn write_files(&self, name: &str, files: &[ContainerFile]) -> Result<()> {
+        let body = serde_json::json!({ "files": files });
+
+        self.send("write files", true, || {
+            self.signed(
+                Method::POST,
+                &format!("/containers/{name}/files"),
+                Some(&body),
+            )
+        })
+        .await?
+        .error_for_status()
+        .context("Failed to write container files")?;
         Ok(())
     }
 
-    pub async fn remove_container(&self, name: &str) -> Result<()> {
-        self.client
-            .post(format!("{}/containers/{}/remove", self.base_url, name))
-            .json(&serde_json::json!({
-                "force": true,
-            }))
-            .send()
-            .await
-            .context("Failed to send remove container request")?
+    async fn stop_container(&self, name: &str, timeout: Option<u64>) -> Result<()> {
+        let body = timeout.map(|secs| serde_json::json!({ "timeout": secs }));
+
+        self.send("stop", true, || {
+            let mut req = self.signed(
+                Method::POST,
+                &format!("/containers/{name}/stop"),
+                body.as_ref(),
+            )?;
+            if let Some(secs) = timeout {
+                // The executor holds the request open while the container drains.
+                req = req.timeout(Duration::from_secs(secs + 20));
+            }
+            Ok(req)
+        })
+        .await?
+        .error_for_status()
+        .context("Failed to stop container")?;
+        Ok(())
+    }
+
+    async fn exec_container(
+        &self,
+        name: &str,
+        command: &[String],
+        timeout: Duration,
+    ) -> Result<ExecResult> {
+        // Not idempotent: never retried.
+        let body = serde_json::json!({ "cmd": command });
+        let res = self
+            .send("exec", false, || {
+                Ok(self
+                    .signed(
+                        Method::POST,
+                        &format!("/containers/{name}/exec"),
+                        Some(&body),
+                    )?
+                    .timeout(timeout))
+            })
+            .await?
             .error_for_status()
-            .context("Failed to remove container")?;
+            .context("Exec failed")?
+            .json::<ExecResult>()
+            .await
+            .context("Failed to parse exec response")?;
+
+        Ok(res)
+    }
+
+    async fn remove_container(&self, name: &str) -> Result<()> {
+        let body = serde_json::json!({
+            "force": true,
+        });
+
+        self.send("remove", true, || {
+            self.signed(
+                Method::POST,
+                &format!("/containers/{name}/remove"),
+                Some(&body),
+            )
+        })
+        .await?
+        .error_for_status()
+        .context("Failed to remove container")?;
         Ok(())
     }
+
+    async fn exec_session(&self, name: &str, start: &ExecStart) -> Result<ExecSession> {
+        if !self.breaker.allow() {
+            anyhow::bail!("executor circuit is open, not opening exec session");
+        }
+
+        let http_url = format!(
+            "{}/containers/{}/exec/ws",
+            self.base_url.trim_end_matches('/'),
+            name
+        );
+        let mut url = Url::parse(&http_url).context("invalid executor URL")?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|_| anyhow!("cannot derive a WebSocket URL from {}", self.base_url))?;
+
+        // The upgrade request has no body, so the signature covers the start
+        // message instead; the executor checks it against the first frame.
+        let hello = serde_json::to_string(&ExecControl::Start(start.clone()))?;
+        let sig = self.signer.sign("GET", url.path(), hello.as_bytes());
+
+        let mut req = url.as_str().into_client_request()?;
+        for (name, value) in sig.headers() {
+            req.headers_mut().insert(name, value.parse()?);
+        }
+
+        let (ws, _) = match connect_async(req).await {
+            Ok(conn) => {
+                self.breaker.record_success();
+                conn
+            }
+            Err(e) => {
+                self.breaker.record_failure();
+                return Err(e).context("Failed to open exec session");
+            }
+        };
+        let (mut sink, mut stream) = ws.split();
+        sink.send(Message::Text(hello)).await?;
+
+        let (session, mut input, output) = ExecSession::pair();
+
+        tokio::spawn(async move {
+            while let Some(msg) = input.recv().await {
+                let frame = match msg {
+                    ExecInput::Stdin(data) => Message::Binary(exec::frame(exec::STDIN, &data)),
+                    ExecInput::Resize { cols, rows } => Message::Text(
+                        serde_json::to_string(&ExecControl::Resize { cols, rows })
+                            .unwrap_or_default(),
+                    ),
+                };
+                if sink.send(frame).await.is_err() {
+                    return;
+                }
+            }
+            let _ = sink.send(Message::Binary(vec![exec::STDIN])).await;
+        });
+
+        tokio::spawn(async move {
+            while let Some(msg) = stream.next().await {
+                let item = match msg {
+                    Ok(Message::Binary(data)) => match data.split_first() {
+                        Some((&exec::STDOUT, rest)) => {
+                            ExecOutput::Stdout(Bytes::copy_from_slice(rest))
+                        }
+                        Some((&exec::STDERR, rest)) => {
+                            ExecOutput::Stderr(Bytes::copy_from_slice(rest))
+                        }
+                        _ => continue,
+                    },
+                    Ok(Message::Text(text)) => match serde_json::from_str::<ExecControl>(&text) {
+                        Ok(ExecControl::Exit { code }) => ExecOutput::Exit(code),
+                        Ok(ExecControl::Error { message }) => {
+                            let _ = output.send(Err(anyhow!(message))).await;
+                            return;
+                        }
+                        _ => continue,
+                    },
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        let _ = output.send(Err(e.into())).await;
+                        return;
+                    }
+                };
+
+                let exited = matches!(item, ExecOutput::Exit(_));
+                if output.send(Ok(item)).await.is_err() || exited {
+                    return;
+                }
+            }
+
+            // Closed without reporting an exit status.
+            let _ = output.send(Ok(ExecOutput::Exit(None))).await;
+        });
+
+        Ok(session)
+    }
+
+    async fn logs(&self, name: &str, opts: &LogOptions) -> Result<LogStream> {
+        let mut query: Vec<(&str, String)> = vec![("follow", opts.follow.to_string())];
+        if let Some(tail) = opts.tail {
+            query.push(("tail", tail.to_string()));
+        }
+        if let Some(since) = opts.since {
+            query.push(("since", since.to_string()));
+        }
+
+        let res = self
+            .send("logs", true, || {
+                let mut req = self
+                    .client
+                    .get(format!("{}/containers/{}/logs", self.base_url, name))
+                    .query(&query);
+                if opts.follow {
+                    // A followed stream stays open for as long as the container runs.
+                    req = req.timeout(Duration::from_secs(u32::MAX as u64));
+                }
+                Ok(req)
+            })
+            .await?
+            .error_for_status()
+            .context("Failed to fetch logs")?;
+
+        Ok(res.bytes_stream().map_err(anyhow::Error::from).boxed())
+    }
+
+    fn circuit(&self) -> Option<CircuitStatus> {
+        Some(self.breaker.status())
+    }
+}
+
+/// Full-jitter exponential backoff before retry number `attempt`.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BACKOFF_BASE
+        .saturating_mul(1 << attempt.min(16))
+        .min(BACKOFF_MAX);
+    rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    fn client(base_url: &str) -> OrqosClient {
+        let signer = Box::leak(Box::new(CommandSigner::generate()));
+        OrqosClient::new(base_url, signer)
+    }
+
+    /// A server that answers every connection with `raw` and hangs up.
+    async fn server(raw: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket.write_all(raw).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    async fn list(client: &OrqosClient) -> Result<Response> {
+        client
+            .send("list", false, || {
+                Ok(client.client.get(format!("{}/containers", client.base_url)))
+            })
+            .await
+    }
+
+    fn failures(client: &OrqosClient) -> u32 {
+        client.breaker.status().consecutive_failures
+    }
+
+    #[tokio::test]
+    async fn any_http_answer_counts_as_success() {
+        let url = server(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n").await;
+        let client = client(&url);
+        client.breaker.record_failure();
+        client.breaker.record_failure();
+
+        let res = list(&client).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(failures(&client), 0);
+    }
+
+    #[tokio::test]
+    async fn server_errors_count_as_failures() {
+        let url = server(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n").await;
+        let client = client(&url);
+
+        list(&client).await.unwrap();
+        assert_eq!(failures(&client), 1);
+    }
+
+    #[tokio::test]
+    async fn garbled_answers_count_as_failures() {
+        let url = server(b"this is not http\r\n\r\n").await;
+        let client = client(&url);
+
+        assert!(list(&client).await.is_err());
+        assert_eq!(failures(&client), 1);
+    }
+
+    #[tokio::test]
+    async fn refused_connections_count_as_failures() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = client(&url);
+
+        assert!(list(&client).await.is_err());
+        assert_eq!(failures(&client), 1);
+    }
+
+    #[tokio::test]
+    async fn requests_that_cannot_be_built_are_not_counted() {
+        let client = client("http://127.0.0.1:1");
+        client.breaker.record_failure();
+
+        let result = client
+            .send("list", false, || Ok(client.client.get("http://[::1")))
+            .await;
+        assert!(result.is_err());
+        assert_eq!(failures(&client), 1);
+    }
 }
@@ -0,0 +1,355 @@
+//! Runtime-selectable `enum` parameters.
+//!
+//! A molecule's `enum` instructions declare the allowed `options`; the value
+//! selected for each one lives in sled under `params/{mol}` and can change
+//! without re-signing the program. Any pod, job or cron field may vary by it:
+//!
+//! ```json
+//! { "replicas": { "$enum": "env", "cases": { "prod": 5, "staging": 2 }, "default": 1 } }
+//! ```
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use common::types::Instruction;
+use serde_json::{Map, Value};
+use sled::Db;
+
+pub type Selections = BTreeMap<String, String>;
+
+/// The `enum` instructions of a program, by name.
+pub fn enums(program: &[Instruction]) -> BTreeMap<String, Vec<String>> {
+    program
+        .iter()
+        .filter(|i| i.kind == "enum")
+        .map(|i| (i.name.clone(), i.options.clone().unwrap_or_default()))
+        .collect()
+}
+
+/// Stored selections for `mol`, without validation against the program.
+pub fn stored(db: &Db, mol: &str) -> Result<Selections> {
+    Ok(match db.get(format!("params/{mol}"))? {
+        Some(bytes) => serde_json::from_slice(&bytes)?,
+        None => Selections::new(),
+    })
+}
+
+/// Selections for `mol` that are still valid for its current program.
+///
+/// A value that is no longer among the enum's `options` is ignored.
+pub fn selections(db: &Db, mol: &str, program: &[Instruction]) -> Result<Selections> {
+    let enums = enums(program);
+    let mut selected = stored(db, mol)?;
+
+    selected.retain(|name, value| {
+        let valid = enums.get(name).is_some_and(|opts| opts.contains(value));
+        if !valid {
+            tracing::warn!(
+                "Ignoring stale selection {}={} for molecule '{}'",
+                name,
+                value,
+                mol
+            );
+        }
+        valid
+    });
+
+    Ok(selected)
+}
+
+/// Validate and persist the value selected for one enum of `mol`.
+pub fn select(db: &Db, mol: &str, program: &[Instruction], name: &str, value: &str) -> Result<()> {
+    let enums = enums(program);
+    let options = enums
+        .get(name)
+        .ok_or_else(|| anyhow!("molecule '{mol}' has no enum '{name}'"))?;
+
+    if !options.iter().any(|o| o == value) {
+        return Err(anyhow!(
+            "'{value}' is not an option of enum '{name}' (expected one of: {})",
+            options.join(", ")
+        ));
+    }
+
+    let mut selected = stored(db, mol)?;
+    selected.insert(name.to_string(), value.to_string());
+    db.insert(format!("params/{mol}"), serde_json::to_vec(&selected)?)?;
+    db.flush()?;
+
+    Ok(())
+}
+
+/// Replace every `$enum` switch in `value` by the branch for the selection.
+pub fn resolve(value: &Value, selected: &Selections) -> Result<Value> {
+    match value {
+        Value::Object(obj) if obj.contains_key("$enum") => {
+            let name = obj
+                .get("$enum")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("'$enum' must name an enum"))?;
+
+            let branch = selected
+                .get(name)
+                .and_then(|sel| obj.get("cases").and_then(|c| c.get(sel)))
+                .or_else(|| obj.get("default"))
+                .ok_or_else(|| match selected.get(name) {
+                    Some(sel) => anyhow!("no case for {name}={sel} and no default"),
+                    None => anyhow!("enum '{name}' has no selected value and no default"),
+                })?;
+
+            resolve(branch, selected)
+        }
+        Value::Object(obj) => {
+            let mut out = Map::with_capacity(obj.len());
+            for (k, v) in obj {
+                out.insert(k.clone(), resolve(v, selected)?);
+            }
+            Ok(Value::Object(out))
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|v| resolve(v, selected))
+            .collect::<Result<Vec<_>>>()
+            .map(Value::Array),
+        other => Ok(other.clone()),
+    }
+}
+
+/// `program` as it runs with `selected`. Items whose fields cannot be
+/// resolved yet keep no fields, as reconcile skips them until a case with a
+/// branch is selected.
+pub fn resolve_program(program: &[Instruction], selected: &Selections) -> Vec<Instruction> {
+    program
+        .iter()
+        .map(|item| Instruction {
+            fields: item.fields.as_ref().and_then(|f| resolve(f, selected).ok()),
+            ..item.clone()
+        })
+        .collect()
+}
+
+/// Every value `value` may resolve to, whichever cases are selected. Only
+/// switches at the top are expanded; those nested inside a branch are not.
+pub fn alternatives(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Object(obj) if obj.contains_key("$enum") => obj
+            .get("cases")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flat_map(|cases| cases.values())
+            .chain(obj.get("default"))
+            .flat_map(alternatives)
+            .collect(),
+        other => vec![other],
+    }
+}
+
+/// Reject `$enum` switches naming unknown enums or cases outside `options`.
+pub fn validate_program(program: &[Instruction]) -> Result<()> {
+    let enums = enums(program);
+
+    for item in program {
+        if let Some(fields) = &item.fields {
+            check(fields, &enums).map_err(|e| anyhow!("{} '{}': {}", item.kind, item.name, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn check(value: &Value, enums: &BTreeMap<String, Vec<String>>) -> Result<()> {
+    match value {
+        Value::Object(obj) if obj.contains_key("$enum") => {
+            let name = obj
+                .get("$enum")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("'$enum' must name an enum"))?;
+            let options = enums
+                .get(name)
+                .ok_or_else(|| anyhow!("unknown enum '{name}'"))?;
+
+            let cases = obj
+                .get("cases")
+                .and_then(Value::as_object)
+                .ok_or_else(|| anyhow!("'$enum' switch on '{name}' needs a 'cases' object"))?;
+
+            for (case, branch) in cases {
+                if !options.contains(case) {
+                    return Err(anyhow!("'{case}' is not an option of enum '{name}'"));
+                }
+                check(branch, enums)?;
+            }
+
+            match obj.get("default") {
+                Some(default) => check(default, enums),
+                None => Ok(()),
+            }
+        }
+        Value::Object(obj) => obj.values().try_for_each(|v| check(v, enums)),
+        Value::Array(items) => items.iter().try_for_each(|v| check(v, enums)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn program(json: Value) -> Vec<Instruction> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn selected(pairs: &[(&str, &str)]) -> Selections {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn switches_resolve_to_the_selected_case_or_the_default() {
+        let fields = json!({
+            "image": "web:1",
+            "replicas": {"$enum": "env", "cases": {"prod": 5, "staging": 2}, "default": 1},
+            "ports": [{"$enum": "env", "cases": {"prod": 443}, "default": 8080}],
+        });
+
+        let prod = resolve(&fields, &selected(&[("env", "prod")])).unwrap();
+        assert_eq!(
+            prod,
+            json!({"image": "web:1", "replicas": 5, "ports": [443]})
+        );
+
+        let staging = resolve(&fields, &selected(&[("env", "staging")])).unwrap();
+        assert_eq!(
+            staging,
+            json!({"image": "web:1", "replicas": 2, "ports": [8080]})
+        );
+
+        let unset = resolve(&fields, &Selections::new()).unwrap();
+        assert_eq!(
+            unset,
+            json!({"image": "web:1", "replicas": 1, "ports": [8080]})
+        );
+    }
+
+    #[test]
+    fn switches_nest_inside_branches() {
+        let fields = json!({"$enum": "env", "cases": {
+            "prod": {"$enum": "region", "cases": {"eu": "web:eu"}, "default": "web:us"},
+        }, "default": "web:dev"});
+
+        let pick = |pairs: &[(&str, &str)]| resolve(&fields, &selected(pairs)).unwrap();
+        assert_eq!(pick(&[("env", "prod"), ("region", "eu")]), json!("web:eu"));
+        assert_eq!(pick(&[("env", "prod")]), json!("web:us"));
+        assert_eq!(pick(&[("region", "eu")]), json!("web:dev"));
+    }
+
+    #[test]
+    fn switches_without_a_matching_branch_fail_to_resolve() {
+        let fields = json!({"$enum": "env", "cases": {"prod": 5}});
+
+        let err = resolve(&fields, &selected(&[("env", "staging")])).unwrap_err();
+        assert_eq!(err.to_string(), "no case for env=staging and no default");
+
+        let err = resolve(&fields, &Selections::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "enum 'env' has no selected value and no default"
+        );
+
+        let err = resolve(&json!({"$enum": 3}), &Selections::new()).unwrap_err();
+        assert_eq!(err.to_string(), "'$enum' must name an enum");
+    }
+
+    #[test]
+    fn programs_resolve_item_by_item() {
+        let raw = program(json!([
+            {"kind": "enum", "name": "env", "options": ["prod", "staging"]},
+            {"kind": "pod", "name": "web", "fields": {
+                "image": {"$enum": "env", "cases": {"prod": "web:1"}, "default": "web:dev"},
+            }},
+            {"kind": "pod", "name": "db", "fields": {
+                "image": {"$enum": "env", "cases": {"prod": "db:1"}},
+            }},
+        ]));
+
+        let prod = resolve_program(&raw, &selected(&[("env", "prod")]));
+        assert_eq!(prod[0].options, raw[0].options);
+        assert_eq!(prod[1].fields, Some(json!({"image": "web:1"})));
+        assert_eq!(prod[2].fields, Some(json!({"image": "db:1"})));
+
+        let staging = resolve_program(&raw, &selected(&[("env", "staging")]));
+        assert_eq!(staging[1].fields, Some(json!({"image": "web:dev"})));
+        assert_eq!(staging[2].fields, None);
+    }
+
+    #[test]
+    fn alternatives_cover_every_case_and_the_default() {
+        let fields = json!({"$enum": "env", "cases": {
+            "prod": {"$enum": "region", "cases": {"eu": "a"}, "default": "b"},
+            "staging": "c",
+        }, "default": "d"});
+
+        let all: Vec<_> = alternatives(&fields).into_iter().cloned().collect();
+        assert_eq!(all, [json!("a"), json!("b"), json!("c"), json!("d")]);
+        assert_eq!(alternatives(&json!(1)), [&json!(1)]);
+    }
+
+    #[test]
+    fn programs_may_only_switch_on_declared_options() {
+        let with = |fields: Value| {
+            validate_program(&program(json!([
+                {"kind": "enum", "name": "env", "options": ["prod", "staging"]},
+                {"kind": "pod", "name": "web", "fields": fields},
+            ])))
+        };
+
+        with(json!({"replicas": {"$enum": "env", "cases": {"prod": 5}, "default": 1}})).unwrap();
+
+        let err = with(json!({"replicas": {"$enum": "tier", "cases": {}}})).unwrap_err();
+        assert_eq!(err.to_string(), "pod 'web': unknown enum 'tier'");
+
+        let err = with(json!({"replicas": {"$enum": "env", "cases": {"dev": 1}}})).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pod 'web': 'dev' is not an option of enum 'env'"
+        );
+
+        let err = with(json!({"replicas": {"$enum": "env", "default": 1}})).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pod 'web': '$enum' switch on 'env' needs a 'cases' object"
+        );
+
+        let nested =
+            json!({"env": [{"$enum": "env", "cases": {"prod": {"$enum": "x", "cases": {}}}}]});
+        assert!(with(nested).is_err());
+    }
+
+    #[test]
+    fn selections_outlive_only_the_options_that_remain() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let before = program(json!([
+            {"kind": "enum", "name": "env", "options": ["prod", "staging"]},
+            {"kind": "enum", "name": "region", "options": ["eu", "us"]},
+        ]));
+
+        select(&db, "shop", &before, "env", "staging").unwrap();
+        select(&db, "shop", &before, "region", "eu").unwrap();
+        assert!(select(&db, "shop", &before, "env", "dev").is_err());
+        assert!(select(&db, "shop", &before, "tier", "gold").is_err());
+
+        let after = program(json!([
+            {"kind": "enum", "name": "env", "options": ["prod"]},
+            {"kind": "enum", "name": "region", "options": ["eu", "us"]},
+        ]));
+        assert_eq!(
+            selections(&db, "shop", &after).unwrap(),
+            selected(&[("region", "eu")])
+        );
+        assert_eq!(stored(&db, "shop").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Datelike, Utc};
+use futures_util::future::join_all;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    container_runtime::{ContainerPhase, ContainerSummary},
+    nodes::Node,
+    nodes::NodeRegistry,
+};
+
+/// One replica of a pod as its node reports it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ContainerDetails {
+    pub node: String,
+    pub name: String,
+    pub id: String,
+    /// The image as requested, usually pinned to a digest.
+    pub image: Option<String>,
+    /// Local ID of the image the container runs.
+    pub image_id: Option<String>,
+    pub command: Option<String>,
+    /// Digest the image was resolved to when the replica was created.
+    pub digest: Option<String>,
+    /// Hash of the pod spec the replica was created from.
+    pub spec: Option<String>,
+    pub state: ContainerPhase,
+    /// The engine's human readable status, e.g. `Up 5 minutes (healthy)`.
+    pub status: Option<String>,
+    pub ready: bool,
+    pub exit_code: Option<i64>,
+    pub restart_count: u64,
+    pub oom_killed: bool,
+    /// Why the engine could not start the container, if it could not.
+    pub error: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub labels: HashMap<String, String>,
+}
+
+/// The replicas of `mol:pod` currently known to reachable nodes.
+///
+/// Nodes that fail to answer are skipped; reconcile takes care of marking
+/// them unreachable.
+pub async fn find_replicas(
+    nodes: &NodeRegistry,
+    mol: &str,
+    pod: &str,
+) -> Vec<(Arc<Node>, ContainerSummary)> {
+    let pod_label = format!("{mol}:{pod}");
+    let label = format!("pod={pod_label}");
+
+    let per_node = join_all(
+        nodes
+            .nodes()
+            .iter()
+            .filter(|n| n.is_reachable())
+            .map(|node| async {
+                match node.client.list_containers(&label).await {
+                    Ok(containers) => containers
+                        .into_iter()
+                        .filter(|c| c.labels.get("pod") == Some(&pod_label))
+                        .map(|c| (Arc::clone(node), c))
+                        .collect(),
+                    Err(e) => {
+                        tracing::warn!("Failed to list {} on '{}': {:#}", label, node.name, e);
+                        Vec::new()
+                    }
+                }
+            }),
+    )
+    .await;
+
+    per_node.into_iter().flatten().collect()
+}
+
+/// Every container of `mol:pod`, running or not, with what the nodes report
+/// on inspection. Sorted by name.
+pub async fn describe(nodes: &NodeRegistry, mol: &str, pod: &str) -> Vec<ContainerDetails> {
+    let replicas = find_replicas(nodes, mol, pod).await;
+
+    let mut details = join_all(replicas.into_iter().map(|(node, c)| async move {
+        let name = c.name().unwrap_or(&c.id).to_string();
+        let info = match node.client.inspect_container(&name).await {
+            Ok(info) => info,
+            Err(e) => {
+                tracing::warn!("Failed to inspect {} on '{}': {:#}", name, node.name, e);
+                None
+            }
+        };
+
+        let mut d = ContainerDetails {
+            node: node.name.clone(),
+            id: c.id.clone(),
+            image: c.image.clone(),
+            image_id: c.image_id.clone(),
+            command: c.command.clone(),
+            digest: c.labels.get("digest").cloned(),
+            spec: c.labels.get("spec").cloned(),
+            state: c.phase(),
+            status: c.status.clone(),
+            ready: c.is_ready(),
+            exit_code: c.exit_code(),
+            restart_count: 0,
+            oom_killed: false,
+            error: None,
+            created_at: c.created.and_then(|t| DateTime::from_timestamp(t, 0)),
+            started_at: None,
+            finished_at: None,
+            labels: c.labels.clone(),
+            name,
+        };
+
+        if let Some(info) = info {
+            d.state = info.state.phase();
+            if info.state.is_finished() {
+                d.exit_code = info.state.exit_code;
+            }
+            d.restart_count = info.restart_count;
+            d.oom_killed = info.state.oom_killed;
+            d.error = info.state.error.filter(|e| !e.is_empty());
+            d.started_at = info.state.started_at.as_deref().and_then(engine_time);
+            d.finished_at = info.state.finished_at.as_deref().and_then(engine_time);
+            d.created_at = info
+                .created
+                .as_deref()
+                .and_then(engine_time)
+                .or(d.created_at);
+            if d.image_id.is_none() {
+                d.image_id = info.image;
+            }
+            if d.image.is_none() {
+                d.image = info.config.image;
+            }
+        }
+
+        d
+    }))
+    .await;
+
+    details.sort_by(|a, b| a.name.cmp(&b.name));
+    details
+}
+
+/// Parse an engine timestamp; the zero time it uses for "never" becomes `None`.
+fn engine_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+        .filter(|t| t.year() > 1)
+}
@@ -1,13 +1,95 @@
-use crate::orqos_client::{CreateReq, OrqosClient, PortMap};
+use crate::autoscale;
+use crate::container_runtime::{ContainerPhase, ContainerSummary, CreateReq, PortMap};
+use crate::deps;
+use crate::images::{self, Provenance};
+use crate::jobs;
+use crate::modes::{self, DriftItem, DriftReport, MoleculeMode};
+use crate::nodes::{Node, NodeRegistry};
+use crate::params;
+use crate::scheduler::Scheduler;
+use crate::secret;
+use crate::secret_files;
+use crate::shutdown;
 use anyhow::{Context, Result};
 use chrono::Utc;
-use common::types::{DesiredMap, PodFields, PodSpec};
+use common::types::{
+    DependencyCondition, DesiredMap, EnvVar, PodFields, PodSpec, PreStopHook, PullPolicy,
+    SecretFile,
+};
+use futures_util::future::join_all;
+use sha2::{Digest, Sha256};
 use sled::Db;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
-pub async fn reconcile(db: &Db, orqos: &OrqosClient) -> Result<()> {
+use crate::AppState;
+
+/// Container label holding the hash of the pod spec a replica was created from.
+const SPEC_LABEL: &str = "spec";
+
+/// One pod's containers keyed by node name.
+type PerNode = HashMap<String, Vec<ContainerSummary>>;
+
+/// A single step that brings one pod closer to its desired state.
+enum Action {
+    /// Fetch the image ahead of a rolling replace on a node that runs the
+    /// old version, so the swap itself does not wait on the registry.
+    Prepull {
+        node: Arc<Node>,
+        image: String,
+        policy: PullPolicy,
+    },
+    Start {
+        node: Arc<Node>,
+        req: CreateReq,
+        policy: PullPolicy,
+        /// Resolved into `req.env` right before the container is created.
+        env: HashMap<String, EnvVar>,
+        /// Read into `req.files` along with `env`.
+        secret_files: Vec<SecretFile>,
+    },
+    /// Rewrite a replica's secret files after a secret it mounts changed.
+    Refresh {
+        node: Arc<Node>,
+        container: String,
+        secret_files: Vec<SecretFile>,
+    },
+    Remove {
+        node: Arc<Node>,
+        container: ContainerSummary,
+        grace: Option<u64>,
+        pre_stop: Option<PreStopHook>,
+    },
+}
+
+impl Action {
+    fn drift_item(&self, pod_label: &str) -> DriftItem {
+        let (action, node, container) = match self {
+            Action::Prepull { node, image, .. } => ("pull", node, image.as_str()),
+            Action::Start { node, req, .. } => ("start", node, req.name.as_str()),
+            Action::Refresh {
+                node, container, ..
+            } => ("refresh", node, container.as_str()),
+            Action::Remove {
+                node, container, ..
+            } => ("remove", node, container.name().unwrap_or(&container.id)),
+        };
+
+        DriftItem {
+            pod: pod_label.to_string(),
+            action: action.to_string(),
+            node: node.name.clone(),
+            container: container.to_string(),
+        }
+    }
+}
+
+pub async fn reconcile(app: &AppState) -> Result<()> {
     tracing::debug!("Reconcile: starting");
 
+    let db = &*app.db;
+    let nodes = &*app.nodes;
+
     let data = match db.get("desired") {
         Ok(Some(bytes)) => bytes,
         Ok(None) => {
@@ -30,14 +112,35 @@ pub async fn reconcile(db: &Db, orqos: &OrqosClient) -> Result<()> {
         desired.len()
     );
 
+    let mut molecule_modes: HashMap<String, MoleculeMode> = HashMap::new();
+    for mol_name in desired.keys() {
+        molecule_modes.insert(mol_name.clone(), modes::mode(db, mol_name)?);
+    }
+    let mode_of = |mol: &str| molecule_modes.get(mol).copied().unwrap_or_default();
+
     let mut desired_pods = Vec::<PodSpec>::new();
 
     for (mol_name, atoms) in &desired {
+        let selected = params::selections(db, mol_name, atoms)?;
+
         for item in atoms {
             if item.kind == "pod" {
                 if let Some(fields_val) = &item.fields {
-                    let fields: PodFields = serde_json::from_value(fields_val.clone())
-                        .with_context(|| {
+                    let fields_val = match params::resolve(fields_val, &selected) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            tracing::warn!(
+                                "Skipping pod '{}' in '{}': {:#}",
+                                item.name,
+                                mol_name,
+                                e
+                            );
+                            continue;
+                        }
+                    };
+
+                    let fields: PodFields =
+                        serde_json::from_value(fields_val).with_context(|| {
                             format!("Failed to parse pod fields in instruction '{mol_name}'")
                         })?;
 
@@ -45,93 +148,465 @@ pub async fn reconcile(db: &Db, orqos: &OrqosClient) -> Result<()> {
                         mol_name: mol_name.clone(),
                         name: item.name.clone(),
                         image: fields.image,
+                        pull_policy: fields.pull_policy.unwrap_or_default(),
                         replicas: fields.replicas,
                         ports: fields.ports,
+                        env: fields.env.map(|e| e.0).unwrap_or_default(),
+                        node_selector: fields.node_selector.unwrap_or_default(),
+                        autoscale: fields.autoscale,
+                        depends_on: fields.depends_on.unwrap_or_default(),
+                        stop_grace_period: fields.stop_grace_period,
+                        pre_stop: fields.pre_stop,
+                        restart_on_secret_change: fields.restart_on_secret_change.unwrap_or(true),
+                        secret_files: fields.secret_files.unwrap_or_default(),
                     });
                 }
             }
         }
     }
 
-    let mut tasks = vec![];
+    let mut desired_pods = order_by_dependencies(desired_pods);
+
+    // ---- observe: what is running where ----
+
+    if nodes.nodes().iter().all(|n| n.circuit_open()) {
+        // Every executor is down: acting on an empty observation would only
+        // reschedule replicas that are probably still running.
+        tracing::warn!("Reconcile: all executor circuits are open, pausing");
+        return Ok(());
+    }
+
+    let (observed, finished) = split_finished(observe(nodes, &desired_pods).await);
+
+    let mut load: HashMap<String, usize> = HashMap::new();
+    for per_node in &observed {
+        for (node_name, containers) in per_node {
+            *load.entry(node_name.clone()).or_default() += containers.len();
+        }
+    }
+
+    // ---- autoscale: derive the effective replica count from stats ----
+
+    let stats = app.stats.read().await.clone();
+
+    for (pod, per_node) in desired_pods.iter_mut().zip(&observed) {
+        let Some(spec) = &pod.autoscale else {
+            continue;
+        };
+
+        let containers: Vec<_> = per_node.values().flatten().collect();
+
+        let replicas = match mode_of(&pod.mol_name) {
+            MoleculeMode::Active => {
+                autoscale::effective_replicas(db, pod, spec, &containers, &stats)
+            }
+            // Report drift against the last decision without making new ones.
+            _ => autoscale::current_replicas(db, pod, spec),
+        };
+
+        match replicas {
+            Ok(replicas) => pod.replicas = replicas,
+            Err(e) => tracing::warn!(
+                "Autoscaling {}:{} failed, keeping {} replicas: {:#}",
+                pod.mol_name,
+                pod.name,
+                pod.replicas,
+                e
+            ),
+        }
+    }
+
+    // ---- dependencies: which pods may start new replicas this round ----
+
+    let deps_ok = dependencies_satisfied(&desired_pods, &observed);
+
+    // ---- plan: start or remove replicas to match the desired count ----
+
+    let mut scheduler = Scheduler::new(nodes.nodes(), load);
+    let mut plans: Vec<(String, String, Vec<Action>)> = Vec::with_capacity(desired_pods.len());
+
+    for (((pod, per_node), deps_ok), finished) in
+        desired_pods.iter().zip(observed).zip(deps_ok).zip(finished)
+    {
+        if mode_of(&pod.mol_name) == MoleculeMode::Paused {
+            continue;
+        }
 
-    for pod in desired_pods {
         let pod_label = format!("{}:{}", pod.mol_name, pod.name);
 
-        let running = orqos
-            .list_pod_containers(&pod_label)
-            .await
-            .context("Failed to query Orqos for running containers")?;
+        let secrets = match secret_versions(&app.secret_store, pod) {
+            Ok(secrets) => secrets,
+            Err(e) => {
+                tracing::warn!("[{}] Failed to read secret versions: {:#}", pod_label, e);
+                continue;
+            }
+        };
 
-        // Clone all necessary data before moving into the async block
-        let mol_name = pod.mol_name.clone();
-        let pod_name = pod.name.clone();
-        let pod_image = pod.image.clone();
-        let pod_ports = pod.ports.clone();
-        let pod_replicas = pod.replicas;
-        let orqos = orqos.clone();
-        let mut labels: HashMap<String, String> = HashMap::new();
+        let mut actions = Vec::new();
 
-        labels.insert("mol".to_string(), format!("{}", pod.mol_name));
-        labels.insert("pod".to_string(), pod_label.clone());
+        // Replicas whose process is gone are not counted below, so replacements
+        // get scheduled in the same round; the dead container is cleared out.
+        for (node_name, container) in finished {
+            tracing::warn!(
+                "[{}] {} on '{}' has {} (exit code {}), replacing it",
+                pod_label,
+                container.name().unwrap_or(&container.id),
+                node_name,
+                container.state.as_deref().unwrap_or("stopped"),
+                container
+                    .exit_code()
+                    .map_or_else(|| "unknown".to_string(), |c| c.to_string())
+            );
 
-        let running = running.clone();
+            if let Some(node) = nodes.get(&node_name) {
+                actions.push(Action::Remove {
+                    node: Arc::clone(node),
+                    container,
+                    grace: Some(0),
+                    pre_stop: None,
+                });
+            }
+        }
 
-        let task = tokio::spawn(async move {
-            let matches: Vec<_> = running
-                .iter()
-                .filter(|c| {
-                    c.names.iter().any(|n| {
-                        n.trim_start_matches('/')
-                            .starts_with(&format!("{}-{}-", mol_name, pod_name))
-                    })
-                })
-                .collect();
-
-            if matches.len() < pod_replicas {
-                for _ in 0..(pod_replicas - matches.len()) {
-                    let cname: String = format!(
-                        "{}-{}-{}",
-                        mol_name,
-                        pod_name,
-                        Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        for c in per_node.values().flatten() {
+            if c.phase() == ContainerPhase::Restarting {
+                tracing::debug!(
+                    "[{}] {} is being restarted by the engine",
+                    pod_label,
+                    c.name().unwrap_or(&c.id)
+                );
+            }
+        }
+
+        let spec = spec_hash(pod, &secrets);
+
+        // Current replicas whose mounted secrets fell behind get their files
+        // rewritten; outdated ones are about to be replaced anyway.
+        if !pod.secret_files.is_empty() {
+            match secret_files::versions(&app.secret_store, &pod.secret_files) {
+                Ok(versions) => {
+                    for (node_name, containers) in &per_node {
+                        let Some(node) = nodes.get(node_name) else {
+                            continue;
+                        };
+                        for c in containers {
+                            let Some(name) = c.name() else {
+                                continue;
+                            };
+                            let current = c.labels.get(SPEC_LABEL) == Some(&spec)
+                                && c.phase() == ContainerPhase::Running;
+                            if current
+                                && secret_files::applied(db, name)?.as_ref() != Some(&versions)
+                            {
+                                actions.push(Action::Refresh {
+                                    node: Arc::clone(node),
+                                    container: name.to_string(),
+                                    secret_files: pod.secret_files.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "[{}] Failed to read secret file versions: {:#}",
+                        pod_label,
+                        e
+                    )
+                }
+            }
+        }
+
+        let mut placed: HashMap<String, usize> = per_node
+            .iter()
+            .map(|(node_name, containers)| (node_name.clone(), containers.len()))
+            .collect();
+        let running: usize = placed.values().sum();
+
+        // Replicas created from an older spec. Containers from before spec
+        // labels existed are left alone rather than all restarted at once.
+        let is_outdated =
+            |c: &ContainerSummary| c.labels.get(SPEC_LABEL).is_some_and(|s| *s != spec);
+        let outdated = per_node
+            .values()
+            .flatten()
+            .filter(|c| is_outdated(c))
+            .count();
+        let current_ready = per_node
+            .values()
+            .flatten()
+            .filter(|c| !is_outdated(c))
+            .all(|c| c.is_ready());
+        // While a rollout waits for its new replicas, one of them may stand
+        // over count; anything beyond that is surplus even mid-rollout.
+        let surge = usize::from(outdated > 0 && !current_ready);
+
+        if running < pod.replicas && !deps_ok {
+            tracing::debug!("[{}] Waiting for dependencies before starting", pod_label);
+        } else if running < pod.replicas {
+            for _ in 0..(pod.replicas - running) {
+                let Some(node) = scheduler.place(&pod.node_selector, &mut placed) else {
+                    tracing::warn!(
+                        "No eligible node for a replica of {} ({} of {} scheduled)",
+                        pod_label,
+                        running + actions.len(),
+                        pod.replicas
                     );
-                    let image = pod_image.clone();
-                    let ports = pod_ports.clone();
-                    let orqos = orqos.clone();
-
-                    let port_maps: Vec<PortMap> = ports
-                        .iter()
-                        .map(|p| PortMap {
-                            container: *p,
-                            host: 0,
-                        })
-                        .collect();
-
-                    let req = CreateReq {
-                        name: cname.clone(),
-                        image,
-                        ports: port_maps,
-                        labels: labels.clone(),
-                        cpu: None,
+                    break;
+                };
+
+                actions.push(Action::Start {
+                    req: create_request(pod, &pod_label, &spec, &node),
+                    policy: pod.pull_policy,
+                    env: pod.env.clone(),
+                    secret_files: pod.secret_files.clone(),
+                    node,
+                });
+            }
+        } else if running > pod.replicas + surge {
+            let mut remaining = per_node;
+            let mut excess = running - pod.replicas - surge;
+
+            // Outdated replicas go first.
+            for (node_name, containers) in remaining.iter_mut() {
+                while excess > 0 {
+                    let Some(i) = containers.iter().position(is_outdated) else {
+                        break;
+                    };
+                    let Some(node) = nodes.get(node_name) else {
+                        break;
                     };
 
-                    if let Err(e) = orqos.start_container(req).await {
-                        tracing::warn!("Failed to start {}: {}", cname, e);
+                    if let Some(n) = placed.get_mut(node_name) {
+                        *n = n.saturating_sub(1);
                     }
+                    excess -= 1;
+
+                    actions.push(Action::Remove {
+                        node: Arc::clone(node),
+                        container: containers.remove(i),
+                        grace: pod.stop_grace_period,
+                        pre_stop: pod.pre_stop.clone(),
+                    });
                 }
-            } else if matches.len() > pod_replicas {
-                for c in matches.iter().take(matches.len() - pod_replicas) {
-                    if let Some(name) = c.names.first().map(|s| s.trim_start_matches('/')) {
-                        if let Err(e) = orqos.stop_container(name).await {
-                            tracing::warn!("Failed to stop {}: {}", name, e);
+            }
+
+            // Then take from the most crowded nodes first, newest entries last.
+            for _ in 0..excess {
+                let Some(node) = scheduler.evict(&mut placed) else {
+                    break;
+                };
+                let Some(c) = remaining.get_mut(&node.name).and_then(|cs| cs.pop()) else {
+                    continue;
+                };
+
+                actions.push(Action::Remove {
+                    node,
+                    container: c,
+                    grace: pod.stop_grace_period,
+                    pre_stop: pod.pre_stop.clone(),
+                });
+            }
+        } else if outdated > 0 && !current_ready {
+            tracing::debug!(
+                "[{}] Rolling replace: waiting for new replicas to become ready",
+                pod_label
+            );
+        } else if running == pod.replicas && outdated > 0 && deps_ok {
+            // Rolling replace, one surge replica at a time: once it is ready,
+            // the next round is over count and removes an outdated replica.
+            let mut pulled: Vec<&str> = Vec::new();
+            for (node_name, containers) in &per_node {
+                if containers.iter().any(is_outdated) && !pulled.contains(&node_name.as_str()) {
+                    if let Some(node) = nodes.get(node_name) {
+                        actions.push(Action::Prepull {
+                            node: Arc::clone(node),
+                            image: pod.image.clone(),
+                            policy: pod.pull_policy,
+                        });
+                    }
+                    pulled.push(node_name);
+                }
+            }
+
+            match scheduler.place(&pod.node_selector, &mut placed) {
+                Some(node) => {
+                    tracing::info!(
+                        "[{}] Rolling replace: {} replica(s) left on the old spec",
+                        pod_label,
+                        outdated
+                    );
+                    actions.push(Action::Start {
+                        req: create_request(pod, &pod_label, &spec, &node),
+                        policy: pod.pull_policy,
+                        env: pod.env.clone(),
+                        secret_files: pod.secret_files.clone(),
+                        node,
+                    });
+                }
+                None => tracing::warn!(
+                    "No eligible node for a surge replica of {}, rolling replace stalled",
+                    pod_label
+                ),
+            }
+        }
+
+        if !actions.is_empty() {
+            plans.push((pod.mol_name.clone(), pod_label, actions));
+        }
+    }
+
+    // ---- drift: report what differs, drop actions for observe-only ----
+
+    let now = Utc::now();
+    for (mol_name, mode) in &molecule_modes {
+        if *mode == MoleculeMode::Paused {
+            continue;
+        }
+
+        let items: Vec<DriftItem> = plans
+            .iter()
+            .filter(|(mol, _, _)| mol == mol_name)
+            .flat_map(|(_, pod_label, actions)| actions.iter().map(|a| a.drift_item(pod_label)))
+            .collect();
+
+        if *mode == MoleculeMode::ObserveOnly && !items.is_empty() {
+            tracing::info!(
+                "[observe] Molecule '{}' has drifted ({} pending actions)",
+                mol_name,
+                items.len()
+            );
+        }
+
+        let report = DriftReport {
+            computed_at: now,
+            acted: *mode == MoleculeMode::Active,
+            items,
+        };
+        if let Err(e) = modes::record_drift(db, mol_name, &report) {
+            tracing::warn!("Failed to record drift for '{}': {}", mol_name, e);
+        }
+    }
+
+    plans.retain(|(mol, _, _)| mode_of(mol) == MoleculeMode::Active);
+
+    // ---- execute ----
+
+    let mut tasks = vec![];
+
+    for (mol_name, pod_label, actions) in plans {
+        let db = Arc::clone(&app.db);
+        let store = app.secret_store.clone();
+        let task = tokio::spawn(async move {
+            for action in actions {
+                match action {
+                    Action::Prepull {
+                        node,
+                        image,
+                        policy,
+                    } => {
+                        tracing::debug!("[{}] Pre-pulling {} on {}", pod_label, image, node.name);
+
+                        if let Err(e) = node.client.pull_image(&image, policy).await {
+                            tracing::warn!("Failed to pull {} on {}: {:#}", image, node.name, e);
+                        }
+                    }
+                    Action::Start {
+                        node,
+                        mut req,
+                        policy,
+                        env,
+                        secret_files,
+                    } => {
+                        let cname = req.name.clone();
+                        tracing::debug!("[{}] Starting {} on {}", pod_label, cname, node.name);
+
+                        // Versions first: a secret changing while the files are
+                        // read only costs one extra refresh.
+                        let resolved =
+                            secret_files::versions(&store, &secret_files).and_then(|versions| {
+                                let env = secret::resolve_env(&store, &env)?;
+                                let files = secret_files::resolve(&store, &secret_files)?;
+                                Ok((versions, env, files))
+                            });
+                        let versions = match resolved {
+                            Ok((versions, env, files)) => {
+                                req.env = env.vars;
+                                req.files = env.files;
+                                req.files.extend(files);
+                                versions
+                            }
+                            Err(e) => {
+                                tracing::warn!("Not starting {}: {:#}", cname, e);
+                                continue;
+                            }
+                        };
+
+                        if let Err(e) =
+                            start_pinned(&db, &mol_name, &pod_label, &node, req, policy).await
+                        {
+                            tracing::warn!("Failed to start {} on {}: {:#}", cname, node.name, e);
+                        } else if !secret_files.is_empty() {
+                            if let Err(e) = secret_files::record(&db, &cname, &versions) {
+                                tracing::warn!("Failed to record secret files of {}: {}", cname, e);
+                            }
                         }
+                    }
+                    Action::Refresh {
+                        node,
+                        container,
+                        secret_files,
+                    } => {
+                        tracing::info!(
+                            "[{}] Refreshing secret files of {} on {}",
+                            pod_label,
+                            container,
+                            node.name
+                        );
 
-                        if let Err(e) = orqos.remove_container(name).await {
-                            tracing::warn!("Failed to remove {}: {}", name, e);
+                        let refreshed = async {
+                            let versions = secret_files::versions(&store, &secret_files)?;
+                            let files = secret_files::resolve(&store, &secret_files)?;
+                            node.client.write_files(&container, &files).await?;
+                            secret_files::record(&db, &container, &versions)
+                        };
+                        if let Err(e) = refreshed.await {
+                            tracing::warn!(
+                                "Failed to refresh secret files of {} on {}: {:#}",
+                                container,
+                                node.name,
+                                e
+                            );
+                        }
+                    }
+                    Action::Remove {
+                        node,
+                        container,
+                        grace,
+                        pre_stop,
+                    } => {
+                        tracing::debug!(
+                            "[{}] Removing {} from {}",
+                            pod_label,
+                            container.name().unwrap_or(&container.id),
+                            node.name
+                        );
+
+                        shutdown::graceful_remove(&node, &container, grace, pre_stop.as_ref())
+                            .await;
+
+                        if let Some(name) = container.name() {
+                            if let Err(e) = images::forget_provenance(&db, &mol_name, name) {
+                                tracing::warn!("Failed to drop provenance of {}: {}", name, e);
+                            }
+                            if let Err(e) = secret_files::forget(&db, name) {
+                                tracing::warn!(
+                                    "Failed to drop secret file versions of {}: {}",
+                                    name,
+                                    e
+                                );
+                            }
                         }
-                    } else {
-                        tracing::warn!("Container {} has no name?!", c.id);
                     }
                 }
             }
@@ -147,5 +622,525 @@ pub async fn reconcile(db: &Db, orqos: &OrqosClient) -> Result<()> {
         }
     }
 
+    // ---- run-to-completion workloads ----
+
+    let active: DesiredMap = desired
+        .into_iter()
+        .filter(|(mol, _)| mode_of(mol) == MoleculeMode::Active)
+        .collect();
+
+    if let Err(e) = jobs::reconcile_jobs(app, &active, &mut scheduler).await {
+        tracing::warn!("Job reconcile failed: {:#}", e);
+    }
+
     Ok(())
 }
+
+/// Reorder pods so each molecule's pods follow their `depends_on` order.
+///
+/// Molecules with an invalid graph (which `apply` normally rejects) keep
+/// their declaration order; their gated pods simply never become startable.
+fn order_by_dependencies(pods: Vec<PodSpec>) -> Vec<PodSpec> {
+    let mut by_mol: Vec<(String, Vec<PodSpec>)> = Vec::new();
+    for pod in pods {
+        match by_mol.last_mut() {
+            Some((mol, group)) if *mol == pod.mol_name => group.push(pod),
+            _ => by_mol.push((pod.mol_name.clone(), vec![pod])),
+        }
+    }
+
+    let mut ordered = Vec::new();
+    for (mol, mut group) in by_mol {
+        let graph: Vec<(String, Vec<String>)> = group
+            .iter()
+            .map(|p| {
+                let deps = p.depends_on.iter().map(|d| d.pod().to_string()).collect();
+                (p.name.clone(), deps)
+            })
+            .collect();
+
+        match deps::topo_order(&graph) {
+            Ok(order) => {
+                for name in order {
+                    if let Some(i) = group.iter().position(|p| p.name == name) {
+                        ordered.push(group.swap_remove(i));
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Molecule '{}' has invalid dependencies: {:#}", mol, e);
+                ordered.append(&mut group);
+            }
+        }
+    }
+
+    ordered
+}
+
+/// For each pod, whether all of its dependencies meet their condition.
+fn dependencies_satisfied(
+    pods: &[PodSpec],
+    observed: &[HashMap<String, Vec<ContainerSummary>>],
+) -> Vec<bool> {
+    let index: HashMap<(&str, &str), usize> = pods
+        .iter()
+        .enumerate()
+        .map(|(i, p)| ((p.mol_name.as_str(), p.name.as_str()), i))
+        .collect();
+
+    pods.iter()
+        .map(|pod| {
+            pod.depends_on.iter().all(|dep| {
+                let Some(&i) = index.get(&(pod.mol_name.as_str(), dep.pod())) else {
+                    return false;
+                };
+
+                let containers: Vec<_> = observed[i].values().flatten().collect();
+                let enough = containers.len() >= pods[i].replicas;
+
+                match dep.condition() {
+                    DependencyCondition::Started => enough,
+                    DependencyCondition::Ready => enough && containers.iter().all(|c| c.is_ready()),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Query every node for the containers of every desired pod.
+///
+/// Returns, per pod, the matching containers keyed by node name. A node that
+/// fails any query is marked unreachable and left out entirely, so its
+/// replicas are no longer counted and get rescheduled elsewhere.
+async fn observe(
+    nodes: &NodeRegistry,
+    pods: &[PodSpec],
+) -> Vec<HashMap<String, Vec<ContainerSummary>>> {
+    let per_node = join_all(nodes.nodes().iter().map(|node| async move {
+        let mut found = Vec::with_capacity(pods.len());
+
+        for pod in pods {
+            let pod_label = format!("{}:{}", pod.mol_name, pod.name);
+
+            match node
+                .client
+                .list_containers(&format!("pod={pod_label}"))
+                .await
+            {
+                Ok(containers) => found.push(
+                    containers
+                        .into_iter()
+                        .filter(|c| c.labels.get("pod") == Some(&pod_label))
+                        .collect::<Vec<_>>(),
+                ),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to query node '{}' for running containers: {:#}",
+                        node.name,
+                        e
+                    );
+                    node.set_reachable(false);
+                    return None;
+                }
+            }
+        }
+
+        node.set_reachable(true);
+        Some((node.name.clone(), found))
+    }))
+    .await;
+
+    let mut observed: Vec<HashMap<String, Vec<ContainerSummary>>> =
+        vec![HashMap::new(); pods.len()];
+
+    for (node_name, found) in per_node.into_iter().flatten() {
+        for (i, containers) in found.into_iter().enumerate() {
+            if !containers.is_empty() {
+                observed[i].insert(node_name.clone(), containers);
+            }
+        }
+    }
+
+    observed
+}
+
+/// Separate exited and dead containers from the live ones, per pod. Only the
+/// live ones count as replicas; the others are returned with their node name
+/// to be cleared out.
+fn split_finished(observed: Vec<PerNode>) -> (Vec<PerNode>, Vec<Vec<(String, ContainerSummary)>>) {
+    observed
+        .into_iter()
+        .map(|per_node| {
+            let mut live = HashMap::new();
+            let mut finished = Vec::new();
+
+            for (node_name, containers) in per_node {
+                let (done, running): (Vec<_>, Vec<_>) = containers
+                    .into_iter()
+                    .partition(|c| c.phase().is_finished());
+
+                finished.extend(done.into_iter().map(|c| (node_name.clone(), c)));
+                if !running.is_empty() {
+                    live.insert(node_name, running);
+                }
+            }
+
+            (live, finished)
+        })
+        .unzip()
+}
+
+/// Resolve the image to a digest on `node`, create the container from that
+/// digest and record where it came from.
+async fn start_pinned(
+    db: &Db,
+    mol: &str,
+    pod_label: &str,
+    node: &Node,
+    mut req: CreateReq,
+    policy: PullPolicy,
+) -> Result<()> {
+    let declared = req.image.clone();
+    let resolved = node.client.pull_image(&declared, policy).await?;
+
+    req.image = resolved.reference;
+    req.labels
+        .insert("digest".to_string(), resolved.digest.clone());
+    let cname = req.name.clone();
+
+    node.client.create_container(req).await?;
+
+    images::record_provenance(
+        db,
+        mol,
+        &Provenance {
+            container: cname,
+            pod: pod_label.to_string(),
+            node: node.name.clone(),
+            image: declared,
+            digest: resolved.digest,
+            created_at: Utc::now(),
+        },
+    )
+}
+
+/// Current versions of the secrets `pod` reads unpinned, so a new version
+/// changes its spec hash and rolls its replicas. Empty for pods that opted
+/// out with `restart_on_secret_change: false`.
+fn secret_versions<'a>(
+    store: &secret::SecretStore,
+    pod: &'a PodSpec,
+) -> Result<BTreeMap<&'a str, Option<u64>>> {
+    if !pod.restart_on_secret_change {
+        return Ok(BTreeMap::new());
+    }
+    pod.env
+        .values()
+        .filter_map(EnvVar::unpinned_secret)
+        .map(|name| Ok((name, store.current_version(name)?)))
+        .collect()
+}
+
+/// Short hash of the fields that require new containers when they change.
+fn spec_hash(pod: &PodSpec, secrets: &BTreeMap<&str, Option<u64>>) -> String {
+    let mut spec = serde_json::json!({
+        "image": pod.image,
+        "ports": pod.ports,
+    });
+    // Only when set, so pods without env keep the hash they always had.
+    if !pod.env.is_empty() {
+        let env: BTreeMap<_, _> = pod.env.iter().collect();
+        spec["env"] = serde_json::json!(env);
+    }
+    // Where secret files are mounted needs new containers; new versions of
+    // their secrets do not.
+    if !pod.secret_files.is_empty() {
+        spec["secret_files"] = serde_json::json!(pod.secret_files);
+    }
+    if !secrets.is_empty() {
+        spec["secrets"] = serde_json::json!(secrets);
+    }
+    let digest = Sha256::digest(spec.to_string().as_bytes());
+    hex::encode(&digest[..6])
+}
+
+fn create_request(pod: &PodSpec, pod_label: &str, spec: &str, node: &Node) -> CreateReq {
+    let cname: String = format!(
+        "{}-{}-{}",
+        pod.mol_name,
+        pod.name,
+        Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    );
+
+    let port_maps: Vec<PortMap> = pod
+        .ports
+        .iter()
+        .map(|p| PortMap {
+            container: *p,
+            host: 0,
+        })
+        .collect();
+
+    let mut labels: HashMap<String, String> = HashMap::new();
+    labels.insert("mol".to_string(), pod.mol_name.clone());
+    labels.insert("pod".to_string(), pod_label.to_string());
+    labels.insert("node".to_string(), node.name.clone());
+    labels.insert(SPEC_LABEL.to_string(), spec.to_string());
+    labels.insert("image".to_string(), pod.image.clone());
+
+    CreateReq {
+        name: cname,
+        image: pod.image.clone(),
+        ports: port_maps,
+        labels,
+        env: HashMap::new(),
+        cpu: None,
+        command: None,
+        files: Vec::new(),
+        pull_policy: pod.pull_policy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::Authenticator, container_runtime::ContainerRuntime, memory_runtime::MemoryRuntime,
+        seal::Seal,
+    };
+    use common::types::Instruction;
+    use std::path::PathBuf;
+    use tokio::sync::{broadcast, mpsc, RwLock};
+
+    /// An app on in-memory nodes, with its stores removed on drop.
+    struct TestApp {
+        app: AppState,
+        runtimes: Vec<Arc<MemoryRuntime>>,
+        dir: PathBuf,
+    }
+
+    impl TestApp {
+        fn new(nodes: &[&str]) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "rezn-reconcile-{}",
+                hex::encode(rand::random::<[u8; 8]>())
+            ));
+            let (registry, runtimes) = NodeRegistry::memory(nodes);
+            let app = AppState {
+                db: Arc::new(sled::open(dir.join("state")).unwrap()),
+                nodes: Arc::new(registry),
+                stats: Arc::new(RwLock::new(Default::default())),
+                stats_tx: broadcast::channel(1).0,
+                secret_store: secret::SecretStore::open(dir.join("secrets"), None, Vec::new())
+                    .unwrap(),
+                seal: Arc::new(Seal::new(None)),
+                auth: Arc::new(Authenticator::from_env().unwrap()),
+                reconcile_tx: mpsc::channel(1).0,
+            };
+            Self { app, runtimes, dir }
+        }
+
+        /// Desire one pod `web` in molecule `shop`.
+        fn desire(&self, image: &str, replicas: usize) {
+            let desired: DesiredMap = BTreeMap::from([(
+                "shop".to_string(),
+                vec![Instruction {
+                    kind: "pod".into(),
+                    name: "web".into(),
+                    fields: Some(serde_json::json!({
+                        "image": image,
+                        "replicas": replicas,
+                        "ports": [80],
+                    })),
+                    options: None,
+                }],
+            )]);
+            self.app
+                .db
+                .insert("desired", serde_json::to_vec(&desired).unwrap())
+                .unwrap();
+        }
+
+        async fn reconcile(&self) {
+            reconcile(&self.app).await.unwrap();
+        }
+
+        /// The pod's containers on each node, in node order.
+        async fn containers(&self) -> Vec<Vec<ContainerSummary>> {
+            let mut all = Vec::new();
+            for runtime in &self.runtimes {
+                all.push(runtime.list_containers("pod=shop:web").await.unwrap());
+            }
+            all
+        }
+
+        async fn count(&self) -> usize {
+            self.containers().await.iter().map(Vec::len).sum()
+        }
+    }
+
+    impl Drop for TestApp {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn scales_up_across_nodes() {
+        let t = TestApp::new(&["a", "b"]);
+        t.desire("nginx:1", 4);
+
+        t.reconcile().await;
+
+        let containers = t.containers().await;
+        assert_eq!(containers[0].len(), 2);
+        assert_eq!(containers[1].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn scales_down() {
+        let t = TestApp::new(&["a", "b"]);
+        t.desire("nginx:1", 3);
+        t.reconcile().await;
+
+        t.desire("nginx:1", 1);
+        t.reconcile().await;
+
+        assert_eq!(t.count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn converged_pods_are_left_alone() {
+        let t = TestApp::new(&["a"]);
+        t.desire("nginx:1", 2);
+        t.reconcile().await;
+        let before: Vec<String> = t.containers().await[0]
+            .iter()
+            .map(|c| c.id.clone())
+            .collect();
+
+        t.reconcile().await;
+
+        let after: Vec<String> = t.containers().await[0]
+            .iter()
+            .map(|c| c.id.clone())
+            .collect();
+        assert_eq!(before, after);
+    }
+
+    #[tokio::test]
+    async fn rolls_replicas_when_the_spec_changes() {
+        let t = TestApp::new(&["a"]);
+        t.desire("nginx:1", 1);
+        t.reconcile().await;
+
+        t.desire("nginx:2", 1);
+
+        // A surge replica first, then the outdated one goes.
+        t.reconcile().await;
+        assert_eq!(t.count().await, 2);
+        t.reconcile().await;
+
+        let containers = t.containers().await;
+        assert_eq!(containers[0].len(), 1);
+        assert_eq!(
+            containers[0][0].labels.get("image").map(String::as_str),
+            Some("nginx:2")
+        );
+    }
+
+    #[tokio::test]
+    async fn scales_down_while_a_rollout_waits() {
+        let t = TestApp::new(&["a"]);
+        t.desire("nginx:1", 3);
+        t.reconcile().await;
+
+        t.desire("nginx:2", 3);
+        t.reconcile().await;
+        let image = |c: &ContainerSummary| c.labels.get("image").cloned().unwrap_or_default();
+        let surge = t.containers().await[0]
+            .iter()
+            .find(|c| image(c) == "nginx:2")
+            .and_then(|c| c.name())
+            .unwrap()
+            .to_string();
+        t.runtimes[0].set_health(&surge, Some("health: starting"));
+
+        // Only the unready surge replica may stay over count.
+        t.desire("nginx:2", 1);
+        t.reconcile().await;
+        let images: Vec<String> = t.containers().await[0].iter().map(image).collect();
+        assert_eq!(images.len(), 2);
+        assert!(images.contains(&"nginx:1".to_string()));
+        assert!(images.contains(&"nginx:2".to_string()));
+
+        t.runtimes[0].set_health(&surge, None);
+        t.reconcile().await;
+        let containers = t.containers().await;
+        assert_eq!(containers[0].len(), 1);
+        assert_eq!(containers[0][0].name(), Some(surge.as_str()));
+    }
+
+    #[tokio::test]
+    async fn replaces_exited_replicas() {
+        let t = TestApp::new(&["a"]);
+        t.desire("nginx:1", 1);
+        t.reconcile().await;
+        let first = t.containers().await[0][0].name().unwrap().to_string();
+        t.runtimes[0].stop_container(&first, None).await.unwrap();
+
+        t.reconcile().await;
+
+        let containers = t.containers().await;
+        assert_eq!(containers[0].len(), 1);
+        assert_ne!(containers[0][0].name(), Some(first.as_str()));
+    }
+
+    #[tokio::test]
+    async fn paused_molecules_are_not_touched() {
+        let t = TestApp::new(&["a"]);
+        modes::set_mode(&t.app.db, "shop", MoleculeMode::Paused).unwrap();
+        t.desire("nginx:1", 2);
+
+        t.reconcile().await;
+
+        assert_eq!(t.count().await, 0);
+        assert!(modes::drift(&t.app.db, "shop").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn observe_only_reports_drift_without_acting() {
+        let t = TestApp::new(&["a"]);
+        modes::set_mode(&t.app.db, "shop", MoleculeMode::ObserveOnly).unwrap();
+        t.desire("nginx:1", 2);
+
+        t.reconcile().await;
+
+        assert_eq!(t.count().await, 0);
+        let report = modes::drift(&t.app.db, "shop").unwrap().unwrap();
+        assert!(!report.acted);
+        assert_eq!(report.items.len(), 2);
+        assert!(report.items.iter().all(|i| i.action == "start"));
+    }
+
+    #[tokio::test]
+    async fn reschedules_replicas_of_unreachable_nodes() {
+        let t = TestApp::new(&["a", "b"]);
+        t.desire("nginx:1", 2);
+        t.reconcile().await;
+        assert_eq!(t.containers().await[1].len(), 1);
+
+        t.runtimes[1].set_unreachable(true);
+        t.reconcile().await;
+
+        assert!(!t.app.nodes.get("b").unwrap().is_reachable());
+        assert_eq!(
+            t.runtimes[0]
+                .list_containers("pod=shop:web")
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
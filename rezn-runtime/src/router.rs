@@ -1,17 +1,39 @@
 use std::sync::Arc;
 
 use axum::{
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
-use utoipa::OpenApi;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
 
 use crate::{
     routes::{
         apply::apply_handler,
+        audit::get_audit_handler,
+        autoscale::get_autoscale_events_handler,
         delete_secret::delete_secret_handler,
-        get_secrets::{get_secret_handler, get_secrets_handler},
-        put_secret::put_secret_handler,
+        exec::exec_handler,
+        get_secrets::{
+            get_expiring_secrets_handler, get_secret_handler, get_secret_raw_handler,
+            get_secrets_handler,
+        },
+        health::get_health_handler,
+        jobs::get_jobs_handler,
+        logs::get_pod_logs_handler,
+        mode::put_mode_handler,
+        nodes::get_nodes_handler,
+        params::{get_params_handler, put_param_handler},
+        pods::get_pod_containers_handler,
+        provenance::get_provenance_handler,
+        put_secret::{put_secret_handler, put_secret_raw_handler},
+        recipients::{get_recipients_handler, rekey_handler},
+        rotate_identity::rotate_identity_handler,
+        seal::{get_seal_status_handler, init_seal_handler, seal_handler, unseal_handler},
+        secret_transfer::{export_secrets_handler, import_secrets_handler},
+        secret_versions::{get_secret_versions_handler, rollback_secret_handler},
         state::{get_state_handler, get_state_raw_handler},
         stats::get_stats_handler,
         stats_ws::stats_ws_handler,
@@ -19,9 +41,22 @@ use crate::{
     AppState,
 };
 
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
 #[derive(OpenApi)]
 #[openapi(
     info(description = "Rezn Api"),
+    modifiers(&SecurityAddon),
     paths(
         crate::routes::apply::apply_handler,
         crate::routes::state::get_state_handler,
@@ -30,8 +65,34 @@ use crate::{
         crate::routes::stats_ws::stats_ws_handler,
         crate::routes::get_secrets::get_secret_handler,
         crate::routes::get_secrets::get_secrets_handler,
+        crate::routes::get_secrets::get_expiring_secrets_handler,
+        crate::routes::get_secrets::get_secret_raw_handler,
         crate::routes::put_secret::put_secret_handler,
-        crate::routes::delete_secret::delete_secret_handler
+        crate::routes::put_secret::put_secret_raw_handler,
+        crate::routes::delete_secret::delete_secret_handler,
+        crate::routes::secret_versions::get_secret_versions_handler,
+        crate::routes::secret_versions::rollback_secret_handler,
+        crate::routes::secret_transfer::export_secrets_handler,
+        crate::routes::secret_transfer::import_secrets_handler,
+        crate::routes::rotate_identity::rotate_identity_handler,
+        crate::routes::recipients::get_recipients_handler,
+        crate::routes::recipients::rekey_handler,
+        crate::routes::seal::get_seal_status_handler,
+        crate::routes::seal::init_seal_handler,
+        crate::routes::seal::unseal_handler,
+        crate::routes::seal::seal_handler,
+        crate::routes::nodes::get_nodes_handler,
+        crate::routes::autoscale::get_autoscale_events_handler,
+        crate::routes::jobs::get_jobs_handler,
+        crate::routes::params::get_params_handler,
+        crate::routes::params::put_param_handler,
+        crate::routes::mode::put_mode_handler,
+        crate::routes::health::get_health_handler,
+        crate::routes::provenance::get_provenance_handler,
+        crate::routes::logs::get_pod_logs_handler,
+        crate::routes::exec::exec_handler,
+        crate::routes::audit::get_audit_handler,
+        crate::routes::pods::get_pod_containers_handler
     )
 )]
 struct ApiDoc;
@@ -39,14 +100,46 @@ struct ApiDoc;
 pub(crate) fn build_router(app: Arc<AppState>) -> Router {
     Router::new()
         .route("/secrets", get(get_secrets_handler))
+        .route("/secrets/expiring", get(get_expiring_secrets_handler))
         .route("/secret", get(get_secret_handler))
         .route("/secret", delete(delete_secret_handler))
+        .route("/secret/raw", get(get_secret_raw_handler))
+        .route("/secret/raw", put(put_secret_raw_handler))
         .route("/secrets", post(put_secret_handler))
+        .route("/secret/versions", get(get_secret_versions_handler))
+        .route("/secret/rollback", post(rollback_secret_handler))
+        .route("/secrets/rotate-identity", post(rotate_identity_handler))
+        .route("/secrets/recipients", get(get_recipients_handler))
+        .route("/secrets/rekey", post(rekey_handler))
+        .route("/secrets/export", post(export_secrets_handler))
+        .route("/secrets/import", post(import_secrets_handler))
+        .route("/seal/status", get(get_seal_status_handler))
+        .route("/seal/init", post(init_seal_handler))
+        .route("/unseal", post(unseal_handler))
+        .route("/seal", post(seal_handler))
         .route("/apply", post(apply_handler))
         .route("/stats", get(get_stats_handler))
         .route("/stats/ws", get(stats_ws_handler))
         .route("/state", get(get_state_handler))
         .route("/state/raw", get(get_state_raw_handler))
+        .route("/nodes", get(get_nodes_handler))
+        .route("/health", get(get_health_handler))
+        .route("/autoscale/events", get(get_autoscale_events_handler))
+        .route("/molecules/{mol}/jobs", get(get_jobs_handler))
+        .route("/molecules/{mol}/params", get(get_params_handler))
+        .route("/molecules/{mol}/params/{name}", put(put_param_handler))
+        .route("/molecules/{mol}/mode", put(put_mode_handler))
+        .route("/molecules/{mol}/provenance", get(get_provenance_handler))
+        .route(
+            "/molecules/{mol}/pods/{pod}/logs",
+            get(get_pod_logs_handler),
+        )
+        .route(
+            "/molecules/{mol}/pods/{pod}/containers",
+            get(get_pod_containers_handler),
+        )
+        .route("/molecules/{mol}/pods/{pod}/exec", get(exec_handler))
+        .route("/audit", get(get_audit_handler))
         .with_state(app)
         .merge(
             utoipa_swagger_ui::SwaggerUi::new("/swagger")
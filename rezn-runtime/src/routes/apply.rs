@@ -2,7 +2,7 @@ use axum::extract::State;
 use axum::Json;
 use base64::engine::general_purpose;
 use base64::Engine;
-use common::types::{DesiredMap, InstructionMeta, InstructionWrapper};
+use common::types::{DesiredMap, Instruction, InstructionMeta, InstructionWrapper};
 use serde::Deserialize;
 use serde_json_canonicalizer::to_vec;
 use sled::transaction::{ConflictableTransactionError, TransactionError};
@@ -13,8 +13,10 @@ use anyhow::Result;
 use ed25519_dalek::{Signature as Ed25519Signature, Verifier, VerifyingKey as PublicKey};
 
 use crate::{
-    routes::common::{app_error, AppError},
-    AppState,
+    auth::{Caller, Permission, Scope},
+    deps, images, params,
+    routes::common::{app_error, bad_request, AppError},
+    secret, secret_files, AppState,
 };
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -32,14 +34,21 @@ pub struct ApplyPayload {
         content_type = "application/json",
     ),
     responses(
-        (status = 200, body = Object)
+        (status = 200, body = Object),
+        (status = 400, description = "Program is invalid, e.g. cyclic depends_on or unknown enum cases"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller may not deploy the molecule or read a secret it uses")
     ),
+    security(("bearer" = [])),
     tag = "Apply",
 )]
 pub async fn apply_handler(
     State(app): State<Arc<AppState>>,
+    caller: Caller,
     Json(payload): Json<ApplyPayload>,
 ) -> Result<Json<bool>, AppError> {
+    caller.require(Permission::Deploy, Scope::Molecule(&payload.name))?;
+
     tracing::debug!("Applying payload");
 
     let name = payload.name;
@@ -69,12 +78,22 @@ pub async fn apply_handler(
     let pubkey_array: &[u8; 32] = pubkey_bytes.as_slice().try_into().map_err(app_error)?;
     let public_key = PublicKey::from_bytes(pubkey_array).map_err(app_error)?;
     let sig_array: &[u8; 64] = sig_bytes.as_slice().try_into().map_err(app_error)?;
-    let signature = Ed25519Signature::try_from(sig_array).map_err(app_error)?;
+    let signature = Ed25519Signature::from_bytes(sig_array);
 
     public_key
         .verify(&program_raw, &signature)
         .map_err(app_error)?;
 
+    params::validate_program(program).map_err(bad_request)?;
+    let selected = params::selections(&app.db, &name, program).map_err(app_error)?;
+    validate_resolved(&params::resolve_program(program, &selected)).map_err(bad_request)?;
+
+    // Whatever the program can read, its containers can show through exec
+    // and logs.
+    for key in secret::program_refs(program) {
+        caller.require(Permission::ReadSecrets, Scope::Secret(&key))?;
+    }
+
     app.db
         .transaction(|tree| {
             // ---- load current state (may be absent) ----
@@ -135,3 +154,59 @@ pub async fn apply_handler(
 
     Ok(Json(true))
 }
+
+/// Checks that only hold once `$enum` switches are resolved, run on the
+/// program as reconcile would see it.
+pub(crate) fn validate_resolved(program: &[Instruction]) -> Result<()> {
+    deps::validate_program(program)?;
+    images::validate_program(program)?;
+    secret_files::validate_program(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::params::Selections;
+
+    fn resolved(program: &serde_json::Value, env: &str) -> Result<()> {
+        let program: Vec<Instruction> = serde_json::from_value(program.clone()).unwrap();
+        let selected = Selections::from([("env".to_string(), env.to_string())]);
+        validate_resolved(&params::resolve_program(&program, &selected))
+    }
+
+    #[test]
+    fn switched_fields_are_checked_for_the_selected_case() {
+        let program = json!([
+            {"kind": "enum", "name": "env", "options": ["prod", "staging"]},
+            {"kind": "pod", "name": "a", "fields": {
+                "image": {"$enum": "env", "cases": {"staging": "a:"}, "default": "a:1"},
+                "depends_on": {"$enum": "env", "cases": {"prod": ["b"]}, "default": []},
+            }},
+            {"kind": "pod", "name": "b", "fields": {
+                "image": "b:1",
+                "depends_on": {"$enum": "env", "cases": {"prod": ["a"]}, "default": []},
+            }},
+        ]);
+
+        let err = resolved(&program, "prod").unwrap_err();
+        assert_eq!(err.to_string(), "dependency cycle between pods: a, b");
+
+        let err = resolved(&program, "staging").unwrap_err();
+        assert_eq!(err.to_string(), "pod 'a': invalid image reference 'a:'");
+    }
+
+    #[test]
+    fn pods_that_do_not_resolve_yet_are_left_to_reconcile() {
+        let program = json!([
+            {"kind": "enum", "name": "env", "options": ["prod", "staging"]},
+            {"kind": "pod", "name": "a", "fields": {
+                "image": {"$enum": "env", "cases": {"prod": "a:1"}},
+            }},
+        ]);
+
+        resolved(&program, "prod").unwrap();
+        resolved(&program, "staging").unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Deserialize;
+
+use std::sync::Arc;
+
+use crate::audit::{self, AuditEntry};
+use crate::auth::{Caller, Permission, Scope};
+use crate::routes::common::{app_error, AppError};
+use crate::AppState;
+
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    params(
+        ("limit" = Option<usize>, Query, description = "Maximum number of entries (default 100)")
+    ),
+    responses(
+        (status = 200, body = Vec<AuditEntry>, description = "Audit log, newest first"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Audit",
+)]
+pub async fn get_audit_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    caller.require(Permission::Admin, Scope::Global)?;
+
+    let entries = audit::list(&app.db, query.limit.unwrap_or(DEFAULT_LIMIT)).map_err(app_error)?;
+
+    Ok(Json(entries))
+}
@@ -0,0 +1,42 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Deserialize;
+
+use std::sync::Arc;
+
+use crate::auth::{Caller, Permission, Scope};
+use crate::autoscale::{self, ScalingEvent};
+use crate::routes::common::{app_error, AppError};
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    mol: Option<String>,
+    pod: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/autoscale/events",
+    params(
+        ("mol" = Option<String>, Query, description = "Only events for this molecule"),
+        ("pod" = Option<String>, Query, description = "Only events for this pod")
+    ),
+    responses(
+        (status = 200, body = Vec<ScalingEvent>, description = "Recorded scaling decisions of the molecules the caller may view, oldest first"),
+        (status = 401, description = "Missing or invalid API token")
+    ),
+    security(("bearer" = [])),
+    tag = "Autoscale",
+)]
+pub async fn get_autoscale_events_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<EventsQuery>,
+) -> Result<Json<Vec<ScalingEvent>>, AppError> {
+    let mut events = autoscale::events(&app.db, query.mol.as_deref(), query.pod.as_deref())
+        .map_err(app_error)?;
+    events.retain(|e| caller.can(Permission::View, Scope::Molecule(&e.mol)));
+
+    Ok(Json(events))
+}
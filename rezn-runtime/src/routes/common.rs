@@ -6,3 +6,20 @@ pub fn app_error<E: std::fmt::Display>(e: E) -> AppError {
     tracing::warn!("internal error: {e}");
     (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
 }
+
+pub fn bad_request<E: std::fmt::Display>(e: E) -> AppError {
+    tracing::debug!("bad request: {e}");
+    (StatusCode::BAD_REQUEST, e.to_string())
+}
+
+/// Like [`app_error`], but a sealed secret store is a 503 and an expired
+/// secret a 410.
+pub fn store_error(e: anyhow::Error) -> AppError {
+    if e.downcast_ref::<crate::secret::Sealed>().is_some() {
+        return (StatusCode::SERVICE_UNAVAILABLE, e.to_string());
+    }
+    if e.downcast_ref::<crate::secret::Expired>().is_some() {
+        return (StatusCode::GONE, e.to_string());
+    }
+    app_error(format!("{e:#}"))
+}
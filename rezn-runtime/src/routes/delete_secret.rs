@@ -1,4 +1,3 @@
-use anyhow::anyhow;
 use std::sync::Arc;
 
 use axum::{
@@ -9,7 +8,8 @@ use axum::{
 use serde::Deserialize;
 
 use crate::{
-    routes::common::{app_error, AppError},
+    auth::{Caller, Permission, Scope},
+    routes::common::{store_error, AppError},
     AppState,
 };
 
@@ -26,15 +26,22 @@ pub struct SecretQuery {
     ),
     responses(
         (status = 200, body = bool, description = "Secret deleted"),
-        (status = 404, description = "Secret not found")
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Secret not found"),
+        (status = 503, description = "The runtime is sealed")
     ),
+    security(("bearer" = [])),
     tag = "Secrets",
 )]
 pub async fn delete_secret_handler(
     State(app): State<Arc<AppState>>,
+    caller: Caller,
     Query(query): Query<SecretQuery>,
 ) -> Result<(StatusCode, Json<bool>), AppError> {
-    let removed = app.secret_store.delete(&query.key).map_err(app_error)?; // ↓ returns bool now
+    caller.require(Permission::WriteSecrets, Scope::Secret(&query.key))?;
+
+    let removed = app.secret_store.delete(&query.key).map_err(store_error)?; // ↓ returns bool now
 
     if removed {
         Ok((StatusCode::OK, Json(true)))
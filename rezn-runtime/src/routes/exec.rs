@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Path, State, WebSocketUpgrade,
+    },
+    response::Response,
+};
+use chrono::Utc;
+use common::exec::{self, ExecControl, ExecStart};
+use futures_util::StreamExt;
+
+use crate::{
+    audit::{self, AuditEntry},
+    auth::{Caller, Permission, Scope},
+    container_runtime::{ContainerPhase, ExecInput, ExecOutput, ExecSession},
+    pods,
+    routes::common::AppError,
+    AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/molecules/{mol}/pods/{pod}/exec",
+    description = "Interactive exec into one replica of a pod over WebSocket. The client \
+                   sends a JSON `start` message first; after that binary frames carry \
+                   stdin/stdout/stderr prefixed with their stream id and text frames carry \
+                   `resize`, `exit` and `error` control messages (see `common::exec`). \
+                   Every session is recorded in the audit log.",
+    params(
+        ("mol" = String, Path, description = "Molecule name"),
+        ("pod" = String, Path, description = "Pod name")
+    ),
+    responses(
+        (status = 101, description = "WebSocket upgrade initiated"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Streaming",
+)]
+pub async fn exec_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path((mol, pod)): Path<(String, String)>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    caller.require(Permission::Exec, Scope::Molecule(&mol))?;
+
+    Ok(ws.on_upgrade(move |mut socket| async move {
+        if let Err(e) = run_session(&app, &caller.name, &mol, &pod, &mut socket).await {
+            tracing::warn!(
+                "exec into {}:{} by {} failed: {:#}",
+                mol,
+                pod,
+                caller.name,
+                e
+            );
+            let _ = send_control(
+                &mut socket,
+                &ExecControl::Error {
+                    message: format!("{e:#}"),
+                },
+            )
+            .await;
+        }
+        let _ = socket.send(Message::Close(None)).await;
+    }))
+}
+
+async fn run_session(
+    app: &AppState,
+    user: &str,
+    mol: &str,
+    pod: &str,
+    socket: &mut WebSocket,
+) -> Result<()> {
+    let start = read_start(socket).await?;
+    if start.command.is_empty() {
+        return Err(anyhow!("no command given"));
+    }
+
+    let mut replicas: Vec<_> = pods::find_replicas(&app.nodes, mol, pod)
+        .await
+        .into_iter()
+        .filter(|(_, c)| c.phase() == ContainerPhase::Running)
+        .collect();
+    replicas.sort_by(|(_, a), (_, b)| a.name().cmp(&b.name()));
+
+    let (node, container) = match &start.replica {
+        Some(wanted) => replicas
+            .into_iter()
+            .find(|(_, c)| c.name() == Some(wanted.as_str()))
+            .ok_or_else(|| anyhow!("{mol}:{pod} has no replica named '{wanted}'"))?,
+        None => replicas
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("{mol}:{pod} has no running replicas"))?,
+    };
+    let container = container.name().unwrap_or_default().to_string();
+
+    let mut entry = AuditEntry {
+        at: Utc::now(),
+        user: user.to_string(),
+        action: "exec".to_string(),
+        target: format!("{mol}:{pod}"),
+        container: Some(container.clone()),
+        node: Some(node.name.clone()),
+        command: start.command.clone(),
+        ended_at: None,
+        exit_code: None,
+        error: None,
+    };
+    let key = audit::record(&app.db, &entry)?;
+    tracing::info!(
+        "{} exec into {} on '{}': {:?}",
+        user,
+        container,
+        node.name,
+        start.command
+    );
+
+    let result = match node.client.exec_session(&container, &start).await {
+        Ok(session) => pump(socket, session).await,
+        Err(e) => Err(e),
+    };
+
+    entry.ended_at = Some(Utc::now());
+    match &result {
+        Ok(code) => entry.exit_code = *code,
+        Err(e) => entry.error = Some(format!("{e:#}")),
+    }
+    if let Err(e) = audit::update(&app.db, &key, &entry) {
+        tracing::warn!("Failed to update audit entry {}: {:#}", key, e);
+    }
+
+    let code = result?;
+    send_control(socket, &ExecControl::Exit { code }).await
+}
+
+/// The first message of a session must be `start`.
+async fn read_start(socket: &mut WebSocket) -> Result<ExecStart> {
+    loop {
+        match socket.recv().await {
+            Some(Ok(Message::Text(text))) => {
+                return match serde_json::from_str(text.as_str())? {
+                    ExecControl::Start(start) => Ok(start),
+                    _ => Err(anyhow!("expected a start message")),
+                };
+            }
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+            Some(Ok(_)) => return Err(anyhow!("expected a start message")),
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(anyhow!("client disconnected before starting")),
+        }
+    }
+}
+
+/// Shuttle frames between the client and the session until the process
+/// exits. Returns its exit code.
+async fn pump(socket: &mut WebSocket, session: ExecSession) -> Result<Option<i64>> {
+    let ExecSession { input, mut output } = session;
+    let mut input = Some(input);
+
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    let Some((&exec::STDIN, rest)) = data.split_first() else {
+                        continue;
+                    };
+                    if rest.is_empty() {
+                        // EOF: closing our end closes the process's stdin.
+                        input = None;
+                    } else if let Some(tx) = &input {
+                        let _ = tx.send(ExecInput::Stdin(data.slice(1..))).await;
+                    }
+                }
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(ExecControl::Resize { cols, rows }) = serde_json::from_str(text.as_str()) {
+                        if let Some(tx) = &input {
+                            let _ = tx.send(ExecInput::Resize { cols, rows }).await;
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(anyhow!("client disconnected"));
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            },
+            out = output.next() => match out {
+                Some(Ok(ExecOutput::Stdout(data))) => {
+                    socket.send(Message::Binary(exec::frame(exec::STDOUT, &data).into())).await?;
+                }
+                Some(Ok(ExecOutput::Stderr(data))) => {
+                    socket.send(Message::Binary(exec::frame(exec::STDERR, &data).into())).await?;
+                }
+                Some(Ok(ExecOutput::Exit(code))) => return Ok(code),
+                Some(Err(e)) => return Err(e),
+                None => return Ok(None),
+            },
+        }
+    }
+}
+
+async fn send_control(socket: &mut WebSocket, msg: &ExecControl) -> Result<()> {
+    socket
+        .send(Message::Text(serde_json::to_string(msg)?.into()))
+        .await?;
+    Ok(())
+}
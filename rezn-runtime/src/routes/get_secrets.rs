@@ -1,57 +1,209 @@
-use anyhow::anyhow;
 use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
-use serde::Deserialize;
+use base64::{engine::general_purpose, Engine};
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::routes::common::{app_error, AppError};
+use crate::auth::{Caller, Permission, Scope};
+use crate::routes::common::{store_error, AppError};
+use crate::secret::{SecretEncoding, SecretInfo};
 use crate::AppState;
 
+/// How far ahead `/secrets/expiring` looks unless told otherwise: 7 days.
+const DEFAULT_EXPIRY_WINDOW: u64 = 7 * 24 * 3600;
+
 #[derive(Debug, Deserialize)]
 pub struct SecretQuery {
     key: String,
+    version: Option<u64>,
+    #[serde(default)]
+    encoding: SecretEncoding,
+}
+
+/// The decrypted value of `query.key`, or a 404.
+fn read_secret(app: &AppState, query: &SecretQuery) -> Result<Vec<u8>, AppError> {
+    match query.version {
+        Some(version) => app.secret_store.get_version(&query.key, version),
+        None => app.secret_store.get(&query.key),
+    }
+    .map_err(store_error)?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            match query.version {
+                Some(v) => format!("Secret '{}' has no version {}", query.key, v),
+                None => format!("Secret '{}' not found", query.key),
+            },
+        )
+    })
 }
 
 #[utoipa::path(
     get,
     path = "/secret",
     params(
-        ("key" = String, Query, description = "The full secret key name, e.g. rezn/prod/db_url")
+        ("key" = String, Query, description = "The full secret key name, e.g. rezn/prod/db_url"),
+        ("version" = Option<u64>, Query, description = "A specific version instead of the current one"),
+        ("encoding" = Option<SecretEncoding>, Query, description = "`base64` for binary values (default `utf8`)")
     ),
     responses(
         (status = 200, description = "Decrypted secret value", body = String),
-        (status = 404, description = "Secret not found")
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Secret or version not found"),
+        (status = 410, description = "The secret has expired"),
+        (status = 422, description = "The value is binary; ask for `encoding=base64`"),
+        (status = 503, description = "The runtime is sealed")
     ),
+    security(("bearer" = [])),
     tag = "Secrets",
 )]
 pub async fn get_secret_handler(
     State(app): State<Arc<AppState>>,
+    caller: Caller,
     Query(query): Query<SecretQuery>,
 ) -> Result<Json<String>, AppError> {
-    let plaintext = app
+    caller.require(Permission::ReadSecrets, Scope::Secret(&query.key))?;
+
+    let plaintext = read_secret(&app, &query)?;
+
+    let value = match query.encoding {
+        SecretEncoding::Base64 => general_purpose::STANDARD.encode(plaintext),
+        SecretEncoding::Utf8 => String::from_utf8(plaintext).map_err(|_| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Secret '{}' is not valid UTF-8; use encoding=base64 or /secret/raw",
+                    query.key
+                ),
+            )
+        })?,
+    };
+
+    Ok(Json(value))
+}
+
+#[utoipa::path(
+    get,
+    path = "/secret/raw",
+    description = "The decrypted value as raw bytes, served with the content type it was stored \
+                   with (`application/octet-stream` if none).",
+    params(
+        ("key" = String, Query, description = "The full secret key name"),
+        ("version" = Option<u64>, Query, description = "A specific version instead of the current one")
+    ),
+    responses(
+        (status = 200, description = "Decrypted secret value", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Secret or version not found"),
+        (status = 410, description = "The secret has expired"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn get_secret_raw_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<SecretQuery>,
+) -> Result<Response, AppError> {
+    caller.require(Permission::ReadSecrets, Scope::Secret(&query.key))?;
+
+    let plaintext = read_secret(&app, &query)?;
+    let content_type = app
         .secret_store
-        .get(&query.key)
-        .map_err(app_error)?
-        .ok_or_else(|| app_error(anyhow!("Secret '{}' not found", query.key)))?;
+        .content_type(&query.key, query.version)
+        .map_err(store_error)?
+        .unwrap_or_else(|| "application/octet-stream".to_string());
 
-    let secret_str = String::from_utf8(plaintext)
-        .map_err(|_| app_error(anyhow!("Secret '{}' is not valid UTF-8", query.key)))?;
+    Ok(([(header::CONTENT_TYPE, content_type)], plaintext).into_response())
+}
 
-    Ok(Json(secret_str))
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListQuery {
+    /// Only keys starting with this.
+    prefix: Option<String>,
+    /// Return each key's metadata instead of just its name.
+    #[serde(default)]
+    metadata: bool,
+}
+
+/// Key names, or their metadata with `metadata=true`.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum SecretList {
+    Keys(Vec<String>),
+    Detailed(Vec<SecretInfo>),
 }
 
 #[utoipa::path(
     get,
     path = "/secrets",
+    description = "Secrets the caller may read. Nothing is decrypted, so this works while the \
+                   runtime is sealed.",
+    params(ListQuery),
     responses(
-        (status = 200, description = "List of secret keys", body = Vec<String>)
+        (status = 200, description = "Secret keys or metadata, in key order", body = SecretList),
+        (status = 401, description = "Missing or invalid API token")
     ),
+    security(("bearer" = [])),
     tag = "Secrets",
 )]
 pub async fn get_secrets_handler(
     State(app): State<Arc<AppState>>,
-) -> Result<Json<Vec<String>>, AppError> {
-    let keys: Vec<String> = app.secret_store.keys().map_err(app_error)?;
+    caller: Caller,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<SecretList>, AppError> {
+    let prefix = query.prefix.as_deref().unwrap_or("");
+    let readable = |k: &str| caller.can(Permission::ReadSecrets, Scope::Secret(k));
+
+    let mut infos = app.secret_store.list(prefix).map_err(store_error)?;
+    infos.retain(|i| readable(&i.key));
+
+    Ok(Json(if query.metadata {
+        SecretList::Detailed(infos)
+    } else {
+        SecretList::Keys(infos.into_iter().map(|i| i.key).collect())
+    }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExpiringQuery {
+    /// Look this many seconds ahead (default 7 days).
+    within: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/secrets/expiring",
+    description = "Secrets the caller may read that expire within the window or already have, \
+                   soonest first. Expired secrets are no longer served, to pods or over the API.",
+    params(ExpiringQuery),
+    responses(
+        (status = 200, description = "Metadata of expiring secrets", body = Vec<SecretInfo>),
+        (status = 401, description = "Missing or invalid API token")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn get_expiring_secrets_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<ExpiringQuery>,
+) -> Result<Json<Vec<SecretInfo>>, AppError> {
+    // Windows too large to represent reach as far as time does.
+    let within = i64::try_from(query.within.unwrap_or(DEFAULT_EXPIRY_WINDOW))
+        .ok()
+        .and_then(TimeDelta::try_seconds)
+        .unwrap_or(TimeDelta::MAX);
+
+    let mut infos = app.secret_store.expiring(within).map_err(store_error)?;
+    infos.retain(|i| caller.can(Permission::ReadSecrets, Scope::Secret(&i.key)));
 
-    Ok(Json(keys))
+    Ok(Json(infos))
 }
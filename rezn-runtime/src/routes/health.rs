@@ -0,0 +1,81 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use std::sync::Arc;
+
+use crate::circuit::CircuitStatus;
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// Every node is reachable with a closed circuit.
+    Ok,
+    /// Some nodes are unreachable or have an open circuit.
+    Degraded,
+    /// No node can take commands; reconcile is paused.
+    Down,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NodeHealth {
+    pub name: String,
+    pub reachable: bool,
+    /// Absent for backends without a circuit breaker.
+    pub circuit: Option<CircuitStatus>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub nodes: Vec<NodeHealth>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, body = HealthReport, description = "At least one node is usable"),
+        (status = 503, body = HealthReport, description = "Every executor is down")
+    ),
+    tag = "Health",
+)]
+pub async fn get_health_handler(
+    State(app): State<Arc<AppState>>,
+) -> (StatusCode, Json<HealthReport>) {
+    let nodes: Vec<NodeHealth> = app
+        .nodes
+        .nodes()
+        .iter()
+        .map(|n| NodeHealth {
+            name: n.name.clone(),
+            reachable: n.is_reachable(),
+            circuit: n.client.circuit(),
+        })
+        .collect();
+
+    let usable = app
+        .nodes
+        .nodes()
+        .iter()
+        .filter(|n| n.is_reachable() && !n.circuit_open())
+        .count();
+
+    let status = if usable == nodes.len() {
+        HealthStatus::Ok
+    } else if usable > 0 {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Down
+    };
+
+    let code = match status {
+        HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+
+    (code, Json(HealthReport { status, nodes }))
+}
@@ -0,0 +1,35 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use std::sync::Arc;
+
+use crate::auth::{Caller, Permission, Scope};
+use crate::jobs::{self, JobRun};
+use crate::routes::common::{app_error, AppError};
+use crate::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/molecules/{mol}/jobs",
+    params(
+        ("mol" = String, Path, description = "Molecule name")
+    ),
+    responses(
+        (status = 200, body = Vec<JobRun>, description = "Retained job and cron runs, oldest first per job"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Jobs",
+)]
+pub async fn get_jobs_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path(mol): Path<String>,
+) -> Result<Json<Vec<JobRun>>, AppError> {
+    caller.require(Permission::View, Scope::Molecule(&mol))?;
+
+    let runs = jobs::molecule_runs(&app.db, &mol).map_err(app_error)?;
+
+    Ok(Json(runs))
+}
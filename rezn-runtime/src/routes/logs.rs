@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{
+        ws::{rejection::WebSocketUpgradeRejection, Message},
+        Path, Query, State, WebSocketUpgrade,
+    },
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    auth::{Caller, Permission, Scope},
+    container_runtime::LogOptions,
+    logs,
+    routes::common::{bad_request, AppError},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LogsQuery {
+    /// Only the last `tail` lines of each replica.
+    tail: Option<usize>,
+    /// Only lines written after this unix timestamp.
+    since: Option<i64>,
+    /// Keep streaming new lines; requires a WebSocket upgrade.
+    #[serde(default)]
+    follow: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/molecules/{mol}/pods/{pod}/logs",
+    description = "Logs of every replica of a pod, each line prefixed with `[container]`. \
+                   With `follow=true` the request must be a WebSocket upgrade and lines \
+                   arrive as text messages until the client disconnects.",
+    params(
+        ("mol" = String, Path, description = "Molecule name"),
+        ("pod" = String, Path, description = "Pod name"),
+        LogsQuery
+    ),
+    responses(
+        (status = 200, description = "Log lines", content_type = "text/plain", body = String),
+        (status = 101, description = "WebSocket upgrade initiated (follow mode)"),
+        (status = 400, description = "follow=true without a WebSocket upgrade"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Pod has no replicas")
+    ),
+    security(("bearer" = [])),
+    tag = "Streaming",
+)]
+pub async fn get_pod_logs_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path((mol, pod)): Path<(String, String)>,
+    Query(query): Query<LogsQuery>,
+    ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Result<Response, AppError> {
+    caller.require(Permission::View, Scope::Molecule(&mol))?;
+
+    let opts = LogOptions {
+        tail: query.tail,
+        since: query.since,
+        follow: query.follow,
+    };
+
+    let ws = ws.ok();
+    if opts.follow && ws.is_none() {
+        return Err(bad_request("follow=true requires a WebSocket upgrade"));
+    }
+
+    tracing::debug!("{} reading logs of {}:{}", caller.name, mol, pod);
+
+    let lines = logs::pod_lines(&app.nodes, &mol, &pod, &opts)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+
+    match ws {
+        Some(ws) if opts.follow => Ok(ws
+            .on_upgrade(move |mut socket| async move {
+                let mut lines = lines;
+                while let Some(line) = lines.next().await {
+                    let msg = match line {
+                        Ok(line) => line,
+                        Err(e) => format!("[rezn] log stream error: {e:#}"),
+                    };
+                    if socket.send(Message::Text(msg.into())).await.is_err() {
+                        // Client went away.
+                        break;
+                    }
+                }
+                let _ = socket.send(Message::Close(None)).await;
+            })
+            .into_response()),
+        _ => {
+            let body = Body::from_stream(lines.map(|line| line.map(|l| format!("{l}\n"))));
+            Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response())
+        }
+    }
+}
@@ -1,8 +1,24 @@
 pub mod apply;
+pub mod audit;
+pub mod autoscale;
 pub mod common;
 pub mod delete_secret;
+pub mod exec;
 pub mod get_secrets;
+pub mod health;
+pub mod jobs;
+pub mod logs;
+pub mod mode;
+pub mod nodes;
+pub mod params;
+pub mod pods;
+pub mod provenance;
 pub mod put_secret;
+pub mod recipients;
+pub mod rotate_identity;
+pub mod seal;
+pub mod secret_transfer;
+pub mod secret_versions;
 pub mod state;
 pub mod stats;
 pub mod stats_ws;
@@ -0,0 +1,70 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use common::types::DesiredMap;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Caller, Permission, Scope},
+    modes::{self, MoleculeMode},
+    routes::common::{app_error, AppError},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetModePayload {
+    mode: MoleculeMode,
+}
+
+#[utoipa::path(
+    put,
+    path = "/molecules/{mol}/mode",
+    params(
+        ("mol" = String, Path, description = "Molecule name")
+    ),
+    request_body(
+        content = SetModePayload,
+        description = "active, paused or observe_only",
+        content_type = "application/json",
+    ),
+    responses(
+        (status = 200, body = MoleculeMode, description = "Mode now in effect"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Molecule not found")
+    ),
+    security(("bearer" = [])),
+    tag = "State",
+)]
+pub async fn put_mode_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path(mol): Path<String>,
+    Json(payload): Json<SetModePayload>,
+) -> Result<Json<MoleculeMode>, AppError> {
+    caller.require(Permission::Deploy, Scope::Molecule(&mol))?;
+
+    let desired: DesiredMap = match app.db.get("desired").map_err(app_error)? {
+        Some(bytes) => serde_json::from_slice(&bytes).map_err(app_error)?,
+        None => DesiredMap::new(),
+    };
+
+    if !desired.contains_key(&mol) {
+        return Err((StatusCode::NOT_FOUND, format!("molecule '{mol}' not found")));
+    }
+
+    modes::set_mode(&app.db, &mol, payload.mode).map_err(app_error)?;
+
+    tracing::info!(
+        "{} set molecule '{}' to {:?}",
+        caller.name,
+        mol,
+        payload.mode
+    );
+
+    let _ = app.reconcile_tx.try_send(());
+
+    Ok(Json(payload.mode))
+}
@@ -0,0 +1,29 @@
+use axum::extract::State;
+use axum::Json;
+
+use std::sync::Arc;
+
+use crate::auth::{Caller, Permission, Scope};
+use crate::nodes::NodeStatus;
+use crate::routes::common::AppError;
+use crate::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/nodes",
+    responses(
+        (status = 200, body = Vec<NodeStatus>, description = "Configured executor nodes"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Nodes",
+)]
+pub async fn get_nodes_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<Vec<NodeStatus>>, AppError> {
+    caller.require(Permission::View, Scope::Global)?;
+
+    Ok(Json(app.nodes.statuses()))
+}
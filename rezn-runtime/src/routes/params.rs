@@ -0,0 +1,136 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use common::types::{DesiredMap, Instruction};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::{
+    auth::{Caller, Permission, Scope},
+    params,
+    routes::{
+        apply::validate_resolved,
+        common::{app_error, bad_request, AppError},
+    },
+    AppState,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EnumParam {
+    options: Vec<String>,
+    selected: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SelectParamPayload {
+    value: String,
+}
+
+fn molecule_program(app: &AppState, mol: &str) -> Result<Vec<Instruction>, AppError> {
+    let desired: DesiredMap = match app.db.get("desired").map_err(app_error)? {
+        Some(bytes) => serde_json::from_slice(&bytes).map_err(app_error)?,
+        None => DesiredMap::new(),
+    };
+
+    desired
+        .get(mol)
+        .cloned()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("molecule '{mol}' not found")))
+}
+
+fn molecule_params(
+    app: &AppState,
+    mol: &str,
+    program: &[Instruction],
+) -> Result<BTreeMap<String, EnumParam>, AppError> {
+    let selected = params::selections(&app.db, mol, program).map_err(app_error)?;
+
+    Ok(params::enums(program)
+        .into_iter()
+        .map(|(name, options)| {
+            let selected = selected.get(&name).cloned();
+            (name, EnumParam { options, selected })
+        })
+        .collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/molecules/{mol}/params",
+    params(
+        ("mol" = String, Path, description = "Molecule name")
+    ),
+    responses(
+        (status = 200, body = BTreeMap<String, EnumParam>, description = "Enums of the molecule with their selected values"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Molecule not found")
+    ),
+    security(("bearer" = [])),
+    tag = "Params",
+)]
+pub async fn get_params_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path(mol): Path<String>,
+) -> Result<Json<BTreeMap<String, EnumParam>>, AppError> {
+    caller.require(Permission::View, Scope::Molecule(&mol))?;
+
+    let program = molecule_program(&app, &mol)?;
+
+    Ok(Json(molecule_params(&app, &mol, &program)?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/molecules/{mol}/params/{name}",
+    params(
+        ("mol" = String, Path, description = "Molecule name"),
+        ("name" = String, Path, description = "Enum name")
+    ),
+    request_body(
+        content = SelectParamPayload,
+        description = "Value to select, one of the enum's options",
+        content_type = "application/json",
+    ),
+    responses(
+        (status = 200, body = BTreeMap<String, EnumParam>, description = "Updated enums of the molecule"),
+        (status = 400, description = "Unknown enum, value not among its options, or the program is invalid with it"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Molecule not found")
+    ),
+    security(("bearer" = [])),
+    tag = "Params",
+)]
+pub async fn put_param_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path((mol, name)): Path<(String, String)>,
+    Json(payload): Json<SelectParamPayload>,
+) -> Result<Json<BTreeMap<String, EnumParam>>, AppError> {
+    caller.require(Permission::Deploy, Scope::Molecule(&mol))?;
+
+    let program = molecule_program(&app, &mol)?;
+
+    let mut selected = params::selections(&app.db, &mol, &program).map_err(app_error)?;
+    selected.insert(name.clone(), payload.value.clone());
+    validate_resolved(&params::resolve_program(&program, &selected)).map_err(bad_request)?;
+
+    params::select(&app.db, &mol, &program, &name, &payload.value).map_err(bad_request)?;
+
+    tracing::info!(
+        "{} selected {}={} for molecule '{}'",
+        caller.name,
+        name,
+        payload.value,
+        mol
+    );
+
+    // A reconcile is already queued if the channel is full.
+    let _ = app.reconcile_tx.try_send(());
+
+    Ok(Json(molecule_params(&app, &mol, &program)?))
+}
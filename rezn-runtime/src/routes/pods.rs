@@ -0,0 +1,34 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use std::sync::Arc;
+
+use crate::auth::{Caller, Permission, Scope};
+use crate::pods::{self, ContainerDetails};
+use crate::routes::common::AppError;
+use crate::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/molecules/{mol}/pods/{pod}/containers",
+    params(
+        ("mol" = String, Path, description = "Molecule name"),
+        ("pod" = String, Path, description = "Pod name")
+    ),
+    responses(
+        (status = 200, body = Vec<ContainerDetails>, description = "Every container of the pod on reachable nodes, including exited and restarting ones"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Pods",
+)]
+pub async fn get_pod_containers_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path((mol, pod)): Path<(String, String)>,
+) -> Result<Json<Vec<ContainerDetails>>, AppError> {
+    caller.require(Permission::View, Scope::Molecule(&mol))?;
+
+    Ok(Json(pods::describe(&app.nodes, &mol, &pod).await))
+}
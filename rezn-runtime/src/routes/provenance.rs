@@ -0,0 +1,35 @@
+use axum::extract::{Path, State};
+use axum::Json;
+
+use std::sync::Arc;
+
+use crate::auth::{Caller, Permission, Scope};
+use crate::images::{self, Provenance};
+use crate::routes::common::{app_error, AppError};
+use crate::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/molecules/{mol}/provenance",
+    params(
+        ("mol" = String, Path, description = "Molecule name")
+    ),
+    responses(
+        (status = 200, body = Vec<Provenance>, description = "Resolved image digest of every container Rezn started for the molecule"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Images",
+)]
+pub async fn get_provenance_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Path(mol): Path<String>,
+) -> Result<Json<Vec<Provenance>>, AppError> {
+    caller.require(Permission::View, Scope::Molecule(&mol))?;
+
+    let records = images::provenance(&app.db, &mol).map_err(app_error)?;
+
+    Ok(Json(records))
+}
@@ -1,5 +1,9 @@
-use axum::extract::State;
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, HeaderValue};
 use axum::Json;
+use base64::{engine::general_purpose, Engine};
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::Deserialize;
 use std::sync::Arc;
 use utoipa::ToSchema;
@@ -7,7 +11,9 @@ use utoipa::ToSchema;
 use anyhow::Result;
 
 use crate::{
-    routes::common::{app_error, AppError},
+    auth::{Caller, Permission, Scope},
+    routes::common::{bad_request, store_error, AppError},
+    secret::{SecretEncoding, WriteMeta},
     AppState,
 };
 
@@ -15,6 +21,55 @@ use crate::{
 pub struct PutSecretPayload {
     name: String,
     secret: String,
+    /// How `secret` is written (default `utf8`).
+    #[serde(default)]
+    encoding: SecretEncoding,
+    /// MIME type stored with the value and served by `/secret/raw`.
+    content_type: Option<String>,
+    /// Replaces the secret's description.
+    description: Option<String>,
+    /// Stop serving this value after this time.
+    expires_at: Option<DateTime<Utc>>,
+    /// Or after this many seconds.
+    ttl: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawSecretQuery {
+    key: String,
+    description: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+    ttl: Option<u64>,
+}
+
+/// When a new value expires, from an absolute time or a TTL in seconds.
+fn expiry(
+    expires_at: Option<DateTime<Utc>>,
+    ttl: Option<u64>,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let expires_at = match (expires_at, ttl) {
+        (Some(_), Some(_)) => return Err(bad_request("give expires_at or ttl, not both")),
+        (Some(at), None) => at,
+        (None, Some(ttl)) => i64::try_from(ttl)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+            .ok_or_else(|| bad_request(format!("ttl {ttl} is too large")))?,
+        (None, None) => return Ok(None),
+    };
+    if expires_at <= Utc::now() {
+        return Err(bad_request(format!("expiry {expires_at} is in the past")));
+    }
+    Ok(Some(expires_at))
+}
+
+/// `content_type` if it can be served back as a `Content-Type` header.
+fn checked_content_type(content_type: Option<&str>) -> Result<Option<String>, AppError> {
+    match content_type {
+        Some(ct) if ct.contains('/') && HeaderValue::from_str(ct).is_ok() => Ok(Some(ct.into())),
+        Some(ct) => Err(bad_request(format!("invalid content type '{ct}'"))),
+        None => Ok(None),
+    }
 }
 
 #[utoipa::path(
@@ -26,20 +81,148 @@ pub struct PutSecretPayload {
         content_type = "application/json",
     ),
     responses(
-        (status = 200, body = bool)
+        (status = 200, body = bool),
+        (status = 400, description = "Invalid base64, content type or expiry"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 503, description = "The runtime is sealed")
     ),
+    security(("bearer" = [])),
     tag = "Secrets",
 )]
 pub async fn put_secret_handler(
     State(app): State<Arc<AppState>>,
+    caller: Caller,
     Json(payload): Json<PutSecretPayload>,
 ) -> Result<Json<bool>, AppError> {
+    caller.require(Permission::WriteSecrets, Scope::Secret(&payload.name))?;
+
     let name = payload.name;
-    let secret = payload.secret;
+    let secret = match payload.encoding {
+        SecretEncoding::Utf8 => payload.secret.into_bytes(),
+        SecretEncoding::Base64 => general_purpose::STANDARD
+            .decode(payload.secret.trim())
+            .map_err(|e| bad_request(format!("secret is not valid base64: {e}")))?,
+    };
+    let write = WriteMeta {
+        by: Some(caller.name.clone()),
+        content_type: checked_content_type(payload.content_type.as_deref())?,
+        description: payload.description,
+        expires_at: expiry(payload.expires_at, payload.ttl)?,
+    };
 
     app.secret_store
-        .put(&name, secret.as_bytes())
-        .map_err(app_error)?;
+        .put(&name, &secret, write)
+        .map_err(store_error)?;
+
+    // Pods reading this secret roll onto the new version.
+    let _ = app.reconcile_tx.try_send(());
 
     Ok(Json(true))
 }
+
+#[utoipa::path(
+    put,
+    path = "/secret/raw",
+    description = "Store the request body as the secret's new version, byte for byte. The \
+                   request's `Content-Type` is kept with it unless it is \
+                   `application/octet-stream`. A new value never inherits the previous \
+                   one's expiry.",
+    params(
+        ("key" = String, Query, description = "The full secret key name"),
+        ("description" = Option<String>, Query, description = "Replaces the secret's description"),
+        ("expires_at" = Option<String>, Query, description = "RFC 3339 time after which the value is no longer served"),
+        ("ttl" = Option<u64>, Query, description = "Or the number of seconds it is served for")
+    ),
+    request_body(
+        content = Vec<u8>,
+        description = "The secret value",
+        content_type = "application/octet-stream",
+    ),
+    responses(
+        (status = 200, body = u64, description = "The new version number"),
+        (status = 400, description = "Invalid content type or expiry"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn put_secret_raw_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<RawSecretQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<u64>, AppError> {
+    caller.require(Permission::WriteSecrets, Scope::Secret(&query.key))?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.to_str().unwrap_or_default())
+        .filter(|ct| *ct != "application/octet-stream");
+    let write = WriteMeta {
+        by: Some(caller.name.clone()),
+        content_type: checked_content_type(content_type)?,
+        description: query.description,
+        expires_at: expiry(query.expires_at, query.ttl)?,
+    };
+
+    let version = app
+        .secret_store
+        .put(&query.key, &body, write)
+        .map_err(store_error)?;
+
+    tracing::info!(
+        "{} stored {} bytes as version {} of secret '{}'",
+        caller.name,
+        body.len(),
+        version,
+        query.key
+    );
+
+    let _ = app.reconcile_tx.try_send(());
+
+    Ok(Json(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[test]
+    fn ttl_sets_an_expiry_in_the_future() {
+        let at = expiry(None, Some(60)).unwrap().unwrap();
+        let left = at - Utc::now();
+        assert!(left > TimeDelta::seconds(55) && left <= TimeDelta::seconds(60));
+    }
+
+    #[test]
+    fn oversized_ttls_are_bad_requests() {
+        for ttl in [
+            u64::MAX,
+            i64::MAX as u64,
+            i64::MAX as u64 / 1000 + 1,
+            1 << 43,
+        ] {
+            let err = expiry(None, Some(ttl)).unwrap_err();
+            assert_eq!(err.0, StatusCode::BAD_REQUEST, "ttl {ttl}");
+        }
+    }
+
+    #[test]
+    fn expiry_must_be_unambiguous_and_in_the_future() {
+        let past = Utc::now() - TimeDelta::seconds(1);
+        assert_eq!(
+            expiry(Some(past), None).unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            expiry(Some(Utc::now()), Some(60)).unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(expiry(None, None).unwrap(), None);
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    age_keys,
+    auth::{Caller, Permission, Scope},
+    routes::common::{app_error, bad_request, store_error, AppError},
+    AppState,
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RekeyReport {
+    /// Public keys secrets are now encrypted to, the runtime's own first.
+    pub recipients: Vec<String>,
+    /// Ciphertexts rewritten, current values and kept versions together.
+    pub reencrypted: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/secrets/recipients",
+    responses(
+        (status = 200, body = Vec<String>, description = "Public keys every secret is encrypted to, the runtime's own first"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn get_recipients_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<Vec<String>>, AppError> {
+    caller.require(Permission::View, Scope::Global)?;
+
+    app.secret_store
+        .recipient_keys()
+        .map(Json)
+        .map_err(store_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/secrets/rekey",
+    description = "Reload the extra recipients from `REZN_AGE_RECIPIENTS_FILE` and \
+                   `REZN_AGE_RECIPIENTS` and re-encrypt every secret to the new set in one \
+                   transaction.",
+    responses(
+        (status = 200, body = RekeyReport, description = "Re-key complete"),
+        (status = 400, description = "The recipient configuration is invalid"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn rekey_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<RekeyReport>, AppError> {
+    caller.require(Permission::Admin, Scope::Global)?;
+
+    let escrow = age_keys::load_escrow().map_err(|e| bad_request(format!("{e:#}")))?;
+    tracing::info!(
+        "{} requested a re-key to {} extra recipient(s)",
+        caller.name,
+        escrow.len()
+    );
+
+    let store = app.secret_store.clone();
+    let reencrypted = tokio::task::spawn_blocking(move || store.set_escrow(escrow))
+        .await
+        .map_err(app_error)?
+        .map_err(store_error)?;
+
+    Ok(Json(RekeyReport {
+        recipients: app.secret_store.recipient_keys().map_err(store_error)?,
+        reencrypted,
+    }))
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use chrono::Utc;
+
+use crate::{
+    audit::{self, AuditEntry},
+    auth::{Caller, Permission, Scope},
+    routes::common::{app_error, store_error, AppError},
+    secret::{self, RotationReport},
+    AppState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/secrets/rotate-identity",
+    description = "Generate a new age identity and re-encrypt every secret and kept version to it \
+                   in one transaction. The previous identity keeps decrypting for \
+                   `REZN_AGE_ROTATION_WINDOW` seconds and is then archived or destroyed.",
+    responses(
+        (status = 200, body = RotationReport, description = "Rotation complete"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 500, description = "Rotation failed; the previous identity is still current"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn rotate_identity_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<RotationReport>, AppError> {
+    caller.require(Permission::Admin, Scope::Global)?;
+
+    tracing::info!("{} requested an age identity rotation", caller.name);
+
+    let mut entry = AuditEntry {
+        at: Utc::now(),
+        user: caller.name.clone(),
+        action: "rotate-identity".to_string(),
+        target: "secrets".to_string(),
+        container: None,
+        node: None,
+        command: Vec::new(),
+        ended_at: None,
+        exit_code: None,
+        error: None,
+    };
+    let key = audit::record(&app.db, &entry).map_err(app_error)?;
+
+    let store = app.secret_store.clone();
+    let master = app.seal.master_key();
+    let result =
+        tokio::task::spawn_blocking(move || secret::rotate_identity(&store, master.as_ref()))
+            .await
+            .map_err(app_error)?;
+
+    entry.ended_at = Some(Utc::now());
+    entry.error = result.as_ref().err().map(|e| format!("{e:#}"));
+    if let Err(e) = audit::update(&app.db, &key, &entry) {
+        tracing::warn!("Failed to update audit entry {}: {:#}", key, e);
+    }
+
+    result.map(Json).map_err(store_error)
+}
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    audit::{self, AuditEntry},
+    auth::{Caller, Permission, Scope},
+    routes::common::{app_error, bad_request, AppError},
+    seal::{SealMode, SealStatus},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct InitSealPayload {
+    /// Shares needed to unseal, at least 2.
+    threshold: u8,
+    /// Shares to hand out.
+    shares: u8,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InitSealResponse {
+    pub threshold: u8,
+    /// Base64 unseal shares. They are shown only this once.
+    pub shares: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UnsealPayload {
+    /// One base64 unseal share.
+    share: String,
+}
+
+fn audit_seal(app: &AppState, caller: &Caller, action: &str, error: Option<String>) {
+    let mut entry = AuditEntry::new(&caller.name, action, "secrets");
+    entry.ended_at = Some(entry.at);
+    entry.error = error;
+    if let Err(e) = audit::record(&app.db, &entry) {
+        tracing::warn!("Failed to record {} audit entry: {:#}", action, e);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/seal/status",
+    responses(
+        (status = 200, body = SealStatus, description = "Seal mode, whether the runtime is sealed and unseal progress")
+    ),
+    tag = "Secrets",
+)]
+pub async fn get_seal_status_handler(State(app): State<Arc<AppState>>) -> Json<SealStatus> {
+    Json(app.seal.status(&app.secret_store))
+}
+
+#[utoipa::path(
+    post,
+    path = "/seal/init",
+    description = "Switch to sealed mode: encrypt the age identity files under a new key and \
+                   split that key into Shamir shares. The shares are returned once and never \
+                   stored. The runtime stays unsealed until the next restart or `/seal`.",
+    request_body(
+        content = InitSealPayload,
+        description = "Share count and unseal threshold",
+        content_type = "application/json",
+    ),
+    responses(
+        (status = 200, body = InitSealResponse, description = "Sealed mode enabled"),
+        (status = 400, description = "Invalid threshold or share count"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 409, description = "The runtime is already in sealed mode")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn init_seal_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<InitSealPayload>,
+) -> Result<Json<InitSealResponse>, AppError> {
+    caller.require(Permission::Admin, Scope::Global)?;
+
+    if payload.threshold < 2 || payload.shares < payload.threshold {
+        return Err(bad_request(format!(
+            "need 2 <= threshold <= shares, got threshold {} of {}",
+            payload.threshold, payload.shares
+        )));
+    }
+    if app.seal.status(&app.secret_store).mode == SealMode::Shamir {
+        return Err((
+            StatusCode::CONFLICT,
+            "The runtime is already in sealed mode".to_string(),
+        ));
+    }
+
+    tracing::info!(
+        "{} initialised the seal with {} of {} shares",
+        caller.name,
+        payload.threshold,
+        payload.shares
+    );
+
+    let state = Arc::clone(&app);
+    let result = tokio::task::spawn_blocking(move || {
+        state
+            .seal
+            .init(&state.secret_store, payload.threshold, payload.shares)
+    })
+    .await
+    .map_err(app_error)?;
+
+    audit_seal(
+        &app,
+        &caller,
+        "seal-init",
+        result.as_ref().err().map(|e| format!("{e:#}")),
+    );
+
+    let shares = result.map_err(|e| app_error(format!("{e:#}")))?;
+    Ok(Json(InitSealResponse {
+        threshold: payload.threshold,
+        shares,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/unseal",
+    description = "Submit one unseal share. Once the threshold is reached the key is rebuilt, the \
+                   identities are loaded and secrets become available. Shares that fail to \
+                   rebuild the key are kept; any `threshold` valid ones among them unseal.",
+    request_body(
+        content = UnsealPayload,
+        description = "One unseal share",
+        content_type = "application/json",
+    ),
+    responses(
+        (status = 200, body = SealStatus, description = "Share accepted; `sealed` is false once the threshold was reached"),
+        (status = 400, description = "Malformed share, wrong shares, or the runtime is not sealed"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn unseal_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<UnsealPayload>,
+) -> Result<Json<SealStatus>, AppError> {
+    caller.require(Permission::Admin, Scope::Global)?;
+
+    let state = Arc::clone(&app);
+    let result = tokio::task::spawn_blocking(move || {
+        state.seal.submit_share(&state.secret_store, &payload.share)
+    })
+    .await
+    .map_err(app_error)?;
+
+    let unsealed = matches!(&result, Ok(status) if !status.sealed);
+    if unsealed || result.is_err() {
+        audit_seal(
+            &app,
+            &caller,
+            "unseal",
+            result.as_ref().err().map(|e| format!("{e:#}")),
+        );
+    }
+    tracing::info!("{} submitted an unseal share", caller.name);
+
+    result.map(Json).map_err(|e| bad_request(format!("{e:#}")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/seal",
+    description = "Seal the runtime: forget the unseal key and the identities. Secret endpoints \
+                   and env resolution fail until enough shares are submitted to `/unseal`.",
+    responses(
+        (status = 200, body = SealStatus, description = "Runtime sealed"),
+        (status = 400, description = "The runtime is not in sealed mode"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn seal_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<SealStatus>, AppError> {
+    caller.require(Permission::Admin, Scope::Global)?;
+
+    let result = app.seal.seal(&app.secret_store);
+    audit_seal(
+        &app,
+        &caller,
+        "seal",
+        result.as_ref().err().map(|e| format!("{e:#}")),
+    );
+    result.map_err(|e| bad_request(format!("{e:#}")))?;
+
+    tracing::info!("{} sealed the runtime", caller.name);
+    Ok(Json(app.seal.status(&app.secret_store)))
+}
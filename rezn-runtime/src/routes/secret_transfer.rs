@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use age::x25519;
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    audit::{self, AuditEntry},
+    auth::{Caller, Permission, Scope},
+    routes::common::{bad_request, store_error, AppError},
+    secret::{ConflictPolicy, ExportFormat, ImportConflict, ImportReport, Sealed},
+    AppState,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExportPayload {
+    /// Public key (`age1…`) the export is encrypted to.
+    recipient: String,
+    /// Export this one secret.
+    key: Option<String>,
+    /// Export every secret under this prefix; `""` for the whole store.
+    prefix: Option<String>,
+    /// `age` (one key only) or `archive`. Defaults to `age` for a key and
+    /// `archive` for a prefix.
+    format: Option<ExportFormat>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportQuery {
+    /// `age` or `archive`.
+    format: ExportFormat,
+    /// Key to store a `.age` file under.
+    key: Option<String>,
+    /// What to do with keys that already exist (default `fail`).
+    #[serde(default)]
+    conflict: ConflictPolicy,
+}
+
+fn audit_transfer(app: &AppState, caller: &Caller, action: &str, target: &str, keys: &[String]) {
+    let mut entry = AuditEntry::new(&caller.name, action, target);
+    entry.ended_at = Some(entry.at);
+    entry.command = keys.to_vec();
+    if let Err(e) = audit::record(&app.db, &entry) {
+        tracing::warn!("Failed to record {} audit entry: {:#}", action, e);
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/secrets/export",
+    description = "Export one secret, or every secret under a prefix the caller may read, \
+                   encrypted to a caller-supplied age recipient. The response is a binary \
+                   `.age` file: the secret's value for the `age` format, a JSON archive of \
+                   keys and base64 values for `archive`.",
+    request_body(
+        content = ExportPayload,
+        description = "What to export and who to encrypt it to",
+        content_type = "application/json",
+    ),
+    responses(
+        (status = 200, description = "The encrypted export", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 400, description = "Invalid recipient, or neither/both of key and prefix"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Secret not found, or nothing under the prefix"),
+        (status = 410, description = "A secret to export has expired"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn export_secrets_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<ExportPayload>,
+) -> Result<Response, AppError> {
+    let recipient = payload
+        .recipient
+        .trim()
+        .parse::<x25519::Recipient>()
+        .map_err(|e| bad_request(format!("invalid age recipient: {e}")))?;
+
+    let (keys, target, format) = match (&payload.key, &payload.prefix) {
+        (Some(key), None) => {
+            caller.require(Permission::ReadSecrets, Scope::Secret(key))?;
+            if app.secret_store.get(key).map_err(store_error)?.is_none() {
+                return Err((StatusCode::NOT_FOUND, format!("Secret '{key}' not found")));
+            }
+            let format = payload.format.unwrap_or(ExportFormat::Age);
+            (vec![key.clone()], key.clone(), format)
+        }
+        (None, Some(prefix)) => {
+            let format = payload.format.unwrap_or(ExportFormat::Archive);
+            if format == ExportFormat::Age {
+                return Err(bad_request(
+                    "the age format holds one secret; export a key or use the archive format",
+                ));
+            }
+            let keys: Vec<String> = app
+                .secret_store
+                .keys()
+                .map_err(store_error)?
+                .into_iter()
+                .filter(|k| k.starts_with(prefix.as_str()))
+                .filter(|k| caller.can(Permission::ReadSecrets, Scope::Secret(k)))
+                .collect();
+            (keys, format!("{prefix}*"), format)
+        }
+        _ => return Err(bad_request("give exactly one of key and prefix")),
+    };
+
+    if keys.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("No readable secrets match '{target}'"),
+        ));
+    }
+    let data = app
+        .secret_store
+        .export(&keys, &recipient, format)
+        .map_err(store_error)?;
+
+    tracing::info!(
+        "{} exported {} secret(s) matching '{}' to {}",
+        caller.name,
+        keys.len(),
+        target,
+        recipient
+    );
+    audit_transfer(&app, &caller, "secret-export", &target, &keys);
+
+    let filename = match format {
+        ExportFormat::Age => format!("{}.age", target.replace('/', "_")),
+        ExportFormat::Archive => "secrets.age".to_string(),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/secrets/import",
+    description = "Import a `.age` file or archive encrypted to this runtime's public key (the \
+                   first of `/secrets/recipients`). Armored files are accepted. Every imported \
+                   value becomes a new version, re-encrypted to the current recipients.",
+    params(ImportQuery),
+    request_body(
+        content = Vec<u8>,
+        description = "The encrypted export",
+        content_type = "application/octet-stream",
+    ),
+    responses(
+        (status = 200, body = ImportReport, description = "Imported and skipped keys"),
+        (status = 400, description = "Not an export for this runtime, or a .age file without a key"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow writing every imported key"),
+        (status = 409, description = "Keys already exist and conflict is `fail`; nothing was imported"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn import_secrets_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<Json<ImportReport>, AppError> {
+    // Checked first so a sealed runtime is not reported as a bad export.
+    if app.secret_store.is_sealed() {
+        return Err(store_error(Sealed.into()));
+    }
+
+    let secrets = app
+        .secret_store
+        .open_export(&body, query.format, query.key.as_deref())
+        .map_err(|e| bad_request(format!("{e:#}")))?;
+    for key in secrets.keys() {
+        caller.require(Permission::WriteSecrets, Scope::Secret(key))?;
+    }
+
+    let keys: Vec<String> = secrets.keys().cloned().collect();
+    let report = app
+        .secret_store
+        .import(secrets, query.conflict, &caller.name)
+        .map_err(|e| match e.downcast_ref::<ImportConflict>() {
+            Some(conflict) => (StatusCode::CONFLICT, conflict.to_string()),
+            None => store_error(e),
+        })?;
+
+    tracing::info!(
+        "{} imported {} secret(s), skipped {}",
+        caller.name,
+        report.imported.len(),
+        report.skipped.len()
+    );
+    audit_transfer(
+        &app,
+        &caller,
+        "secret-import",
+        query.key.as_deref().unwrap_or("archive"),
+        &keys,
+    );
+
+    if !report.imported.is_empty() {
+        let _ = app.reconcile_tx.try_send(());
+    }
+
+    Ok(Json(report))
+}
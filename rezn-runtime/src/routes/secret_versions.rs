@@ -0,0 +1,117 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::auth::{Caller, Permission, Scope};
+use crate::routes::common::{store_error, AppError};
+use crate::secret::SecretVersion;
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct SecretQuery {
+    key: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RollbackPayload {
+    key: String,
+    /// The version whose value becomes current again.
+    version: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/secret/versions",
+    params(
+        ("key" = String, Query, description = "The full secret key name, e.g. rezn/prod/db_url")
+    ),
+    responses(
+        (status = 200, description = "Kept versions, oldest first; the last one is current", body = Vec<SecretVersion>),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Secret not found"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn get_secret_versions_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Query(query): Query<SecretQuery>,
+) -> Result<Json<Vec<SecretVersion>>, AppError> {
+    caller.require(Permission::ReadSecrets, Scope::Secret(&query.key))?;
+
+    let versions = app.secret_store.versions(&query.key).map_err(store_error)?;
+
+    if versions.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Secret '{}' not found", query.key),
+        ));
+    }
+
+    Ok(Json(versions))
+}
+
+#[utoipa::path(
+    post,
+    path = "/secret/rollback",
+    request_body(
+        content = RollbackPayload,
+        description = "Secret and version to roll back to",
+        content_type = "application/json",
+    ),
+    responses(
+        (status = 200, body = u64, description = "The new current version, holding the old value"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this"),
+        (status = 404, description = "Secret or version not found"),
+        (status = 503, description = "The runtime is sealed")
+    ),
+    security(("bearer" = [])),
+    tag = "Secrets",
+)]
+pub async fn rollback_secret_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<RollbackPayload>,
+) -> Result<Json<u64>, AppError> {
+    caller.require(Permission::WriteSecrets, Scope::Secret(&payload.key))?;
+
+    let known = app
+        .secret_store
+        .versions(&payload.key)
+        .map_err(store_error)?
+        .iter()
+        .any(|v| v.version == payload.version);
+    if !known {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!(
+                "Secret '{}' has no version {}",
+                payload.key, payload.version
+            ),
+        ));
+    }
+
+    let version = app
+        .secret_store
+        .rollback(&payload.key, payload.version, &caller.name)
+        .map_err(store_error)?;
+
+    tracing::info!(
+        "Secret '{}' rolled back to version {} (now version {})",
+        payload.key,
+        payload.version,
+        version
+    );
+
+    // Pods reading this secret roll onto the restored value.
+    let _ = app.reconcile_tx.try_send(());
+
+    Ok(Json(version))
+}
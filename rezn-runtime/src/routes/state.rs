@@ -2,25 +2,40 @@ use axum::body::Bytes;
 use axum::extract::State;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use common::types::DesiredMap;
+use common::types::{DesiredMap, Instruction};
+use serde::Serialize;
+use utoipa::ToSchema;
 
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
+use crate::auth::{Caller, Permission, Scope};
+use crate::modes::{self, DriftReport, MoleculeMode};
 use crate::routes::common::{app_error, AppError};
 use crate::AppState;
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MoleculeState {
+    mode: MoleculeMode,
+    /// Drift found by the last reconcile; absent until one has run.
+    drift: Option<DriftReport>,
+    instructions: Vec<Instruction>,
+}
+
 #[utoipa::path(
     get,
     path = "/state",
     responses(
-        (status = 200, body = Object)
+        (status = 200, body = BTreeMap<String, MoleculeState>, description = "Molecules the caller may view"),
+        (status = 401, description = "Missing or invalid API token")
     ),
+    security(("bearer" = [])),
     tag = "State",
 )]
 pub async fn get_state_handler(
     State(app): State<Arc<AppState>>,
-) -> Result<Json<DesiredMap>, AppError> {
+    caller: Caller,
+) -> Result<Json<BTreeMap<String, MoleculeState>>, AppError> {
     tracing::debug!("Retrieving current state");
 
     let desired: DesiredMap = match app.db.get("desired").map_err(app_error)? {
@@ -37,21 +52,47 @@ pub async fn get_state_handler(
         }
     };
 
-    Ok(Json(desired))
+    let mut state = BTreeMap::new();
+    for (mol, instructions) in desired {
+        if !caller.can(Permission::View, Scope::Molecule(&mol)) {
+            continue;
+        }
+        let mode = modes::mode(&app.db, &mol).map_err(app_error)?;
+        let drift = modes::drift(&app.db, &mol).map_err(app_error)?;
+
+        state.insert(
+            mol,
+            MoleculeState {
+                mode,
+                drift,
+                instructions,
+            },
+        );
+    }
+
+    Ok(Json(state))
 }
 
 #[utoipa::path(
     get,
     path = "/state/raw",
     responses(
-        (status = 200, body = Object)
+        (status = 200, body = Object),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
     ),
+    security(("bearer" = [])),
     tag = "State",
 )]
-pub async fn get_state_raw_handler(State(app): State<Arc<AppState>>) -> Result<Response, AppError> {
+pub async fn get_state_raw_handler(
+    State(app): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Response, AppError> {
+    caller.require(Permission::View, Scope::Global)?;
+
     let data = match app.db.get("desired").map_err(app_error)? {
         Some(ivec) => Bytes::from(ivec.to_vec()),
         None => Bytes::copy_from_slice(b"{}"),
     };
-    Ok(([("Content-Type", "application/json")], Bytes::from(data)).into_response())
+    Ok(([("Content-Type", "application/json")], data).into_response())
 }
@@ -3,6 +3,7 @@ use axum::Json;
 
 use std::sync::Arc;
 
+use crate::auth::{Caller, Permission, Scope};
 use crate::routes::common::AppError;
 use crate::{AppState, ContainerID, StatsMap, TimestampedStats};
 
@@ -10,14 +11,20 @@ use crate::{AppState, ContainerID, StatsMap, TimestampedStats};
     get,
     path = "/stats",
     responses(
-        (status = 200, body = BTreeMap<ContainerID, TimestampedStats>)
+        (status = 200, body = BTreeMap<ContainerID, TimestampedStats>),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
     ),
+    security(("bearer" = [])),
     tag = "Stats",
 )]
 #[axum::debug_handler]
 pub async fn get_stats_handler(
     State(app): State<Arc<AppState>>,
+    caller: Caller,
 ) -> Result<Json<StatsMap>, AppError> {
+    caller.require(Permission::View, Scope::Global)?;
+
     let stats = app.stats.read().await;
 
     Ok(Json(stats.clone()))
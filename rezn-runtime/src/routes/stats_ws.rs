@@ -5,26 +5,36 @@ use axum::{
     response::IntoResponse,
 };
 
-use crate::AppState;
+use crate::{
+    auth::{Caller, Permission, Scope},
+    routes::common::AppError,
+    AppState,
+};
 
 #[utoipa::path(
     get,
     path = "/stats/ws",
     description = "Exposes container stats via WS",
     responses(
-        (status = 101, description = "WebSocket upgrade initiated")
+        (status = 101, description = "WebSocket upgrade initiated"),
+        (status = 401, description = "Missing or invalid API token"),
+        (status = 403, description = "The caller's roles do not allow this")
     ),
+    security(("bearer" = [])),
     tag = "Streaming"
 )]
 pub async fn stats_ws_handler(
     State(app): State<Arc<AppState>>,
+    caller: Caller,
     ws: WebSocketUpgrade,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |mut socket| async move {
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Permission::View, Scope::Global)?;
+
+    Ok(ws.on_upgrade(move |mut socket| async move {
         let mut rx = app.stats_tx.subscribe();
         while let Ok(ev) = rx.recv().await {
             // Ignore errors if client closed
             let _ = socket.send(Message::Text(ev.to_string().into())).await;
         }
-    })
+    }))
 }
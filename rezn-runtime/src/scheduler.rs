@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::nodes::Node;
+
+/// Spreads replicas over the reachable nodes.
+///
+/// Placement prefers the node holding the fewest replicas of the pod being
+/// scheduled, then the node with the lowest overall load, so a pod's replicas
+/// end up on as many distinct hosts as its selector and capacities allow.
+pub struct Scheduler {
+    nodes: Vec<Arc<Node>>,
+    load: HashMap<String, usize>,
+}
+
+impl Scheduler {
+    /// `load` is the number of managed containers currently observed per node.
+    pub fn new(nodes: &[Arc<Node>], load: HashMap<String, usize>) -> Self {
+        Self {
+            nodes: nodes.iter().filter(|n| n.is_reachable()).cloned().collect(),
+            load,
+        }
+    }
+
+    fn load_of(&self, node: &str) -> usize {
+        self.load.get(node).copied().unwrap_or_default()
+    }
+
+    /// Pick a node for one new replica. `placed` holds the pod's current
+    /// replica count per node and is updated with the decision.
+    pub fn place(
+        &mut self,
+        selector: &HashMap<String, String>,
+        placed: &mut HashMap<String, usize>,
+    ) -> Option<Arc<Node>> {
+        let node = self
+            .nodes
+            .iter()
+            .filter(|n| n.matches(selector))
+            .filter(|n| n.capacity.is_none_or(|cap| self.load_of(&n.name) < cap))
+            .min_by_key(|n| {
+                (
+                    placed.get(&n.name).copied().unwrap_or_default(),
+                    self.load_of(&n.name),
+                    n.name.clone(),
+                )
+            })?
+            .clone();
+
+        *self.load.entry(node.name.clone()).or_default() += 1;
+        *placed.entry(node.name.clone()).or_default() += 1;
+
+        Some(node)
+    }
+
+    /// Pick the node to remove one replica from: the one holding the most
+    /// replicas of the pod, ties broken by the highest overall load.
+    pub fn evict(&mut self, placed: &mut HashMap<String, usize>) -> Option<Arc<Node>> {
+        let node = self
+            .nodes
+            .iter()
+            .filter(|n| placed.get(&n.name).copied().unwrap_or_default() > 0)
+            .max_by_key(|n| {
+                (
+                    placed.get(&n.name).copied().unwrap_or_default(),
+                    self.load_of(&n.name),
+                )
+            })?
+            .clone();
+
+        if let Some(l) = self.load.get_mut(&node.name) {
+            *l = l.saturating_sub(1);
+        }
+        if let Some(p) = placed.get_mut(&node.name) {
+            *p -= 1;
+        }
+
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{NodeConfig, NodeRegistry};
+
+    /// `(name, capacity, labels)` of an in-memory node.
+    type NodeSpec<'a> = (&'a str, Option<usize>, &'a [(&'a str, &'a str)]);
+
+    fn nodes(specs: &[NodeSpec]) -> NodeRegistry {
+        let configs = specs
+            .iter()
+            .map(|(name, capacity, labels)| {
+                let labels: HashMap<&str, &str> = labels.iter().copied().collect();
+                serde_json::from_value::<NodeConfig>(serde_json::json!({
+                    "name": name,
+                    "backend": "memory",
+                    "capacity": capacity,
+                    "labels": labels,
+                }))
+                .unwrap()
+            })
+            .collect();
+        NodeRegistry::new(configs).unwrap()
+    }
+
+    fn place_n(scheduler: &mut Scheduler, n: usize) -> HashMap<String, usize> {
+        let mut placed = HashMap::new();
+        for _ in 0..n {
+            scheduler.place(&HashMap::new(), &mut placed).unwrap();
+        }
+        placed
+    }
+
+    #[test]
+    fn spreads_replicas_over_nodes() {
+        let registry = nodes(&[("a", None, &[]), ("b", None, &[]), ("c", None, &[])]);
+        let mut scheduler = Scheduler::new(registry.nodes(), HashMap::new());
+
+        let placed = place_n(&mut scheduler, 6);
+
+        assert_eq!(placed.len(), 3);
+        assert!(placed.values().all(|n| *n == 2));
+    }
+
+    #[test]
+    fn prefers_the_least_loaded_node() {
+        let registry = nodes(&[("a", None, &[]), ("b", None, &[])]);
+        let load = HashMap::from([("a".to_string(), 5)]);
+        let mut scheduler = Scheduler::new(registry.nodes(), load);
+
+        let node = scheduler.place(&HashMap::new(), &mut HashMap::new());
+
+        assert_eq!(node.unwrap().name, "b");
+    }
+
+    #[test]
+    fn honours_selectors() {
+        let registry = nodes(&[("a", None, &[("zone", "x")]), ("b", None, &[("zone", "y")])]);
+        let mut scheduler = Scheduler::new(registry.nodes(), HashMap::new());
+        let selector = HashMap::from([("zone".to_string(), "y".to_string())]);
+        let mut placed = HashMap::new();
+
+        for _ in 0..3 {
+            let node = scheduler.place(&selector, &mut placed).unwrap();
+            assert_eq!(node.name, "b");
+        }
+
+        let missing = HashMap::from([("zone".to_string(), "z".to_string())]);
+        assert!(scheduler.place(&missing, &mut placed).is_none());
+    }
+
+    #[test]
+    fn stops_at_capacity() {
+        let registry = nodes(&[("a", Some(1), &[]), ("b", Some(2), &[])]);
+        let mut scheduler = Scheduler::new(registry.nodes(), HashMap::new());
+
+        let placed = place_n(&mut scheduler, 3);
+
+        assert_eq!(placed["a"], 1);
+        assert_eq!(placed["b"], 2);
+        assert!(scheduler
+            .place(&HashMap::new(), &mut HashMap::new())
+            .is_none());
+    }
+
+    #[test]
+    fn fails_over_from_unreachable_nodes() {
+        let registry = nodes(&[("a", None, &[]), ("b", None, &[])]);
+        registry.get("a").unwrap().set_reachable(false);
+        let mut scheduler = Scheduler::new(registry.nodes(), HashMap::new());
+
+        let placed = place_n(&mut scheduler, 2);
+
+        assert_eq!(placed.get("a"), None);
+        assert_eq!(placed["b"], 2);
+
+        // Once it answers again it takes new replicas.
+        registry.get("a").unwrap().set_reachable(true);
+        let mut scheduler = Scheduler::new(registry.nodes(), HashMap::new());
+        let mut placed = placed;
+        assert_eq!(
+            scheduler.place(&HashMap::new(), &mut placed).unwrap().name,
+            "a"
+        );
+    }
+
+    #[test]
+    fn evicts_from_the_most_crowded_node() {
+        let registry = nodes(&[("a", None, &[]), ("b", None, &[])]);
+        let mut scheduler = Scheduler::new(registry.nodes(), HashMap::new());
+        let mut placed = HashMap::from([("a".to_string(), 1), ("b".to_string(), 3)]);
+
+        assert_eq!(scheduler.evict(&mut placed).unwrap().name, "b");
+        assert_eq!(placed["b"], 2);
+    }
+}
@@ -0,0 +1,491 @@
+//! Sealed startup.
+//!
+//! In sealed mode the age identity files hold the identity encrypted
+//! (ChaCha20-Poly1305) under a random 256-bit master key that is never
+//! written down: when the seal is initialised the key is split into Shamir
+//! shares and handed out once. The runtime then boots with the secret store
+//! sealed, and secret endpoints and env resolution fail until `threshold`
+//! shares have been submitted through `/unseal`. `/seal` locks it again.
+
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use sharks::{Share, Sharks};
+use utoipa::ToSchema;
+use zeroize::Zeroizing;
+
+use crate::{age_keys, secret::SecretStore};
+
+/// Submitted shares are capped at this many per share handed out, so wrong
+/// ones cannot pile up without bound.
+const MAX_PENDING_PER_SHARE: usize = 2;
+
+/// Combinations of pending shares tried per submission. Each one reads and
+/// decrypts the identity files, so wrong shares must not make a submission
+/// try every subset.
+const MAX_UNSEAL_ATTEMPTS: usize = 256;
+
+/// Marks a sealed identity file and its format.
+const SEAL_FORMAT: &str = "rezn-shamir-v1";
+
+/// An identity file's content in sealed mode.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SealedIdentity {
+    sealed: String,
+    pub threshold: u8,
+    pub shares: u8,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl SealedIdentity {
+    /// `Some` when `raw` is a sealed identity file rather than a plain one.
+    pub fn parse(raw: &str) -> Option<Self> {
+        serde_json::from_str::<Self>(raw)
+            .ok()
+            .filter(|s| s.sealed == SEAL_FORMAT)
+    }
+}
+
+/// The key protecting sealed identity files, with the split it came from.
+#[derive(Clone)]
+pub struct MasterKey {
+    bytes: Zeroizing<[u8; 32]>,
+    threshold: u8,
+    shares: u8,
+}
+
+impl MasterKey {
+    fn generate(threshold: u8, shares: u8) -> Self {
+        Self {
+            bytes: Zeroizing::new(rand::random()),
+            threshold,
+            shares,
+        }
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Result<SealedIdentity> {
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(self.bytes.as_slice()));
+        let nonce: [u8; 12] = rand::random();
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("sealing identity failed"))?;
+
+        Ok(SealedIdentity {
+            sealed: SEAL_FORMAT.to_string(),
+            threshold: self.threshold,
+            shares: self.shares,
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        })
+    }
+
+    pub fn open(&self, sealed: &SealedIdentity) -> Result<Zeroizing<Vec<u8>>> {
+        let nonce = general_purpose::STANDARD
+            .decode(&sealed.nonce)
+            .context("malformed nonce in sealed identity")?;
+        let ciphertext = general_purpose::STANDARD
+            .decode(&sealed.ciphertext)
+            .context("malformed ciphertext in sealed identity")?;
+        if nonce.len() != 12 {
+            return Err(anyhow!("malformed nonce in sealed identity"));
+        }
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(self.bytes.as_slice()));
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("the unseal key does not open the sealed identity"))
+    }
+
+    /// Base64 Shamir shares, any `threshold` of which rebuild the key.
+    fn split(&self) -> Vec<String> {
+        Sharks(self.threshold)
+            .dealer(self.bytes.as_slice())
+            .take(self.shares as usize)
+            .map(|share| general_purpose::STANDARD.encode(Vec::from(&share)))
+            .collect()
+    }
+
+    fn recover(threshold: u8, shares: u8, submitted: &[Share]) -> Result<Self> {
+        let secret = Zeroizing::new(
+            Sharks(threshold)
+                .recover(submitted)
+                .map_err(|e| anyhow!("cannot combine unseal shares: {e}"))?,
+        );
+        let bytes: [u8; 32] = secret
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("unseal shares rebuild a key of the wrong length"))?;
+
+        Ok(Self {
+            bytes: Zeroizing::new(bytes),
+            threshold,
+            shares,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SealMode {
+    /// The identity is a plain file; the runtime never seals.
+    Plain,
+    /// The identity is encrypted under a Shamir-split key.
+    Shamir,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SealStatus {
+    pub mode: SealMode,
+    pub sealed: bool,
+    /// Shares needed to unseal.
+    pub threshold: Option<u8>,
+    /// Shares handed out when the seal was initialised.
+    pub shares: Option<u8>,
+    /// Distinct shares submitted towards the next unseal.
+    pub progress: usize,
+}
+
+struct SealState {
+    /// `(threshold, shares)` in sealed mode.
+    split: Option<(u8, u8)>,
+    /// Held while unsealed, so rotations can seal the new identity.
+    master: Option<MasterKey>,
+    pending: Vec<Share>,
+}
+
+fn status(state: &SealState, store: &SecretStore) -> SealStatus {
+    SealStatus {
+        mode: match state.split {
+            Some(_) => SealMode::Shamir,
+            None => SealMode::Plain,
+        },
+        sealed: store.is_sealed(),
+        threshold: state.split.map(|(t, _)| t),
+        shares: state.split.map(|(_, n)| n),
+        progress: state.pending.len(),
+    }
+}
+
+/// Find the key `threshold` of `pending`, including the last one, rebuild
+/// and `open` accepts. Combinations whose share indices repeat are skipped,
+/// and at most [`MAX_UNSEAL_ATTEMPTS`] are tried.
+fn find_key<T>(
+    threshold: u8,
+    shares: u8,
+    pending: &[Share],
+    open: impl Fn(&MasterKey) -> Result<T>,
+) -> Result<(MasterKey, T)> {
+    let (newest, older) = pending.split_last().context("no unseal shares")?;
+    let mut last_error = anyhow!("no combination of distinct shares");
+
+    let candidates = Combinations::new(older.len(), threshold as usize - 1)
+        .map(|combination| {
+            let mut picked = vec![newest.clone()];
+            picked.extend(combination.iter().map(|&i| older[i].clone()));
+            picked
+        })
+        .filter(|picked| {
+            let mut indices: Vec<u8> = picked.iter().map(|s| s.x.0).collect();
+            indices.sort_unstable();
+            indices.dedup();
+            indices.len() == picked.len()
+        });
+
+    for picked in candidates.take(MAX_UNSEAL_ATTEMPTS) {
+        let opened = MasterKey::recover(threshold, shares, &picked)
+            .and_then(|master| Ok((open(&master)?, master)));
+        match opened {
+            Ok((opened, master)) => return Ok((master, opened)),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+/// The `k`-element subsets of `0..n` as ascending indices, in lexicographic
+/// order, built one at a time.
+struct Combinations {
+    n: usize,
+    next: Option<Vec<usize>>,
+}
+
+impl Combinations {
+    fn new(n: usize, k: usize) -> Self {
+        Self {
+            n,
+            next: (k <= n).then(|| (0..k).collect()),
+        }
+    }
+}
+
+impl Iterator for Combinations {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        let current = self.next.take()?;
+
+        // Bump the rightmost index that still has room, and reset the ones
+        // after it to follow on directly.
+        let k = current.len();
+        let mut following = current.clone();
+        if let Some(i) = (0..k).rev().find(|&i| following[i] < self.n - k + i) {
+            following[i] += 1;
+            for j in i + 1..k {
+                following[j] = following[j - 1] + 1;
+            }
+            self.next = Some(following);
+        }
+
+        Some(current)
+    }
+}
+
+/// Sealed-mode bookkeeping: the split in use, the unseal key while unsealed
+/// and the shares submitted so far.
+pub struct Seal {
+    state: Mutex<SealState>,
+}
+
+impl Seal {
+    /// Plain mode, or sealed mode with the split found on disk.
+    pub fn new(split: Option<(u8, u8)>) -> Self {
+        Self {
+            state: Mutex::new(SealState {
+                split,
+                master: None,
+                pending: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn status(&self, store: &SecretStore) -> SealStatus {
+        status(&self.state.lock().unwrap(), store)
+    }
+
+    /// The unseal key, in sealed mode while unsealed.
+    pub fn master_key(&self) -> Option<MasterKey> {
+        self.state.lock().unwrap().master.clone()
+    }
+
+    /// Switch a plain runtime to sealed mode: encrypt the identity files under
+    /// a fresh key and return its shares. They are not stored anywhere.
+    pub fn init(&self, store: &SecretStore, threshold: u8, shares: u8) -> Result<Vec<String>> {
+        if threshold < 2 || shares < threshold {
+            return Err(anyhow!(
+                "need 2 <= threshold <= shares, got threshold {threshold} of {shares}"
+            ));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.split.is_some() {
+            return Err(anyhow!("the runtime is already in sealed mode"));
+        }
+        if store.is_sealed() {
+            return Err(anyhow!("the secret store is sealed"));
+        }
+
+        let master = MasterKey::generate(threshold, shares);
+        age_keys::seal_identity_files(&master)?;
+        tracing::info!(
+            "[seal] Identity files sealed; {} of {} shares needed to unseal",
+            threshold,
+            shares
+        );
+
+        let split = master.split();
+        state.split = Some((threshold, shares));
+        state.master = Some(master);
+        Ok(split)
+    }
+
+    /// Add one base64 share. Once `threshold` distinct shares are in, rebuild
+    /// the key, load the identities and unseal the store.
+    ///
+    /// The search for a working combination runs without the state lock, so
+    /// status and seal calls are not held up by it.
+    pub fn submit_share(&self, store: &SecretStore, share: &str) -> Result<SealStatus> {
+        let (threshold, shares, pending, bytes) = {
+            let mut state = self.state.lock().unwrap();
+            let Some((threshold, shares)) = state.split else {
+                return Err(anyhow!("the runtime is not in sealed mode"));
+            };
+            if !store.is_sealed() {
+                return Err(anyhow!("the runtime is already unsealed"));
+            }
+
+            let bytes = general_purpose::STANDARD
+                .decode(share.trim())
+                .context("share is not valid base64")?;
+            let share = Share::try_from(bytes.as_slice())
+                .map_err(|e| anyhow!("malformed unseal share: {e}"))?;
+
+            if state.pending.iter().any(|s| Vec::from(s) == bytes) {
+                return Ok(status(&state, store));
+            }
+            if state.pending.len() >= MAX_PENDING_PER_SHARE * shares as usize {
+                return Err(anyhow!(
+                    "too many unseal shares submitted; /seal discards them"
+                ));
+            }
+            state.pending.push(share);
+            tracing::info!(
+                "[seal] Unseal progress {}/{}",
+                state.pending.len(),
+                threshold
+            );
+
+            if state.pending.len() < threshold as usize {
+                return Ok(status(&state, store));
+            }
+            (threshold, shares, state.pending.clone(), bytes)
+        };
+
+        // A wrong share must not discard the good ones: keep them all and
+        // try the combinations the new share makes possible.
+        let found = find_key(threshold, shares, &pending, |master| {
+            age_keys::load_keyring(Some(master))
+        });
+
+        let mut state = self.state.lock().unwrap();
+        let (master, keyring) = match found {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("[seal] Unseal failed, shares kept: {:#}", e);
+                return Err(e.context(
+                    "the shares submitted so far do not unseal the runtime; \
+                     they are kept, submit another",
+                ));
+            }
+        };
+        if !state.pending.iter().any(|s| Vec::from(s) == bytes) {
+            return Err(anyhow!("the runtime was sealed again while unsealing"));
+        }
+        if store.is_sealed() {
+            store.unseal(keyring)?;
+        }
+        state.master = Some(master);
+        state.pending.clear();
+        tracing::info!("[seal] Runtime unsealed");
+
+        Ok(status(&state, store))
+    }
+
+    /// Forget the unseal key and the identities until the next unseal.
+    pub fn seal(&self, store: &SecretStore) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.split.is_none() {
+            return Err(anyhow!(
+                "the runtime is not in sealed mode; initialise the seal first"
+            ));
+        }
+
+        store.seal();
+        state.master = None;
+        state.pending.clear();
+        tracing::info!("[seal] Runtime sealed");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(share: &str) -> Share {
+        let bytes = general_purpose::STANDARD.decode(share).unwrap();
+        Share::try_from(bytes.as_slice()).unwrap()
+    }
+
+    /// A share with the index of `share` but a different value.
+    fn forged(share: &Share) -> Share {
+        let mut bytes = Vec::from(share);
+        bytes[1] ^= 0xff;
+        Share::try_from(bytes.as_slice()).unwrap()
+    }
+
+    /// `open` for a trial that only accepts `master`.
+    fn opens(master: &MasterKey) -> impl Fn(&MasterKey) -> Result<()> + '_ {
+        |candidate| {
+            if *candidate.bytes == *master.bytes {
+                Ok(())
+            } else {
+                Err(anyhow!("wrong key"))
+            }
+        }
+    }
+
+    #[test]
+    fn combinations_cover_every_subset_once() {
+        assert_eq!(
+            Combinations::new(3, 0).collect::<Vec<_>>(),
+            vec![Vec::<usize>::new()]
+        );
+        assert_eq!(
+            Combinations::new(4, 2).collect::<Vec<_>>(),
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3]
+            ]
+        );
+        assert_eq!(Combinations::new(1, 2).count(), 0);
+        assert_eq!(Combinations::new(20, 10).count(), 184_756);
+    }
+
+    #[test]
+    fn a_bad_share_among_good_ones_is_skipped() {
+        let master = MasterKey::generate(3, 5);
+        let good: Vec<Share> = master.split().iter().map(|s| decode(s)).collect();
+        let stranger: Vec<Share> = MasterKey::generate(3, 5)
+            .split()
+            .iter()
+            .map(|s| decode(s))
+            .collect();
+
+        // A forged share that reuses a good index, and one from another split.
+        let pending = vec![
+            good[0].clone(),
+            forged(&good[1]),
+            stranger[3].clone(),
+            good[1].clone(),
+            good[2].clone(),
+        ];
+        let (found, ()) = find_key(3, 5, &pending, opens(&master)).unwrap();
+        assert_eq!(*found.bytes, *master.bytes);
+
+        // Nothing works while the newest share is the bad one.
+        let pending = vec![good[0].clone(), good[1].clone(), stranger[4].clone()];
+        assert!(find_key(3, 5, &pending, opens(&master)).is_err());
+    }
+
+    #[test]
+    fn unseal_attempts_are_capped() {
+        let master = MasterKey::generate(5, 10);
+        let strangers: Vec<Share> = (0..4)
+            .flat_map(|_| MasterKey::generate(5, 10).split())
+            .map(|s| decode(&s))
+            .take(MAX_PENDING_PER_SHARE * 10)
+            .collect();
+
+        let tried = std::cell::Cell::new(0);
+        let result = find_key(5, 10, &strangers, |candidate| {
+            tried.set(tried.get() + 1);
+            opens(&master)(candidate)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(tried.get(), MAX_UNSEAL_ATTEMPTS);
+    }
+}
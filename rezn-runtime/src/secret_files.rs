@@ -0,0 +1,113 @@
+//! Secrets mounted into pods as files.
+//!
+//! A pod's `secret_files` are read from the store when a replica starts and
+//! handed to the executor with the container, which puts them on in-memory
+//! mounts. The versions each replica got are kept under
+//! `secret-files/{container}`; when an unpinned secret moves on, reconcile
+//! rewrites the files in place instead of replacing the replica.
+
+use anyhow::{anyhow, Context, Result};
+use common::types::{Instruction, SecretFile};
+use sha2::{Digest, Sha256};
+use sled::Db;
+
+use crate::{
+    container_runtime::ContainerFile,
+    secret::{self, SecretStore, SECRET_FILE_MODE},
+};
+
+/// Reject pods whose secret files have relative paths, invalid modes or
+/// clash with each other. Run on a resolved program; declarations that do not
+/// parse are reported by reconcile.
+pub fn validate_program(program: &[Instruction]) -> Result<()> {
+    for item in program.iter().filter(|i| i.kind == "pod") {
+        let Some(files) = item.fields.as_ref().and_then(|f| f.get("secret_files")) else {
+            continue;
+        };
+        let Ok(files) = serde_json::from_value::<Vec<SecretFile>>(files.clone()) else {
+            continue;
+        };
+
+        for (i, file) in files.iter().enumerate() {
+            mode(file).map_err(|e| anyhow!("pod '{}': {:#}", item.name, e))?;
+            if files[..i].iter().any(|f| f.path == file.path) {
+                return Err(anyhow!(
+                    "pod '{}': secret file path '{}' is used twice",
+                    item.name,
+                    file.path
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read `files` from `store` into what the executor writes.
+pub fn resolve(store: &SecretStore, files: &[SecretFile]) -> Result<Vec<ContainerFile>> {
+    files
+        .iter()
+        .map(|file| {
+            Ok(ContainerFile {
+                mode: mode(file)?,
+                path: file.path.clone(),
+                content: secret::read_ref(store, &file.secret, file.version)?,
+                uid: file.uid,
+                gid: file.gid,
+            })
+        })
+        .collect()
+}
+
+/// The file's permissions, once its path is known to be usable.
+fn mode(file: &SecretFile) -> Result<u32> {
+    if !file.path.starts_with('/') || file.path.ends_with('/') {
+        return Err(anyhow!("secret file path '{}' is not absolute", file.path));
+    }
+    // Its directory becomes the mount.
+    if file.path.rfind('/') == Some(0) {
+        return Err(anyhow!("secret file '{}' cannot live in /", file.path));
+    }
+    let Some(mode) = &file.mode else {
+        return Ok(SECRET_FILE_MODE);
+    };
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|m| *m <= 0o777)
+        .ok_or_else(|| anyhow!("invalid mode '{mode}' for {}", file.path))
+}
+
+/// Short hash of the versions `files` resolve to right now. Reads metadata
+/// only, so it works while the store is sealed.
+pub fn versions(store: &SecretStore, files: &[SecretFile]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for file in files {
+        let version = match file.version {
+            Some(v) => Some(v),
+            None => store.current_version(&file.secret)?,
+        };
+        hasher.update(
+            serde_json::json!([file.secret, file.path, file.mode, version])
+                .to_string()
+                .as_bytes(),
+        );
+    }
+    Ok(hex::encode(&hasher.finalize()[..6]))
+}
+
+/// The versions last written into `container`.
+pub fn applied(db: &Db, container: &str) -> Result<Option<String>> {
+    db.get(format!("secret-files/{container}"))?
+        .map(|v| String::from_utf8(v.to_vec()).context("Failed to parse secret file versions"))
+        .transpose()
+}
+
+pub fn record(db: &Db, container: &str, versions: &str) -> Result<()> {
+    db.insert(format!("secret-files/{container}"), versions.as_bytes())?;
+    Ok(())
+}
+
+pub fn forget(db: &Db, container: &str) -> Result<()> {
+    db.remove(format!("secret-files/{container}"))?;
+    Ok(())
+}
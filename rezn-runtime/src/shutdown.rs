@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use common::types::PreStopHook;
+use url::Url;
+
+use crate::{container_runtime::ContainerSummary, nodes::Node};
+
+/// Upper bound for a pre-stop hook when the pod declares no grace period.
+const DEFAULT_HOOK_TIMEOUT: u64 = 30;
+
+/// Drain and remove one replica.
+///
+/// Runs the pre-stop hook, then asks the executor to stop the container with
+/// whatever is left of the grace period, and only force-removes it once the
+/// stop has returned.
+pub async fn graceful_remove(
+    node: &Node,
+    container: &ContainerSummary,
+    grace: Option<u64>,
+    pre_stop: Option<&PreStopHook>,
+) {
+    let Some(name) = container.name() else {
+        tracing::warn!("Container {} has no name?!", container.id);
+        return;
+    };
+
+    let started = Instant::now();
+
+    if let Some(hook) = pre_stop {
+        let budget = Duration::from_secs(grace.unwrap_or(DEFAULT_HOOK_TIMEOUT));
+        match run_hook(node, container, name, hook, budget).await {
+            Ok(()) => tracing::debug!("Pre-stop hook for {} finished", name),
+            Err(e) => tracing::warn!("Pre-stop hook for {} failed: {:#}", name, e),
+        }
+    }
+
+    let remaining = grace.map(|g| g.saturating_sub(started.elapsed().as_secs()));
+
+    if let Err(e) = node.client.stop_container(name, remaining).await {
+        tracing::warn!("Failed to stop {}: {}", name, e);
+    }
+
+    if let Err(e) = node.client.remove_container(name).await {
+        tracing::warn!("Failed to remove {}: {}", name, e);
+    }
+}
+
+async fn run_hook(
+    node: &Node,
+    container: &ContainerSummary,
+    name: &str,
+    hook: &PreStopHook,
+    budget: Duration,
+) -> Result<()> {
+    match hook {
+        PreStopHook::Exec { command } => {
+            let res = node.client.exec_container(name, command, budget).await?;
+            match res.exit_code {
+                Some(0) | None => Ok(()),
+                Some(code) => Err(anyhow!("command exited with {code}")),
+            }
+        }
+        PreStopHook::Http { path, port } => {
+            let public = container
+                .public_port(*port)
+                .ok_or_else(|| anyhow!("container port {port} is not published"))?;
+
+            // Socket and in-memory nodes are local to the runtime.
+            let base = Url::parse(&node.url).context("invalid node URL")?;
+            let host = base.host_str().unwrap_or("127.0.0.1");
+
+            let url = format!(
+                "http://{}:{}/{}",
+                host,
+                public,
+                path.trim_start_matches('/')
+            );
+
+            reqwest::Client::new()
+                .get(&url)
+                .timeout(budget)
+                .send()
+                .await
+                .with_context(|| format!("GET {url}"))?
+                .error_for_status()?;
+
+            Ok(())
+        }
+    }
+}
@@ -12,10 +12,7 @@ use url::Url;
 
 use crate::{AppState, Stats, TimestampedStats};
 
-pub async fn container_stats_handler(app: Arc<AppState>) -> anyhow::Result<()> {
-    let ws_url = std::env::var("STATS_WS_URL")
-        .unwrap_or_else(|_| "ws://localhost:3000/stats/ws".to_string());
-
+pub async fn container_stats_handler(app: Arc<AppState>, ws_url: String) -> anyhow::Result<()> {
     let url =
         Url::parse(&ws_url).with_context(|| format!("Invalid WebSocket URL: '{}'", ws_url))?;
 
@@ -94,7 +91,7 @@ pub async fn container_stats_handler(app: Arc<AppState>) -> anyhow::Result<()> {
 pub async fn push_stats_to_ws_clients(app: Arc<AppState>) {
     let stats = app.stats.read().await;
 
-    match serde_json::to_value(&stats.clone()) {
+    match serde_json::to_value(stats.clone()) {
         Ok(serialized) => {
             let _ = app.stats_tx.send(serialized);
         }
@@ -0,0 +1,160 @@
+//! HTTPS for the API, with optional client certificates.
+//!
+//! `REZN_TLS_CERT` and `REZN_TLS_KEY` (PEM files) switch the listener to TLS.
+//! `REZN_TLS_CLIENT_CA` additionally asks clients for a certificate signed by
+//! that CA; the SHA-256 of the certificate presented identifies the caller
+//! (see [`crate::auth`]). Clients without one can still use a bearer token.
+
+use std::{env, io, net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use axum::{extract::connect_info::Connected, serve::IncomingStream};
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use sha2::{Digest, Sha256};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{crypto::ring, server::WebPkiClientVerifier, RootCertStore, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+/// Handshakes taking longer than this are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The TLS server configuration, if `REZN_TLS_CERT` and `REZN_TLS_KEY` are set.
+pub fn config_from_env() -> Result<Option<Arc<ServerConfig>>> {
+    let (Ok(cert_path), Ok(key_path)) = (env::var("REZN_TLS_CERT"), env::var("REZN_TLS_KEY"))
+    else {
+        return Ok(None);
+    };
+
+    let certs = CertificateDer::pem_file_iter(&cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow!("reading certificates from {cert_path}: {e}"))?;
+    let key = PrivateKeyDer::from_pem_file(&key_path)
+        .map_err(|e| anyhow!("reading private key from {key_path}: {e}"))?;
+
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+
+    let builder = match env::var("REZN_TLS_CLIENT_CA") {
+        Ok(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(&ca_path)
+                .map_err(|e| anyhow!("reading client CA from {ca_path}: {e}"))?
+            {
+                let cert = cert.map_err(|e| anyhow!("reading client CA from {ca_path}: {e}"))?;
+                roots
+                    .add(cert)
+                    .with_context(|| format!("adding client CA from {ca_path}"))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .context("building the client certificate verifier")?;
+
+            tracing::info!("[tls] Accepting client certificates signed by {}", ca_path);
+            builder.with_client_cert_verifier(verifier)
+        }
+        Err(_) => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .context("loading the TLS certificate")?;
+    // WebSocket upgrades (exec, logs, stats) need HTTP/1.1.
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(Some(Arc::new(config)))
+}
+
+/// A listener yielding connections whose TLS handshake has completed.
+///
+/// Handshakes run in their own tasks so a slow client cannot hold up others.
+pub struct TlsListener {
+    rx: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub fn new(tcp: TcpListener, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let local_addr = tcp.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, rx) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match tcp.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!("[tls] Accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(tls)) => {
+                            let _ = tx.send((tls, addr)).await;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::debug!("[tls] Handshake with {} failed: {}", addr, e)
+                        }
+                        Err(_) => tracing::debug!("[tls] Handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(Self { rx, local_addr })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.rx.recv().await {
+            Some(conn) => conn,
+            // The accept task only ends with the runtime.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// The other end of an API connection.
+#[derive(Debug, Clone)]
+pub struct Peer {
+    /// Hex SHA-256 of the client certificate, if one was presented.
+    pub cert_sha256: Option<String>,
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for Peer {
+    fn connect_info(_stream: IncomingStream<'_, TcpListener>) -> Self {
+        Peer { cert_sha256: None }
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        let (_, conn) = stream.io().get_ref();
+        Peer {
+            cert_sha256: conn
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| hex::encode(Sha256::digest(cert.as_ref()))),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+
+pub fn run(args: &[String]) -> Result<()> {
+    let [url, name, json_path] = args else {
+        eprintln!("Usage: reznctl apply <rezn-url> <name> <signed-ir.json>");
+        std::process::exit(1);
+    };
+
+    let raw = fs::read_to_string(json_path).context("reading IR file")?;
+
+    // Make the HTTP request to the Rezn Runtime
+    let client = reqwest::blocking::Client::new();
+    let payload = serde_json::json!({
+        "name": name,
+        "instruction_wrapper": serde_json::from_str::<Value>(&raw).context("parsing JSON")?,
+    });
+
+    print!("{}", payload);
+
+    // The runtime authorizes applies per molecule; the token comes from REZN_TOKEN.
+    let mut request = client.post(url).json(&payload);
+    if let Ok(token) = std::env::var("REZN_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.send().context("sending HTTP request")?;
+
+    response.error_for_status().context("HTTP request failed")?;
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, bail, Context, Result};
+use common::exec::{self, ExecControl, ExecStart};
+use crossterm::terminal;
+use futures_util::{SinkExt, StreamExt};
+use std::{
+    env,
+    io::{IsTerminal, Write},
+};
+use tokio::{
+    io::AsyncReadExt,
+    signal::unix::{signal, SignalKind},
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+};
+
+/// Puts the local terminal in raw mode for as long as it lives.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode().context("switching terminal to raw mode")?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct ExecArgs {
+    url: String,
+    mol: String,
+    pod: String,
+    replica: Option<String>,
+    tty: bool,
+    command: Vec<String>,
+}
+
+fn parse(args: &[String]) -> Result<ExecArgs> {
+    let (opts, command) = match args.iter().position(|a| a == "--") {
+        Some(i) => (&args[..i], args[i + 1..].to_vec()),
+        None => (args, Vec::new()),
+    };
+
+    let mut positional = Vec::new();
+    let mut replica = None;
+    let mut tty = std::io::stdin().is_terminal();
+    let mut opts = opts.iter();
+    while let Some(arg) = opts.next() {
+        match arg.as_str() {
+            "--replica" => {
+                replica = Some(opts.next().context("--replica needs a value")?.clone());
+            }
+            "--no-tty" => tty = false,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let [url, mol, pod] = <[String; 3]>::try_from(positional)
+        .map_err(|_| anyhow!("expected <rezn-url> <molecule> <pod>"))?;
+    if command.is_empty() {
+        bail!("no command given after --");
+    }
+
+    Ok(ExecArgs {
+        url,
+        mol,
+        pod,
+        replica,
+        tty,
+        command,
+    })
+}
+
+/// Run a command in a pod replica and return its exit code.
+///
+/// The API token is read from `REZN_TOKEN`.
+pub async fn run(args: &[String]) -> Result<i32> {
+    let args = match parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", crate::USAGE);
+            return Ok(1);
+        }
+    };
+
+    let base = args.url.trim_end_matches('/');
+    let ws_url = match base.split_once("://") {
+        Some(("https", rest)) => format!("wss://{rest}"),
+        Some(("http", rest)) => format!("ws://{rest}"),
+        _ => bail!("unsupported rezn URL '{}'", args.url),
+    };
+    let ws_url = format!("{ws_url}/molecules/{}/pods/{}/exec", args.mol, args.pod);
+
+    let mut req = ws_url.into_client_request()?;
+    if let Ok(token) = env::var("REZN_TOKEN") {
+        req.headers_mut()
+            .insert("authorization", format!("Bearer {token}").parse()?);
+    }
+
+    let (ws, _) = connect_async(req)
+        .await
+        .context("connecting to the rezn runtime")?;
+    let (mut sink, mut stream) = ws.split();
+
+    let (cols, rows) = if args.tty {
+        terminal::size().map_or((None, None), |(c, r)| (Some(c), Some(r)))
+    } else {
+        (None, None)
+    };
+    let start = ExecControl::Start(ExecStart {
+        replica: args.replica,
+        command: args.command,
+        tty: args.tty,
+        cols,
+        rows,
+    });
+    sink.send(Message::Text(serde_json::to_string(&start)?))
+        .await?;
+
+    let raw = if args.tty {
+        Some(RawMode::enable()?)
+    } else {
+        None
+    };
+    let mut winch = signal(SignalKind::window_change())?;
+    let mut stdin = tokio::io::stdin();
+    let mut stdin_open = true;
+    let mut buf = vec![0u8; 4096];
+
+    let outcome = loop {
+        tokio::select! {
+            n = stdin.read(&mut buf), if stdin_open => {
+                let n = n.unwrap_or(0);
+                if n == 0 {
+                    stdin_open = false;
+                }
+                // An empty frame tells the runtime stdin is closed.
+                sink.send(Message::Binary(exec::frame(exec::STDIN, &buf[..n]))).await?;
+            }
+            _ = winch.recv(), if args.tty => {
+                if let Ok((cols, rows)) = terminal::size() {
+                    let resize = ExecControl::Resize { cols, rows };
+                    sink.send(Message::Text(serde_json::to_string(&resize)?)).await?;
+                }
+            }
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Binary(data))) => match data.split_first() {
+                    Some((&exec::STDOUT, rest)) => {
+                        let mut out = std::io::stdout();
+                        out.write_all(rest)?;
+                        out.flush()?;
+                    }
+                    Some((&exec::STDERR, rest)) => {
+                        let mut err = std::io::stderr();
+                        err.write_all(rest)?;
+                        err.flush()?;
+                    }
+                    _ => {}
+                },
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(ExecControl::Exit { code }) => break Ok(code),
+                    Ok(ExecControl::Error { message }) => break Err(message),
+                    _ => {}
+                },
+                Some(Ok(Message::Close(_))) | None => {
+                    break Err("connection closed before the command exited".to_string())
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break Err(e.to_string()),
+            },
+        }
+    };
+
+    drop(raw);
+    let _ = sink.send(Message::Close(None)).await;
+
+    match outcome {
+        Ok(Some(code)) => Ok(code as i32),
+        Ok(None) => {
+            eprintln!("reznctl: exit status of the command is unknown");
+            Ok(1)
+        }
+        Err(message) => {
+            eprintln!("reznctl: {message}");
+            Ok(1)
+        }
+    }
+}
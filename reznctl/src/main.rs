@@ -1,35 +1,33 @@
-use anyhow::{Context, Result};
-use serde_json::Value;
-use std::{env, fs};
+use anyhow::Result;
+use std::env;
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 4 {
-        eprintln!("Usage: reznctl-apply <rezn-url> <name> <signed-ir.json>");
-        std::process::exit(1);
-    }
-
-    let url = &args[1];
-    let name = &args[2];
-    let json_path = &args[3];
-    let raw = fs::read_to_string(json_path).context("reading IR file")?;
-
-    // Make the HTTP request to the Rezn Runtime
-    let client = reqwest::blocking::Client::new();
-    let payload = serde_json::json!({
-        "name": name,
-        "instruction_wrapper": serde_json::from_str::<Value>(&raw).context("parsing JSON")?,
-    });
+mod apply;
+mod exec;
+mod secrets;
 
-    print!("{}", payload.to_string());
+const USAGE: &str = "Usage:
+  reznctl apply <rezn-url> <name> <signed-ir.json>
+  reznctl exec <rezn-url> <molecule> <pod> [--replica NAME] [--no-tty] -- <command>...
+  reznctl secrets export <rezn-url> --recipient <age1...> (--key KEY | --prefix PREFIX | --all)
+                         [--format age|archive] --out PATH
+  reznctl secrets import <rezn-url> PATH [--key KEY] [--format age|archive]
+                         [--conflict fail|skip|overwrite]";
 
-    let response = client
-        .post(&*url)
-        .json(&payload)
-        .send()
-        .context("sending HTTP request")?;
-
-    response.error_for_status().context("HTTP request failed")?;
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
 
-    Ok(())
+    match args.get(1).map(String::as_str) {
+        Some("apply") => apply::run(&args[2..]),
+        Some("exec") => {
+            let code = tokio::runtime::Runtime::new()?.block_on(exec::run(&args[2..]))?;
+            std::process::exit(code);
+        }
+        Some("secrets") => secrets::run(&args[2..]),
+        // The original form, before subcommands existed.
+        _ if args.len() == 4 => apply::run(&args[1..]),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(1);
+        }
+    }
 }
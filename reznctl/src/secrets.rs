@@ -0,0 +1,228 @@
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde_json::Value;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+const USAGE: &str = "Usage:
+  reznctl secrets export <rezn-url> --recipient <age1...> (--key KEY | --prefix PREFIX | --all)
+                         [--format age|archive] --out PATH
+  reznctl secrets import <rezn-url> PATH [--key KEY] [--format age|archive]
+                         [--conflict fail|skip|overwrite]";
+
+pub fn run(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--flag value` pairs, in the order given.
+type Options = Vec<(String, String)>;
+
+/// Splits `args` into positionals and the options named in `flags`.
+fn parse(args: &[String], flags: &[&str]) -> Result<(Vec<String>, Options)> {
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some("all") => options.push(("all".to_string(), String::new())),
+            Some(flag) if flags.contains(&flag) => {
+                let value = args
+                    .next()
+                    .with_context(|| format!("--{flag} needs a value"))?;
+                options.push((flag.to_string(), value.clone()));
+            }
+            Some(flag) => bail!("unknown option --{flag}"),
+            None => positional.push(arg.clone()),
+        }
+    }
+    Ok((positional, options))
+}
+
+fn option<'a>(options: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    options
+        .iter()
+        .find(|(flag, _)| flag == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Adds the token from REZN_TOKEN, if set.
+fn authorized(request: RequestBuilder) -> RequestBuilder {
+    match env::var("REZN_TOKEN") {
+        Ok(token) => request.bearer_auth(token),
+        Err(_) => request,
+    }
+}
+
+/// Like `error_for_status`, but keeps the runtime's error message.
+fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().unwrap_or_default();
+    bail!("{status}: {body}")
+}
+
+fn export(args: &[String]) -> Result<()> {
+    let (positional, options) = parse(args, &["recipient", "key", "prefix", "format", "out"])?;
+    let [url] = positional.as_slice() else {
+        bail!("expected <rezn-url>");
+    };
+    let base = url.trim_end_matches('/');
+    let recipient = option(&options, "recipient").context("--recipient is required")?;
+    let out = PathBuf::from(option(&options, "out").context("--out is required")?);
+    let format = option(&options, "format");
+
+    let prefix = match (option(&options, "key"), option(&options, "prefix")) {
+        (Some(key), None) if option(&options, "all").is_none() => {
+            let format = format.unwrap_or("age");
+            return export_one(base, recipient, "key", key, format, &out);
+        }
+        (None, Some(prefix)) if option(&options, "all").is_none() => prefix,
+        (None, None) if option(&options, "all").is_some() => "",
+        _ => bail!("give exactly one of --key, --prefix and --all"),
+    };
+
+    if format != Some("age") {
+        return export_one(base, recipient, "prefix", prefix, "archive", &out);
+    }
+
+    // One .age file per secret, laid out by key under `out`.
+    let client = Client::new();
+    let keys: Vec<String> = check(
+        authorized(client.get(format!("{base}/secrets")))
+            .send()
+            .context("listing secrets")?,
+    )?
+    .json()
+    .context("parsing the secret list")?;
+    let keys: Vec<_> = keys.into_iter().filter(|k| k.starts_with(prefix)).collect();
+    if keys.is_empty() {
+        bail!("no readable secrets match '{prefix}'");
+    }
+
+    for key in &keys {
+        let path = out.join(format!("{key}.age"));
+        export_one(base, recipient, "key", key, "age", &path)?;
+    }
+    Ok(())
+}
+
+/// Export one key, or everything under a prefix, into the file at `out`.
+fn export_one(
+    base: &str,
+    recipient: &str,
+    selector: &str,
+    value: &str,
+    format: &str,
+    out: &Path,
+) -> Result<()> {
+    let mut payload = serde_json::json!({ "recipient": recipient, "format": format });
+    payload[selector] = Value::from(value);
+
+    let response = authorized(Client::new().post(format!("{base}/secrets/export")))
+        .json(&payload)
+        .send()
+        .context("sending HTTP request")?;
+    let data = check(response)?.bytes()?;
+
+    if let Some(dir) = out.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    fs::write(out, &data).with_context(|| format!("writing {}", out.display()))?;
+    let what = match (selector, value) {
+        ("prefix", "") => "all secrets".to_string(),
+        ("prefix", prefix) => format!("{prefix}*"),
+        (_, key) => key.to_string(),
+    };
+    println!("{} -> {}", what, out.display());
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<()> {
+    let (positional, options) = parse(args, &["key", "format", "conflict"])?;
+    let [url, path] = positional.as_slice() else {
+        bail!("expected <rezn-url> PATH");
+    };
+    let base = url.trim_end_matches('/');
+    let conflict = option(&options, "conflict").unwrap_or("fail");
+    let path = Path::new(path);
+
+    if path.is_dir() {
+        // Each .age file holds one secret, keyed by its path under `path`.
+        // Conflicts are handled per file.
+        let mut files = Vec::new();
+        collect_age_files(path, &mut files)?;
+        if files.is_empty() {
+            bail!("no .age files under {}", path.display());
+        }
+        for file in files {
+            let key = file
+                .strip_prefix(path)?
+                .with_extension("")
+                .to_string_lossy()
+                .into_owned();
+            import_one(base, &file, "age", Some(&key), conflict)?;
+        }
+        return Ok(());
+    }
+
+    let key = option(&options, "key");
+    let format =
+        option(&options, "format").unwrap_or(if key.is_some() { "age" } else { "archive" });
+    import_one(base, path, format, key, conflict)
+}
+
+fn import_one(
+    base: &str,
+    file: &Path,
+    format: &str,
+    key: Option<&str>,
+    conflict: &str,
+) -> Result<()> {
+    let data = fs::read(file).with_context(|| format!("reading {}", file.display()))?;
+
+    let mut query = vec![("format", format), ("conflict", conflict)];
+    if let Some(key) = key {
+        query.push(("key", key));
+    }
+    let response = authorized(Client::new().post(format!("{base}/secrets/import")))
+        .query(&query)
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .body(data)
+        .send()
+        .context("sending HTTP request")?;
+    let report: Value = check(response)?
+        .json()
+        .context("parsing the import report")?;
+
+    for key in report["imported"].as_array().into_iter().flatten() {
+        println!("imported {}", key.as_str().unwrap_or_default());
+    }
+    for key in report["skipped"].as_array().into_iter().flatten() {
+        println!("skipped {} (exists)", key.as_str().unwrap_or_default());
+    }
+    Ok(())
+}
+
+fn collect_age_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_age_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "age") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(())
+}